@@ -0,0 +1,53 @@
+/// A preset of [`AccuracySettings`], trading accuracy for speed.
+///
+/// The STAT write bug, the OAM corruption bug and idle skipping aren't implemented yet, so no
+/// profile sets them. The FIFO renderer is the only renderer, so every profile uses it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AccuracyProfile {
+  /// Disables the hardware quirks, and would enable idle skipping once it's implemented.
+  ///
+  /// Sets:
+  ///   - `wave_ram_corruption`: `false`
+  Fast,
+  /// Emulates the hardware quirks that are cheap to emulate.
+  ///
+  /// Sets:
+  ///   - `wave_ram_corruption`: `true`
+  #[default]
+  Balanced,
+  /// Emulates every hardware quirk that is implemented.
+  ///
+  /// Sets:
+  ///   - `wave_ram_corruption`: `true`
+  Accurate,
+}
+
+/// The individual accuracy settings of the emulator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccuracySettings {
+  /// Whether triggering the wave channel right before it reads wave RAM corrupts wave RAM,
+  /// like on DMG.
+  pub wave_ram_corruption: bool,
+}
+
+impl AccuracyProfile {
+  /// Returns the [`AccuracySettings`] that this profile sets.
+  pub const fn settings(self) -> AccuracySettings {
+    match self {
+      AccuracyProfile::Fast => AccuracySettings {
+        wave_ram_corruption: false,
+      },
+      AccuracyProfile::Balanced | AccuracyProfile::Accurate => AccuracySettings {
+        wave_ram_corruption: true,
+      },
+    }
+  }
+}
+
+impl Default for AccuracySettings {
+  fn default() -> Self {
+    AccuracyProfile::default().settings()
+  }
+}
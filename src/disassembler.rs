@@ -0,0 +1,194 @@
+use std::fmt;
+
+/// A decoded instruction, which displays as its GBZ80 mnemonic, like `LD A, [HL]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+  /// The address that the instruction is at.
+  pub address: u16,
+  /// The opcode of the instruction.
+  pub opcode: u8,
+  /// The bytes that follow the opcode.
+  pub operand: Operand,
+}
+
+/// The bytes that follow an opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+  /// The instruction is only its opcode.
+  None,
+  /// An 8-bit operand, which is the second opcode for the instructions prefixed with `0xCB`.
+  U8(u8),
+  /// A 16-bit operand.
+  U16(u16),
+}
+
+impl Instruction {
+  /// Decodes the instruction at the address from its bytes. Only as many bytes as the
+  /// instruction needs are used.
+  pub fn decode(address: u16, bytes: [u8; 3]) -> Self {
+    let opcode = bytes[0];
+    let operand = match operand_length(opcode) {
+      0 => Operand::None,
+      1 => Operand::U8(bytes[1]),
+      _ => Operand::U16(u16::from_le_bytes([bytes[1], bytes[2]])),
+    };
+
+    Self {
+      address,
+      opcode,
+      operand,
+    }
+  }
+
+  /// Returns the number of bytes that the instruction takes up, including the opcode.
+  pub fn length(&self) -> u8 {
+    1 + operand_length(self.opcode)
+  }
+}
+
+impl fmt::Display for Instruction {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.opcode == PREFIX_OPCODE {
+      let Operand::U8(opcode) = self.operand else {
+        unreachable!("prefixed instructions have a second opcode");
+      };
+
+      return write_prefixed_mnemonic(f, opcode);
+    }
+
+    let template = MNEMONICS[self.opcode as usize];
+    let Some((start, token)) = find_operand_token(template) else {
+      return f.write_str(template);
+    };
+
+    f.write_str(&template[..start])?;
+
+    match (token, self.operand) {
+      ("n8", Operand::U8(value)) => write!(f, "${value:02X}")?,
+      ("a8", Operand::U8(value)) => write!(f, "$FF{value:02X}")?,
+      ("s8", Operand::U8(value)) => write!(f, "{:+}", value as i8)?,
+      ("e8", Operand::U8(value)) => {
+        // Relative jumps are relative to the end of the instruction
+        let target = self
+          .address
+          .wrapping_add(self.length() as u16)
+          .wrapping_add(value as i8 as u16);
+
+        write!(f, "${target:04X}")?
+      }
+      ("n16" | "a16", Operand::U16(value)) => write!(f, "${value:04X}")?,
+      _ => unreachable!("the operand matches the size of its token"),
+    }
+
+    f.write_str(&template[start + token.len()..])
+  }
+}
+
+/// Writes the mnemonic of an instruction prefixed with `0xCB`, from its second opcode.
+fn write_prefixed_mnemonic(f: &mut fmt::Formatter<'_>, opcode: u8) -> fmt::Result {
+  const ROTATIONS: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+  const BIT_OPERATIONS: [&str; 3] = ["BIT", "RES", "SET"];
+
+  let register = REGISTERS[(opcode & 0x07) as usize];
+  let bit = (opcode >> 3) & 0x07;
+
+  match opcode >> 6 {
+    0 => write!(f, "{} {register}", ROTATIONS[bit as usize]),
+    operation => write!(
+      f,
+      "{} {bit}, {register}",
+      BIT_OPERATIONS[operation as usize - 1]
+    ),
+  }
+}
+
+/// Returns the number of bytes that follow the opcode.
+fn operand_length(opcode: u8) -> u8 {
+  match opcode {
+    // `STOP` skips the byte after it
+    PREFIX_OPCODE | STOP_OPCODE => 1,
+    _ => match find_operand_token(MNEMONICS[opcode as usize]) {
+      Some((_, "n16" | "a16")) => 2,
+      Some(_) => 1,
+      None => 0,
+    },
+  }
+}
+
+/// Finds the placeholder for the operand in the mnemonic, with where it starts.
+fn find_operand_token(template: &str) -> Option<(usize, &'static str)> {
+  OPERAND_TOKENS
+    .iter()
+    .find_map(|&token| template.find(token).map(|start| (start, token)))
+}
+
+/// The opcode that prefixes the extended instructions.
+const PREFIX_OPCODE: u8 = 0xCB;
+/// The opcode of `STOP`.
+const STOP_OPCODE: u8 = 0x10;
+/// The placeholders for operands in the mnemonics, where the 16-bit ones come first since
+/// they contain an 8-bit one.
+const OPERAND_TOKENS: [&str; 6] = ["n16", "a16", "n8", "a8", "e8", "s8"];
+/// The registers that the lower 3 bits of an opcode select, in order.
+const REGISTERS: [&str; 8] = ["B", "C", "D", "E", "H", "L", "[HL]", "A"];
+/// The mnemonics of the unprefixed opcodes. `n8` and `n16` are immediates, `a8` is an offset
+/// from `0xFF00`, `a16` is an address, `e8` is a relative jump and `s8` is a signed offset
+/// with its sign.
+#[rustfmt::skip]
+const MNEMONICS: [&str; 256] = [
+  // 0x00
+  "NOP", "LD BC, n16", "LD [BC], A", "INC BC", "INC B", "DEC B", "LD B, n8", "RLCA",
+  "LD [a16], SP", "ADD HL, BC", "LD A, [BC]", "DEC BC", "INC C", "DEC C", "LD C, n8", "RRCA",
+  // 0x10
+  "STOP", "LD DE, n16", "LD [DE], A", "INC DE", "INC D", "DEC D", "LD D, n8", "RLA",
+  "JR e8", "ADD HL, DE", "LD A, [DE]", "DEC DE", "INC E", "DEC E", "LD E, n8", "RRA",
+  // 0x20
+  "JR NZ, e8", "LD HL, n16", "LD [HL+], A", "INC HL", "INC H", "DEC H", "LD H, n8", "DAA",
+  "JR Z, e8", "ADD HL, HL", "LD A, [HL+]", "DEC HL", "INC L", "DEC L", "LD L, n8", "CPL",
+  // 0x30
+  "JR NC, e8", "LD SP, n16", "LD [HL-], A", "INC SP", "INC [HL]", "DEC [HL]", "LD [HL], n8", "SCF",
+  "JR C, e8", "ADD HL, SP", "LD A, [HL-]", "DEC SP", "INC A", "DEC A", "LD A, n8", "CCF",
+  // 0x40
+  "LD B, B", "LD B, C", "LD B, D", "LD B, E", "LD B, H", "LD B, L", "LD B, [HL]", "LD B, A",
+  "LD C, B", "LD C, C", "LD C, D", "LD C, E", "LD C, H", "LD C, L", "LD C, [HL]", "LD C, A",
+  // 0x50
+  "LD D, B", "LD D, C", "LD D, D", "LD D, E", "LD D, H", "LD D, L", "LD D, [HL]", "LD D, A",
+  "LD E, B", "LD E, C", "LD E, D", "LD E, E", "LD E, H", "LD E, L", "LD E, [HL]", "LD E, A",
+  // 0x60
+  "LD H, B", "LD H, C", "LD H, D", "LD H, E", "LD H, H", "LD H, L", "LD H, [HL]", "LD H, A",
+  "LD L, B", "LD L, C", "LD L, D", "LD L, E", "LD L, H", "LD L, L", "LD L, [HL]", "LD L, A",
+  // 0x70
+  "LD [HL], B", "LD [HL], C", "LD [HL], D", "LD [HL], E", "LD [HL], H", "LD [HL], L", "HALT",
+  "LD [HL], A",
+  "LD A, B", "LD A, C", "LD A, D", "LD A, E", "LD A, H", "LD A, L", "LD A, [HL]", "LD A, A",
+  // 0x80
+  "ADD A, B", "ADD A, C", "ADD A, D", "ADD A, E", "ADD A, H", "ADD A, L", "ADD A, [HL]",
+  "ADD A, A",
+  "ADC A, B", "ADC A, C", "ADC A, D", "ADC A, E", "ADC A, H", "ADC A, L", "ADC A, [HL]",
+  "ADC A, A",
+  // 0x90
+  "SUB A, B", "SUB A, C", "SUB A, D", "SUB A, E", "SUB A, H", "SUB A, L", "SUB A, [HL]",
+  "SUB A, A",
+  "SBC A, B", "SBC A, C", "SBC A, D", "SBC A, E", "SBC A, H", "SBC A, L", "SBC A, [HL]",
+  "SBC A, A",
+  // 0xA0
+  "AND A, B", "AND A, C", "AND A, D", "AND A, E", "AND A, H", "AND A, L", "AND A, [HL]",
+  "AND A, A",
+  "XOR A, B", "XOR A, C", "XOR A, D", "XOR A, E", "XOR A, H", "XOR A, L", "XOR A, [HL]",
+  "XOR A, A",
+  // 0xB0
+  "OR A, B", "OR A, C", "OR A, D", "OR A, E", "OR A, H", "OR A, L", "OR A, [HL]", "OR A, A",
+  "CP A, B", "CP A, C", "CP A, D", "CP A, E", "CP A, H", "CP A, L", "CP A, [HL]", "CP A, A",
+  // 0xC0
+  "RET NZ", "POP BC", "JP NZ, a16", "JP a16", "CALL NZ, a16", "PUSH BC", "ADD A, n8", "RST $00",
+  "RET Z", "RET", "JP Z, a16", "PREFIX", "CALL Z, a16", "CALL a16", "ADC A, n8", "RST $08",
+  // 0xD0
+  "RET NC", "POP DE", "JP NC, a16", "DB $D3", "CALL NC, a16", "PUSH DE", "SUB A, n8", "RST $10",
+  "RET C", "RETI", "JP C, a16", "DB $DB", "CALL C, a16", "DB $DD", "SBC A, n8", "RST $18",
+  // 0xE0
+  "LDH [a8], A", "POP HL", "LDH [C], A", "DB $E3", "DB $E4", "PUSH HL", "AND A, n8", "RST $20",
+  "ADD SP, s8", "JP HL", "LD [a16], A", "DB $EB", "DB $EC", "DB $ED", "XOR A, n8", "RST $28",
+  // 0xF0
+  "LDH A, [a8]", "POP AF", "LDH A, [C]", "DI", "DB $F4", "PUSH AF", "OR A, n8", "RST $30",
+  "LD HL, SPs8", "LD SP, HL", "LD A, [a16]", "EI", "DB $FC", "DB $FD", "CP A, n8", "RST $38",
+];
@@ -1,49 +1,498 @@
-use crate::hardware::{Cpu, Hardware, clock::TCycle};
+use std::{collections::BTreeSet, fmt};
+
+use crate::{
+  accuracy::{AccuracyProfile, AccuracySettings},
+  disassembler::Instruction,
+  hardware::{
+    Cpu, Hardware,
+    clock::TCycle,
+    cpu::{CGB_REGISTER_A, CpuState},
+    joypad::{Button, ButtonAction, ButtonState},
+    ppu::{Frame, PpuMode},
+  },
+  trace::{InstructionTrace, TraceEntry},
+  watchdog::{StuckCpuReport, Watchdog},
+};
+
+use crate::interrupts::Interrupt;
+#[cfg(feature = "save-states")]
+use crate::save_state::{SAVE_STATE_VERSION, SaveStateError};
 
 /// The Gameboy emulator.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Emulator {
   /// The CPU for the Gameboy.
   cpu: Cpu,
   /// The hardware components of the Gameboy.
   pub hardware: Hardware,
+  /// The accuracy settings being used.
+  accuracy: AccuracySettings,
+  /// The watchdog that detects a stuck CPU, if enabled.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  watchdog: Option<Watchdog>,
+  /// The callback that gets called after each frame is completed, if any.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  post_frame_hook: Option<PostFrameHook>,
+  /// The callback that gets called before each instruction is executed, if any.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  instruction_hook: Option<InstructionHook>,
+  /// The trace of the last executed instructions, if enabled.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  trace: Option<InstructionTrace>,
+  /// The addresses that get read into each [`Observation`].
+  observed_addresses: Vec<u16>,
+}
+
+/// The state of the Gameboy after an [`Emulator::rl_step`].
+#[derive(Debug, Clone)]
+pub struct Observation {
+  /// The frame that was completed.
+  pub frame: Frame,
+  /// The values at the observed addresses, in the order they were given.
+  pub memory: Vec<u8>,
+}
+
+/// What the CPU did during an [`Emulator::step_instruction`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstructionStep {
+  /// What got executed.
+  pub executed: Executed,
+  /// The number of T-cycles that it took, including any time spent halted.
+  pub cycles: usize,
+}
+
+/// What the CPU executed during an [`Emulator::step_instruction`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Executed {
+  /// The CPU executed the instruction.
+  Instruction(Instruction),
+  /// The CPU dispatched the interrupt instead of executing an instruction. It's `None` when the
+  /// dispatch got cancelled by `IE` changing while pushing the program counter, which jumps
+  /// to `0x0000`.
+  Interrupt(Option<Interrupt>),
+}
+
+/// A callback that gets called with each completed frame.
+struct PostFrameHook(Box<dyn FnMut(&Frame)>);
+
+impl fmt::Debug for PostFrameHook {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("PostFrameHook")
+  }
+}
+
+/// A callback that gets called with the CPU and the hardware at each instruction boundary.
+struct InstructionHook(Box<InstructionCallback>);
+
+/// The function behind an [`InstructionHook`].
+type InstructionCallback = dyn FnMut(&Cpu, &Hardware);
+
+impl fmt::Debug for InstructionHook {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("InstructionHook")
+  }
 }
 
 impl Emulator {
-  /// Creates a new [`Emulator`].
+  /// Creates a new [`Emulator`], using the default [`AccuracyProfile`].
   pub fn new(cpu: Cpu, hardware: Hardware) -> Self {
-    Self { cpu, hardware }
+    let mut emulator = Self {
+      cpu,
+      hardware,
+      accuracy: AccuracySettings::default(),
+      watchdog: None,
+      post_frame_hook: None,
+      instruction_hook: None,
+      trace: None,
+      observed_addresses: Vec::new(),
+    };
+
+    emulator.set_accuracy_settings(AccuracySettings::default());
+
+    emulator
+  }
+
+  /// Applies the settings of the [`AccuracyProfile`].
+  pub fn set_accuracy_profile(&mut self, profile: AccuracyProfile) {
+    self.set_accuracy_settings(profile.settings());
+  }
+
+  /// Applies the individual [`AccuracySettings`].
+  pub fn set_accuracy_settings(&mut self, settings: AccuracySettings) {
+    self
+      .hardware
+      .apu
+      .set_wave_ram_corruption(settings.wave_ram_corruption);
+
+    self.accuracy = settings;
+  }
+
+  /// Gets the CPU, for reading its registers.
+  pub fn cpu(&self) -> &Cpu {
+    &self.cpu
+  }
+
+  /// Gets the CPU, for setting its registers up.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn cpu_mut(&mut self) -> &mut Cpu {
+    &mut self.cpu
+  }
+
+  /// Returns the accuracy settings being used.
+  pub fn accuracy_settings(&self) -> AccuracySettings {
+    self.accuracy
+  }
+
+  /// Enables the [`Watchdog`], which reports the CPU once `threshold` instructions in a row
+  /// leave its registers unchanged.
+  pub fn enable_watchdog(&mut self, threshold: usize) {
+    self.watchdog = Some(Watchdog::new(threshold));
+  }
+
+  /// Disables the [`Watchdog`].
+  pub fn disable_watchdog(&mut self) {
+    self.watchdog = None;
+  }
+
+  /// Returns the threshold of the [`Watchdog`], if it's enabled.
+  pub fn watchdog_threshold(&self) -> Option<usize> {
+    self.watchdog.as_ref().map(Watchdog::threshold)
+  }
+
+  /// Returns the report of a stuck CPU from the [`Watchdog`], if one happened since the
+  /// last call.
+  pub fn take_stuck_cpu_report(&mut self) -> Option<StuckCpuReport> {
+    self.watchdog.as_mut().and_then(Watchdog::take_report)
+  }
+
+  /// Enables the [`InstructionTrace`], which keeps the last `capacity` executed instructions.
+  pub fn enable_trace(&mut self, capacity: usize) {
+    self.trace = Some(InstructionTrace::new(capacity));
+  }
+
+  /// Disables the [`InstructionTrace`].
+  pub fn disable_trace(&mut self) {
+    self.trace = None;
+  }
+
+  /// Returns the [`InstructionTrace`], if it's enabled.
+  pub fn dump_trace(&self) -> Option<&InstructionTrace> {
+    self.trace.as_ref()
+  }
+
+  /// Sets a callback that gets called with the frame buffer each time the PPU completes a
+  /// frame, when entering VBlank.
+  pub fn set_post_frame_hook(&mut self, hook: impl FnMut(&Frame) + 'static) {
+    self.post_frame_hook = Some(PostFrameHook(Box::new(hook)));
+  }
+
+  /// Removes the callback set by [`Emulator::set_post_frame_hook`].
+  pub fn clear_post_frame_hook(&mut self) {
+    self.post_frame_hook = None;
+  }
+
+  /// Sets a callback that gets called with the CPU and the hardware each time the CPU finishes
+  /// an instruction and fetches the opcode of the next one, before executing it.
+  ///
+  /// The program counter is already past the fetched opcode, so the next instruction is at
+  /// [`Emulator::next_instruction_address`].
+  pub fn set_instruction_hook(&mut self, hook: impl FnMut(&Cpu, &Hardware) + 'static) {
+    self.instruction_hook = Some(InstructionHook(Box::new(hook)));
+  }
+
+  /// Removes the callback set by [`Emulator::set_instruction_hook`].
+  pub fn clear_instruction_hook(&mut self) {
+    self.instruction_hook = None;
+  }
+
+  /// Sets the addresses that get read into each [`Observation`] from [`Emulator::rl_step`].
+  pub fn set_observed_addresses(&mut self, addresses: Vec<u16>) {
+    self.observed_addresses = addresses;
+  }
+
+  /// Holds the buttons in the [`ButtonState`], releasing the rest, and steps until the PPU
+  /// completes the next frame.
+  ///
+  /// This is meant for driving the emulator from an agent, so there is no frame pacing.
+  pub fn rl_step(&mut self, buttons: ButtonState) -> Observation {
+    for button in Button::all() {
+      let action = if buttons.is_pressed(button) {
+        ButtonAction::Pressed
+      } else {
+        ButtonAction::Released
+      };
+
+      self.hardware.update_button(button, action);
+    }
+
+    self.step();
+
+    Observation {
+      frame: *self.hardware.frame_buffer(),
+      memory: self
+        .observed_addresses
+        .iter()
+        .map(|&address| self.hardware.read_byte(address))
+        .collect(),
+    }
+  }
+
+  /// Snapshots the state of the Gameboy into bytes, which can be restored with
+  /// [`Emulator::load_state`]. The ROM is left out, since it never changes.
+  #[cfg(feature = "save-states")]
+  pub fn save_state(&self) -> Vec<u8> {
+    bincode::serialize(&(SAVE_STATE_VERSION, self)).expect("the emulator should serialize")
+  }
+
+  /// Restores the state of the Gameboy from bytes made by [`Emulator::save_state`].
+  ///
+  /// The loaded game's ROM is kept, and the state is rejected if it was made with a game with a
+  /// different title or header checksum. Settings that belong to the frontend, like the
+  /// volume, the audio output, the link cable, the accuracy settings and the debugging tools,
+  /// are kept as they are.
+  #[cfg(feature = "save-states")]
+  pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), SaveStateError> {
+    let version: u32 = bincode::deserialize(bytes)?;
+
+    if version != SAVE_STATE_VERSION {
+      return Err(SaveStateError::UnsupportedVersion(version));
+    }
+
+    let (_, mut state): (u32, Emulator) = bincode::deserialize(bytes)?;
+    let header = state.hardware.cartridge_header();
+    let loaded_header = self.hardware.cartridge_header();
+
+    // Save states leave the ROM out, so they can only be restored into the same game
+    if header.title != loaded_header.title
+      || header.header_checksum != loaded_header.header_checksum
+    {
+      return Err(SaveStateError::DifferentGame(header.title.clone()));
+    }
+
+    state
+      .hardware
+      .cartridge
+      .take_rom_from(&mut self.hardware.cartridge);
+    state.hardware.ppu.restore_back_buffers();
+
+    state.set_accuracy_settings(self.accuracy);
+    state.watchdog = self.watchdog.take();
+    state.post_frame_hook = self.post_frame_hook.take();
+    state.instruction_hook = self.instruction_hook.take();
+    state.trace = self.trace.take();
+    state.observed_addresses = std::mem::take(&mut self.observed_addresses);
+
+    state.hardware.inherit_settings(&mut self.hardware);
+
+    *self = state;
+
+    Ok(())
+  }
+
+  /// Restarts the game without running the boot ROM, like turning the Gameboy off and on
+  /// again, keeping the cartridge and its RAM.
+  ///
+  /// The CPU starts with the registers that the boot ROM leaves behind.
+  pub fn reset(&mut self) {
+    self.power_cycle(false);
+  }
+
+  /// Restarts the game like [`Emulator::reset`], but runs the boot ROM again if one was
+  /// given.
+  pub fn hard_reset(&mut self) {
+    self.power_cycle(true);
+  }
+
+  /// Puts the CPU and the hardware back into the state they power on in.
+  fn power_cycle(&mut self, run_boot_rom: bool) {
+    let run_boot_rom = run_boot_rom && self.hardware.has_boot_rom();
+    let cgb_mode = self.hardware.ppu.cgb_mode();
+
+    self.hardware.reset(run_boot_rom);
+
+    // The boot ROM starts at address 0 and sets the registers up itself
+    self.cpu = if run_boot_rom {
+      Cpu::new()
+    } else {
+      Cpu::with_register_defaults()
+    };
+
+    if cgb_mode {
+      self.cpu.registers.a = CGB_REGISTER_A;
+    }
+
+    // The new APU needs the accuracy settings applied again
+    self.set_accuracy_settings(self.accuracy);
+  }
+
+  /// Returns the scanline that the PPU is on, `LY`.
+  pub fn current_scanline(&self) -> u8 {
+    self.hardware.current_scanline()
   }
 
-  /// Steps one frame of the Gameboy.
-  pub fn step(&mut self) {
-    // The number of T-cycles per frame.
-    const CYCLES_PER_FRAME: usize = 70224;
+  /// Returns the mode that the PPU is in.
+  pub fn ppu_mode(&self) -> PpuMode {
+    self.hardware.ppu_mode()
+  }
+
+  /// Creates an [`Emulator`] that runs the program right after the cartridge header of an
+  /// otherwise empty ROM, with the registers that the DMG boot ROM leaves behind.
+  ///
+  /// The CPU is stopped at the first instruction of the program.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn with_program(program: &[u8]) -> Self {
+    let mut rom = vec![0; PROGRAM_ROM_SIZE];
+    rom[PROGRAM_START as usize..][..program.len()].copy_from_slice(program);
+
+    let mut cpu = Cpu::with_register_defaults();
+    cpu.registers.pc = PROGRAM_START;
+
+    let mut emulator = Self::new(cpu, Hardware::new(rom).unwrap());
+
+    // The CPU starts out with a `NOP` in its instruction register, which fetches the first
+    // opcode of the program
+    emulator.step_instruction();
+
+    emulator
+  }
+
+  /// Makes the [`Interrupt`] pending and enables the master interrupt flag, so that the CPU
+  /// dispatches it at the next instruction boundary.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn force_interrupt(&mut self, interrupt: Interrupt) {
+    self.hardware.force_interrupt(interrupt);
+    self.cpu.set_interrupt_master_enabled(true);
+  }
+
+  /// Steps one frame of the Gameboy, until the PPU completes a frame and enters VBlank.
+  ///
+  /// Returns whether a frame was completed, which only fails to happen if the PPU never gets
+  /// to VBlank within [`MAX_STEP_UNTIL_CYCLES`] T-cycles. Frames are still completed while the
+  /// LCD is off.
+  pub fn step(&mut self) -> bool {
+    debug_assert_eq!(self.hardware.sys_clock.t_cycle(), TCycle::T4);
+
+    let timed_out = self.step_until(|emulator| emulator.hardware.ppu.frame_completed());
+
+    self.finish_m_cycle();
+
+    !timed_out
+  }
+
+  /// Steps a frame like [`Emulator::step`], but stops early when the CPU is about to execute
+  /// the instruction at one of the breakpoints. Returns whether a breakpoint was hit.
+  pub fn step_with_breakpoints(&mut self, breakpoints: &BTreeSet<u16>) -> bool {
+    debug_assert_eq!(self.hardware.sys_clock.t_cycle(), TCycle::T4);
+
+    let at_breakpoint =
+      |emulator: &Self| breakpoints.contains(&emulator.next_instruction_address());
+    let mut hit_breakpoint = false;
+
+    self.step_until(|emulator| {
+      hit_breakpoint = emulator.at_instruction_boundary() && at_breakpoint(emulator);
+
+      hit_breakpoint || emulator.hardware.ppu.frame_completed()
+    });
 
+    self.finish_m_cycle();
+
+    // Finishing the M-cycle after the frame can end on a breakpoint too
+    hit_breakpoint || (self.at_instruction_boundary() && at_breakpoint(self))
+  }
+
+  /// Steps exactly [`CYCLES_PER_FRAME`] T-cycles, the length of an LCD frame, whatever state
+  /// the PPU is in.
+  ///
+  /// Unlike [`Emulator::step`], the frames don't line up with VBlank, but each one is the same
+  /// length, even while the LCD is off, so runs that count frames always emulate the same time.
+  pub fn run_frame(&mut self) {
     debug_assert_eq!(self.hardware.sys_clock.t_cycle(), TCycle::T4);
 
-    for _ in 0..(CYCLES_PER_FRAME / 4) {
-      // ---------------------------------- T1 ----------------------------------
-      self.hardware.step_sys_clock();
+    for _ in 0..CYCLES_PER_FRAME {
+      self.step_t_cycle();
+    }
+  }
+
+  /// Finishes the current M-cycle, so that stepping can continue from an M-cycle boundary.
+  fn finish_m_cycle(&mut self) {
+    if self.hardware.sys_clock.t_cycle() != TCycle::T4 {
+      self.step_until(|emulator| emulator.hardware.sys_clock.t_cycle() == TCycle::T4);
+    }
+  }
 
-      self.cpu.step(&mut self.hardware);
-      self.hardware.step_timer();
-      self.hardware.step_ppu();
-      self.hardware.step_apu();
-      self.hardware.step_dma_transfer();
+  /// Steps T-cycles until the CPU finishes the current instruction, or dispatches an interrupt,
+  /// and fetches the next opcode. The emulator needs to be between instructions.
+  ///
+  /// Returns `None` if the CPU never got to the next instruction, like when it's halted
+  /// without any interrupts enabled.
+  pub fn step_instruction(&mut self) -> Option<InstructionStep> {
+    let address = self.next_instruction_address();
+    let bytes = [
+      self.cpu.registers.ir,
+      self.hardware.read_byte(address.wrapping_add(1)),
+      self.hardware.read_byte(address.wrapping_add(2)),
+    ];
+    let mut cycles = 0;
+    let mut dispatched_interrupt = false;
 
-      // ---------------------------------- T2 ----------------------------------
-      self.hardware.step_sys_clock();
+    let timed_out = self.step_until(|emulator| {
+      cycles += 1;
+      dispatched_interrupt |= emulator.cpu.state() == CpuState::HandlingInterrupts;
 
-      self.cpu.step(&mut self.hardware);
-      self.hardware.step_timer();
-      self.hardware.step_ppu();
-      self.hardware.step_apu();
-      self.hardware.step_dma_transfer();
+      emulator.at_instruction_boundary()
+    });
 
-      // ---------------------------------- T3 ----------------------------------
-      self.hardware.step_sys_clock();
+    if timed_out {
+      return None;
+    }
 
+    let executed = if dispatched_interrupt {
+      Executed::Interrupt(Interrupt::from_vector(self.next_instruction_address()))
+    } else {
+      Executed::Instruction(Instruction::decode(address, bytes))
+    };
+
+    Some(InstructionStep { executed, cycles })
+  }
+
+  /// Returns the address of the instruction that the CPU executes next, when it's between
+  /// instructions.
+  pub fn next_instruction_address(&self) -> u16 {
+    // The opcode was already fetched, so the program counter is past it
+    self.cpu.registers.pc.wrapping_sub(1)
+  }
+
+  /// Returns whether the CPU just fetched the opcode of the next instruction, at the end of an
+  /// M-cycle.
+  fn at_instruction_boundary(&self) -> bool {
+    self.hardware.sys_clock.t_cycle() == TCycle::T4 && self.cpu.fetched_instruction()
+  }
+
+  /// Steps T-cycles until the predicate returns `true`.
+  ///
+  /// The predicate is checked after every T-cycle. Returns `true` if stepping stopped
+  /// because [`MAX_STEP_UNTIL_CYCLES`] T-cycles elapsed without the predicate being met.
+  pub fn step_until(&mut self, mut predicate: impl FnMut(&Self) -> bool) -> bool {
+    for _ in 0..MAX_STEP_UNTIL_CYCLES {
+      self.step_t_cycle();
+
+      if predicate(self) {
+        return false;
+      }
+    }
+
+    true
+  }
+
+  /// Steps every component of the Gameboy by a T-cycle.
+  fn step_t_cycle(&mut self) {
+    self.hardware.step_sys_clock();
+
+    // The CPU is halted for whole M-cycles while the VRAM DMA copies blocks
+    let cpu_stalled = self.hardware.cpu_stalled();
+
+    match self.hardware.sys_clock.t_cycle() {
       // NOTE: Step the timer first because of the timing sensitive test `rapid_toggle`.
       //
       // If we don't do this, then the timer interrupt won't be ready in time for the CPU,
@@ -52,20 +501,72 @@ impl Emulator {
       // We can handle timer interrupts on an M-cycle basis, but then it truly doesn't
       // wait for an M-cycle and instead triggers the interrupt/reload immediately on the
       // current T4 after a CPU write.
-      self.hardware.step_timer();
-      self.cpu.step(&mut self.hardware);
-      self.hardware.step_ppu();
-      self.hardware.step_apu();
-      self.hardware.step_dma_transfer();
-
-      // ---------------------------------- T4 ----------------------------------
-      self.hardware.step_sys_clock();
-
-      self.cpu.step(&mut self.hardware);
-      self.hardware.step_timer();
-      self.hardware.step_ppu();
-      self.hardware.step_apu();
-      self.hardware.step_dma_transfer();
+      TCycle::T3 => {
+        self.hardware.step_timer();
+
+        if !cpu_stalled {
+          self.cpu.step(&mut self.hardware);
+        }
+      }
+      TCycle::T1 | TCycle::T2 => {
+        if !cpu_stalled {
+          self.cpu.step(&mut self.hardware);
+        }
+
+        self.hardware.step_timer();
+      }
+      TCycle::T4 if cpu_stalled => self.hardware.step_timer(),
+      TCycle::T4 => {
+        self.cpu.step(&mut self.hardware);
+        self.hardware.step_timer();
+
+        if let Some(watchdog) = &mut self.watchdog {
+          if self.cpu.fetched_instruction() {
+            watchdog.check(&self.cpu, &self.hardware);
+          }
+        }
+
+        if let Some(trace) = &mut self.trace {
+          if self.cpu.fetched_instruction() {
+            // The opcode was already fetched, so the program counter is past it
+            trace.push(TraceEntry {
+              pc: self.cpu.registers.pc.wrapping_sub(1),
+              opcode: self.cpu.registers.ir,
+            });
+          }
+        }
+
+        if let Some(InstructionHook(hook)) = &mut self.instruction_hook {
+          if self.cpu.fetched_instruction() {
+            hook(&self.cpu, &self.hardware);
+          }
+        }
+      }
     }
+
+    self.hardware.step_ppu();
+
+    if let Some(PostFrameHook(hook)) = &mut self.post_frame_hook {
+      if self.hardware.ppu.frame_completed() {
+        hook(self.hardware.frame_buffer());
+      }
+    }
+    self.hardware.step_apu();
+    self.hardware.step_serial();
+    self.hardware.step_dma_transfer();
+    self.hardware.step_hdma();
   }
 }
+
+/// The number of T-cycles per frame.
+pub const CYCLES_PER_FRAME: usize = 70224;
+/// The maximum number of T-cycles that [`Emulator::step_until`] steps before giving up.
+pub const MAX_STEP_UNTIL_CYCLES: usize = CYCLES_PER_FRAME * 60;
+/// The address that the program of [`Emulator::with_program`] starts at, right after the
+/// cartridge header.
+#[cfg(any(test, feature = "testing"))]
+const PROGRAM_START: u16 = 0x0150;
+/// The size of the ROM that [`Emulator::with_program`] puts the program in, which fits a
+/// cartridge without a memory bank controller.
+#[cfg(any(test, feature = "testing"))]
+const PROGRAM_ROM_SIZE: usize = 0x8000;
@@ -0,0 +1,18 @@
+pub mod audio;
+pub mod capture;
+pub mod config;
+pub mod font;
+pub mod game;
+pub mod input;
+pub mod overlay;
+pub mod render;
+
+use std::time::Duration;
+
+/// The Gameboy runs at 59.7275 frames per second.
+pub const FRAME_TIME: Duration = Duration::from_micros(16_740);
+/// The number of frames per second that the Gameboy runs at.
+pub const GAMEBOY_FRAME_RATE: f64 = 59.7275;
+
+pub const GAMEBOY_WIDTH: u32 = 160;
+pub const GAMEBOY_HEIGHT: u32 = 144;
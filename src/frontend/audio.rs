@@ -0,0 +1,140 @@
+use gameboy_emu::hardware::apu::{self, AudioSample};
+
+use cpal::{
+  BufferSize, SampleRate, StreamConfig,
+  traits::{DeviceTrait, HostTrait, StreamTrait},
+};
+use ringbuf::{HeapCons, traits::Consumer};
+
+use std::{
+  collections::VecDeque,
+  sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+  },
+};
+
+/// Where the audio stream reads the APU's samples from.
+pub enum AudioSource {
+  /// A mutex-guarded buffer that is shared with the APU.
+  Shared(Arc<Mutex<VecDeque<AudioSample>>>),
+  /// The consumer half of a lock-free ring buffer that the APU pushes into.
+  RingBuffer(HeapCons<AudioSample>),
+}
+
+/// Creates the stream that plays the samples from the audio source on the audio device.
+///
+/// Setting `resumed` after resuming the stream drops the samples that were queued before it
+/// was paused.
+pub fn get_audio_stream(
+  mut audio_source: AudioSource,
+  underruns: Arc<AtomicU64>,
+  resumed: Arc<AtomicBool>,
+) -> cpal::Stream {
+  let device = cpal::default_host().default_output_device().unwrap();
+
+  let config = StreamConfig {
+    channels: 2,
+    sample_rate: SampleRate(apu::SAMPLE_RATE),
+    buffer_size: BufferSize::Fixed(AUDIO_DEVICE_BUFFER_SIZE),
+  };
+
+  let mut last_sample = AudioSample::default();
+
+  device
+    .build_output_stream(
+      &config,
+      move |data: &mut [f32], _| {
+        // Play silence while the emulator queues up new samples, rather than the stale ones
+        if resumed.swap(false, Ordering::Relaxed) {
+          match &mut audio_source {
+            AudioSource::Shared(audio_buffer) => audio_buffer.lock().unwrap().clear(),
+            AudioSource::RingBuffer(consumer) => {
+              consumer.clear();
+            }
+          }
+
+          data.fill(0.0);
+          last_sample = AudioSample::default();
+
+          return;
+        }
+
+        let ran_dry = match &mut audio_source {
+          AudioSource::Shared(audio_buffer) => {
+            let mut buffer = audio_buffer.lock().unwrap();
+
+            fill_audio_frames(data, &mut last_sample, || buffer.pop_front())
+          }
+          AudioSource::RingBuffer(consumer) => {
+            fill_audio_frames(data, &mut last_sample, || consumer.try_pop())
+          }
+        };
+
+        if ran_dry {
+          underruns.fetch_add(1, Ordering::Relaxed);
+        }
+      },
+      move |err| {
+        eprintln!("error {:?}", err);
+      },
+      None,
+    )
+    .unwrap()
+}
+
+/// Fills the audio device's buffer with the next samples, returning whether the samples ran
+/// out before the buffer was full.
+///
+/// Once the samples run out, the last sample fades out instead of dropping straight to
+/// silence, which would pop.
+fn fill_audio_frames(
+  data: &mut [f32],
+  last_sample: &mut AudioSample,
+  mut next_sample: impl FnMut() -> Option<AudioSample>,
+) -> bool {
+  let mut ran_dry = false;
+
+  for frame in data.chunks_mut(2) {
+    let sample = match next_sample() {
+      Some(sample) => sample,
+      None => {
+        ran_dry = true;
+
+        AudioSample {
+          left: last_sample.left * UNDERRUN_FADE,
+          right: last_sample.right * UNDERRUN_FADE,
+        }
+      }
+    };
+
+    frame[0] = sample.left;
+    frame[1] = sample.right;
+    *last_sample = sample;
+  }
+
+  ran_dry
+}
+
+/// Pauses or resumes the audio stream, if there is one.
+pub fn set_audio_paused(
+  audio_stream: Option<&cpal::Stream>,
+  audio_resumed: &AtomicBool,
+  paused: bool,
+) {
+  let Some(audio_stream) = audio_stream else {
+    return;
+  };
+
+  if paused {
+    audio_stream.pause().unwrap();
+  } else {
+    audio_resumed.store(true, Ordering::Relaxed);
+    audio_stream.play().unwrap();
+  }
+}
+
+/// The number of audio frames the audio device requests per callback.
+pub const AUDIO_DEVICE_BUFFER_SIZE: u32 = 4096;
+/// How much of the last sample is kept for each missing sample when the audio runs dry.
+const UNDERRUN_FADE: f32 = 0.995;
@@ -0,0 +1,222 @@
+use gameboy_emu::{
+  emulator::Emulator,
+  hardware::{
+    apu::{self, AudioSample},
+    ppu::Frame,
+  },
+  input_script::InputScript,
+};
+
+use std::{
+  borrow::Cow,
+  fs,
+  io::{self, Write},
+  path::Path,
+  thread,
+  time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+  FRAME_TIME, GAMEBOY_FRAME_RATE, GAMEBOY_HEIGHT, GAMEBOY_WIDTH, game::run_emulator_frame,
+};
+
+/// Records the frames of the game into an animated GIF.
+pub struct GifRecorder {
+  /// The recorded frames, as indices into `colors`.
+  frames: Vec<Box<[u8]>>,
+  /// The colors of the shades when the recording started.
+  colors: [u32; 4],
+  /// The number of frames that were emulated since the recording started.
+  frames_seen: u32,
+}
+
+impl GifRecorder {
+  /// Creates a new [`GifRecorder`] that records the shades in the colors.
+  pub fn new(colors: [u32; 4]) -> Self {
+    Self {
+      frames: Vec::new(),
+      colors,
+      frames_seen: 0,
+    }
+  }
+
+  /// Records a completed emulated frame, if it's not one that gets skipped to lower the frame
+  /// rate and the recording isn't full yet.
+  pub fn capture(&mut self, frame: &Frame) {
+    let is_skipped = self.frames_seen % GIF_FRAME_INTERVAL != 0;
+
+    self.frames_seen += 1;
+
+    if is_skipped || self.is_full() {
+      return;
+    }
+
+    // The shades are the indices into the GIF's color table
+    self.frames.push(frame.as_flattened().into());
+  }
+
+  /// Returns whether the recording is at its maximum length.
+  pub fn is_full(&self) -> bool {
+    self.frames.len() >= GIF_MAX_FRAMES
+  }
+
+  /// Encodes the recorded frames into a GIF file at the path.
+  fn save(&self, path: &Path) -> Result<(), gif::EncodingError> {
+    let color_table: Vec<u8> = self
+      .colors
+      .iter()
+      .flat_map(|color| {
+        let [_, red, green, blue] = color.to_be_bytes();
+
+        [red, green, blue]
+      })
+      .collect();
+
+    let file = fs::File::create(path)?;
+    let mut encoder = gif::Encoder::new(
+      io::BufWriter::new(file),
+      GAMEBOY_WIDTH as u16,
+      GAMEBOY_HEIGHT as u16,
+      &color_table,
+    )?;
+
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+
+    for (index, indices) in self.frames.iter().enumerate() {
+      let frame = gif::Frame {
+        width: GAMEBOY_WIDTH as u16,
+        height: GAMEBOY_HEIGHT as u16,
+        delay: gif_frame_delay(index as u32),
+        buffer: Cow::Borrowed(indices),
+        ..gif::Frame::default()
+      };
+
+      encoder.write_frame(&frame)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Runs the emulator headless for the number of frames, applying the scripted inputs and
+/// writing the produced audio samples into a stereo WAV file.
+pub fn record_audio(
+  emulator: &mut Emulator,
+  path: &str,
+  frames: u32,
+  input_script: &mut InputScript,
+) -> Result<(), hound::Error> {
+  let spec = hound::WavSpec {
+    channels: 2,
+    sample_rate: apu::SAMPLE_RATE,
+    bits_per_sample: 32,
+    sample_format: hound::SampleFormat::Float,
+  };
+
+  let mut writer = hound::WavWriter::create(path, spec)?;
+  let audio_buffer = emulator.hardware.audio_buffer();
+
+  for frame in 0..frames {
+    input_script.apply(frame, emulator);
+    run_emulator_frame(emulator);
+
+    for AudioSample { left, right } in audio_buffer.lock().unwrap().drain(..) {
+      writer.write_sample(left)?;
+      writer.write_sample(right)?;
+    }
+  }
+
+  writer.finalize()
+}
+
+/// Runs the frames as fast as possible without a window or audio device, then prints how long
+/// it took.
+pub fn run_headless(
+  emulator: &mut Emulator,
+  frames: u32,
+  print_serial: bool,
+  input_script: &mut InputScript,
+) {
+  let audio_buffer = emulator.hardware.audio_buffer();
+  let mut serial_printed = 0;
+  let start = Instant::now();
+
+  for frame in 0..frames {
+    input_script.apply(frame, emulator);
+    run_emulator_frame(emulator);
+
+    // Nothing plays the samples, but they still get generated like they would be with audio
+    audio_buffer.lock().unwrap().clear();
+
+    if print_serial {
+      print_serial_output(emulator, &mut serial_printed);
+    }
+  }
+
+  let elapsed = start.elapsed().as_secs_f64();
+  let fps = frames as f64 / elapsed;
+
+  if print_serial && serial_printed > 0 {
+    println!();
+  }
+
+  println!(
+    "Ran {frames} frames in {elapsed:.3}s, at {fps:.1} fps ({:.1}x real time)",
+    fps * FRAME_TIME.as_secs_f64()
+  );
+  println!("Frame hash: {:016X}", emulator.hardware.frame_hash());
+}
+
+/// Prints the serial output that came after the first `printed` bytes, which were already
+/// printed.
+pub fn print_serial_output(emulator: &Emulator, printed: &mut usize) {
+  let output = emulator.hardware.serial_output();
+  let bytes_sent = emulator.hardware.serial_bytes_sent();
+  // The output starts over when another game or a save state gets loaded, and bytes that
+  // were dropped from the output before they got printed are skipped
+  let new_bytes = bytes_sent.checked_sub(*printed).unwrap_or(bytes_sent);
+  let new_output = &output[output.len().saturating_sub(new_bytes)..];
+
+  if !new_output.is_empty() {
+    print!("{}", String::from_utf8_lossy(new_output));
+    let _ = io::stdout().flush();
+  }
+
+  *printed = bytes_sent;
+}
+
+/// Returns how long the recorded frame of a GIF is shown, in hundredths of a second.
+///
+/// GIF delays are whole hundredths of a second, so they're rounded from when each frame was
+/// emulated, which keeps the recording from drifting away from the Gameboy's frame rate.
+fn gif_frame_delay(index: u32) -> u16 {
+  let time = |frame: u32| (frame as f64 * 100.0 / GAMEBOY_FRAME_RATE).round() as u16;
+
+  time((index + 1) * GIF_FRAME_INTERVAL) - time(index * GIF_FRAME_INTERVAL)
+}
+
+/// Saves the GIF recording next to the ROM on another thread, so the game doesn't stutter
+/// while it gets encoded.
+pub fn save_gif(recorder: GifRecorder, rom_path: &Path) -> thread::JoinHandle<()> {
+  let timestamp = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_secs();
+  let stem = rom_path.file_stem().unwrap_or_default().to_string_lossy();
+  let gif_path = rom_path.with_file_name(format!("{stem}-{timestamp}.{GIF_EXTENSION}"));
+
+  thread::spawn(move || match recorder.save(&gif_path) {
+    Ok(()) => eprintln!("Saved the recording to {}", gif_path.display()),
+    Err(e) => eprintln!(
+      "Failed to save the recording to {}: {e}",
+      gif_path.display()
+    ),
+  })
+}
+
+/// The extension of the GIF recordings that are written next to the ROMs.
+const GIF_EXTENSION: &str = "gif";
+/// Only every 3rd emulated frame gets recorded into GIFs, for about 20 frames per second.
+const GIF_FRAME_INTERVAL: u32 = 3;
+/// The maximum number of frames in a GIF, which is about a minute.
+const GIF_MAX_FRAMES: usize = 1200;
@@ -0,0 +1,145 @@
+use gameboy_emu::{
+  accuracy::AccuracyProfile,
+  hardware::{cartridge::CartridgeKind, joypad::SocdMode},
+};
+
+use std::{fs, path::Path};
+
+/// The colors that the 4 shades of the DMG get shown as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Palette {
+  /// The green of the original DMG.
+  Green,
+  /// Shades of gray.
+  Grayscale,
+  /// The olive grays of the Game Boy Pocket.
+  Pocket,
+  /// Colors that are easy to tell apart.
+  HighContrast,
+}
+
+impl Palette {
+  /// Every palette, in the order that they get cycled through.
+  const ALL: [Palette; 4] = [
+    Palette::Green,
+    Palette::Grayscale,
+    Palette::Pocket,
+    Palette::HighContrast,
+  ];
+
+  /// Returns the palette that comes after this one.
+  pub fn next(self) -> Self {
+    match self {
+      Palette::Green => Palette::Grayscale,
+      Palette::Grayscale => Palette::Pocket,
+      Palette::Pocket => Palette::HighContrast,
+      Palette::HighContrast => Palette::Green,
+    }
+  }
+
+  /// Returns the colors of the shades, from the lightest to the darkest shade.
+  pub fn colors(self) -> [u32; 4] {
+    match self {
+      Palette::Green => [0x00FFFFFF, 0x0088C070, 0x00346856, 0x00081820],
+      Palette::Grayscale => [0x00FFFFFF, 0x00AAAAAA, 0x00555555, 0x00000000],
+      Palette::Pocket => [0x00C4CFA1, 0x008B956D, 0x004D533C, 0x001F1F1F],
+      Palette::HighContrast => [0x00FFFFFF, 0x00FFD700, 0x00D01010, 0x00000000],
+    }
+  }
+
+  /// Returns the name of the palette, which is also what gets saved.
+  pub fn name(self) -> &'static str {
+    match self {
+      Palette::Green => "GREEN",
+      Palette::Grayscale => "GRAYSCALE",
+      Palette::Pocket => "POCKET",
+      Palette::HighContrast => "HIGH CONTRAST",
+    }
+  }
+}
+
+/// What decides when the next frame gets emulated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePacing {
+  /// Frames get emulated every [`FRAME_TIME`], going by the system clock.
+  WallClock,
+  /// Frames get emulated whenever the queued audio drops below a target level, so the
+  /// audio device sets the speed and the audio never underruns or overruns.
+  Audio,
+}
+
+/// Parses the name of a [`SocdMode`].
+pub fn parse_socd_mode(name: &str) -> Option<SocdMode> {
+  Some(match name {
+    "hardware" => SocdMode::Hardware,
+    "last" => SocdMode::LastInputWins,
+    "neutral" => SocdMode::Neutral,
+    _ => return None,
+  })
+}
+
+/// Parses the name of an [`AccuracyProfile`].
+pub fn parse_accuracy_profile(name: &str) -> Option<AccuracyProfile> {
+  Some(match name {
+    "fast" => AccuracyProfile::Fast,
+    "balanced" => AccuracyProfile::Balanced,
+    "accurate" => AccuracyProfile::Accurate,
+    _ => return None,
+  })
+}
+
+/// Parses the name of a [`Palette`].
+pub fn parse_palette(name: &str) -> Option<Palette> {
+  Some(match name {
+    "green" => Palette::Green,
+    "grayscale" => Palette::Grayscale,
+    "pocket" => Palette::Pocket,
+    "high_contrast" => Palette::HighContrast,
+    _ => return None,
+  })
+}
+
+/// Parses the name of a [`FramePacing`].
+pub fn parse_frame_pacing(name: &str) -> Option<FramePacing> {
+  Some(match name {
+    "wall_clock" => FramePacing::WallClock,
+    "audio" => FramePacing::Audio,
+    _ => return None,
+  })
+}
+
+/// Parses the name of a [`CartridgeKind`].
+pub fn parse_cartridge_kind(name: &str) -> Option<CartridgeKind> {
+  Some(match name {
+    "rom_only" => CartridgeKind::RomOnly,
+    "mbc1" => CartridgeKind::Mbc1,
+    "mbc2" => CartridgeKind::Mbc2,
+    "mbc5" => CartridgeKind::Mbc5,
+    _ => return None,
+  })
+}
+
+/// Reads the palette that was picked for the game at the path, if one was.
+pub fn load_palette(rom_path: &Path) -> Option<Palette> {
+  let name = fs::read_to_string(rom_path.with_extension(PALETTE_EXTENSION)).ok()?;
+
+  Palette::ALL
+    .into_iter()
+    .find(|palette| palette.name() == name.trim())
+}
+
+/// Writes the palette that was picked for the game next to the ROM, so it's used the next
+/// time that the game gets loaded.
+pub fn save_palette(rom_path: &Path, palette: Palette) {
+  let palette_path = rom_path.with_extension(PALETTE_EXTENSION);
+
+  if let Err(e) = fs::write(&palette_path, palette.name()) {
+    eprintln!(
+      "Failed to save the palette to {}: {e}",
+      palette_path.display()
+    );
+  }
+}
+
+/// The extension of the files that remember the palette picked for the ROMs next to them.
+const PALETTE_EXTENSION: &str = "palette";
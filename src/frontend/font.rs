@@ -0,0 +1,960 @@
+/// Converts the ASCII byte to a 7x8 bitmap, or `None` if it isn't printable.
+#[rustfmt::skip]
+pub const fn get_character_bitmap(byte: u8) -> Option<[u8; DEFAULT_CHARACTER_HEIGHT as usize]> {
+  match byte {
+    b'0' => Some([
+      0b0111100,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'1' => Some([
+      0b0011000,
+      0b0101000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0111100,
+    ]),
+    b'2' => Some([
+      0b0111100,
+      0b1000010,
+      0b0000010,
+      0b0000100,
+      0b0001000,
+      0b0010000,
+      0b0100000,
+      0b1111110,
+    ]),
+    b'3' => Some([
+      0b0111100,
+      0b1000010,
+      0b0000010,
+      0b0011100,
+      0b0000010,
+      0b0000010,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'4' => Some([
+      0b0000100,
+      0b0001100,
+      0b0010100,
+      0b0100100,
+      0b1000100,
+      0b1111110,
+      0b0000100,
+      0b0000100,
+    ]),
+    b'5' => Some([
+      0b1111110,
+      0b1000000,
+      0b1000000,
+      0b1111100,
+      0b0000010,
+      0b0000010,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'6' => Some([
+      0b0111100,
+      0b1000010,
+      0b1000000,
+      0b1111100,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'7' => Some([
+      0b1111110,
+      0b0000010,
+      0b0000100,
+      0b0001000,
+      0b0010000,
+      0b0010000,
+      0b0010000,
+      0b0010000,
+    ]),
+    b'8' => Some([
+      0b0111100,
+      0b1000010,
+      0b1000010,
+      0b0111100,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'9' => Some([
+      0b0111100,
+      0b1000010,
+      0b1000010,
+      0b0111110,
+      0b0000010,
+      0b0000010,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'A' => Some([
+      0b0011000,
+      0b0100100,
+      0b1000010,
+      0b1000010,
+      0b1111110,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+    ]),
+    b'B' => Some([
+      0b1111100,
+      0b1000010,
+      0b1000010,
+      0b1111100,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1111100,
+    ]),
+    b'C' => Some([
+      0b0111100,
+      0b1000010,
+      0b1000000,
+      0b1000000,
+      0b1000000,
+      0b1000000,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'D' => Some([
+      0b1111000,
+      0b1000100,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000100,
+      0b1111000,
+    ]),
+    b'E' => Some([
+      0b1111110,
+      0b1000000,
+      0b1000000,
+      0b1111100,
+      0b1000000,
+      0b1000000,
+      0b1000000,
+      0b1111110,
+    ]),
+    b'F' => Some([
+      0b1111110,
+      0b1000000,
+      0b1000000,
+      0b1111100,
+      0b1000000,
+      0b1000000,
+      0b1000000,
+      0b1000000,
+    ]),
+    b'G' => Some([
+      0b0111100,
+      0b1000010,
+      0b1000000,
+      0b1000000,
+      0b1001110,
+      0b1000010,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'H' => Some([
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1111110,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+    ]),
+    b'I' => Some([
+      0b0111110,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0111110,
+    ]),
+    b'J' => Some([
+      0b0011110,
+      0b0000100,
+      0b0000100,
+      0b0000100,
+      0b0000100,
+      0b1000100,
+      0b1000100,
+      0b0111000,
+    ]),
+    b'K' => Some([
+      0b1000100,
+      0b1001000,
+      0b1010000,
+      0b1100000,
+      0b1010000,
+      0b1001000,
+      0b1000100,
+      0b1000010,
+    ]),
+    b'L' => Some([
+      0b1000000,
+      0b1000000,
+      0b1000000,
+      0b1000000,
+      0b1000000,
+      0b1000000,
+      0b1000000,
+      0b1111110,
+    ]),
+    b'M' => Some([
+      0b1000001,
+      0b1100011,
+      0b1010101,
+      0b1001001,
+      0b1000001,
+      0b1000001,
+      0b1000001,
+      0b1000001,
+    ]),
+    b'N' => Some([
+      0b1000010,
+      0b1100010,
+      0b1010010,
+      0b1010010,
+      0b1001010,
+      0b1001010,
+      0b1000110,
+      0b1000010,
+    ]),
+    b'O' => Some([
+      0b0111100,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'P' => Some([
+      0b1111100,
+      0b1000010,
+      0b1000010,
+      0b1111100,
+      0b1000000,
+      0b1000000,
+      0b1000000,
+      0b1000000,
+    ]),
+    b'Q' => Some([
+      0b0111100,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1001010,
+      0b1000100,
+      0b0111010,
+    ]),
+    b'R' => Some([
+      0b1111100,
+      0b1000010,
+      0b1000010,
+      0b1111100,
+      0b1010000,
+      0b1001000,
+      0b1000100,
+      0b1000010,
+    ]),
+    b'S' => Some([
+      0b0111100,
+      0b1000010,
+      0b1000000,
+      0b0111100,
+      0b0000010,
+      0b0000010,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'T' => Some([
+      0b1111111,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+    ]),
+    b'U' => Some([
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'V' => Some([
+      0b1000001,
+      0b1000001,
+      0b1000001,
+      0b0100010,
+      0b0100010,
+      0b0010100,
+      0b0010100,
+      0b0001000,
+    ]),
+    b'W' => Some([
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1011010,
+      0b1011010,
+      0b1100110,
+      0b1000010,
+    ]),
+    b'X' => Some([
+      0b1000010,
+      0b1000010,
+      0b0100100,
+      0b0011000,
+      0b0011000,
+      0b0100100,
+      0b1000010,
+      0b1000010,
+    ]),
+    b'Y' => Some([
+      0b1000001,
+      0b0100010,
+      0b0010100,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+    ]),
+    b'Z' => Some([
+      0b1111110,
+      0b0000010,
+      0b0000100,
+      0b0001000,
+      0b0010000,
+      0b0100000,
+      0b1000000,
+      0b1111110,
+    ]),
+    b'a' => Some([
+      0b0000000,
+      0b0000000,
+      0b0111100,
+      0b0000010,
+      0b0111110,
+      0b1000010,
+      0b1000110,
+      0b0111010,
+    ]),
+    b'b' => Some([
+      0b1000000,
+      0b1000000,
+      0b1011100,
+      0b1100010,
+      0b1000010,
+      0b1000010,
+      0b1100010,
+      0b1011100,
+    ]),
+    b'c' => Some([
+      0b0000000,
+      0b0000000,
+      0b0111100,
+      0b1000010,
+      0b1000000,
+      0b1000000,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'd' => Some([
+      0b0000010,
+      0b0000010,
+      0b0111010,
+      0b1000110,
+      0b1000010,
+      0b1000010,
+      0b1000110,
+      0b0111010,
+    ]),
+    b'e' => Some([
+      0b0000000,
+      0b0000000,
+      0b0111100,
+      0b1000010,
+      0b1111110,
+      0b1000000,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'f' => Some([
+      0b0001110,
+      0b0010000,
+      0b0010000,
+      0b1111100,
+      0b0010000,
+      0b0010000,
+      0b0010000,
+      0b0010000,
+    ]),
+    b'g' => Some([
+      0b0000000,
+      0b0111110,
+      0b1000010,
+      0b1000010,
+      0b0111110,
+      0b0000010,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'h' => Some([
+      0b1000000,
+      0b1000000,
+      0b1011100,
+      0b1100010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+    ]),
+    b'i' => Some([
+      0b0001000,
+      0b0000000,
+      0b0011000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0011100,
+    ]),
+    b'j' => Some([
+      0b0000100,
+      0b0000000,
+      0b0001100,
+      0b0000100,
+      0b0000100,
+      0b0000100,
+      0b1000100,
+      0b0111000,
+    ]),
+    b'k' => Some([
+      0b1000000,
+      0b1000000,
+      0b1000100,
+      0b1001000,
+      0b1110000,
+      0b1001000,
+      0b1000100,
+      0b1000010,
+    ]),
+    b'l' => Some([
+      0b0011000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0011100,
+    ]),
+    b'm' => Some([
+      0b0000000,
+      0b0000000,
+      0b1101100,
+      0b1010100,
+      0b1010100,
+      0b1010100,
+      0b1010100,
+      0b1010100,
+    ]),
+    b'n' => Some([
+      0b0000000,
+      0b0000000,
+      0b1011100,
+      0b1100010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+    ]),
+    b'o' => Some([
+      0b0000000,
+      0b0000000,
+      0b0111100,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'p' => Some([
+      0b0000000,
+      0b1011100,
+      0b1100010,
+      0b1000010,
+      0b1100010,
+      0b1011100,
+      0b1000000,
+      0b1000000,
+    ]),
+    b'q' => Some([
+      0b0000000,
+      0b0111010,
+      0b1000110,
+      0b1000010,
+      0b1000110,
+      0b0111010,
+      0b0000010,
+      0b0000010,
+    ]),
+    b'r' => Some([
+      0b0000000,
+      0b0000000,
+      0b1011100,
+      0b1100010,
+      0b1000000,
+      0b1000000,
+      0b1000000,
+      0b1000000,
+    ]),
+    b's' => Some([
+      0b0000000,
+      0b0000000,
+      0b0111110,
+      0b1000000,
+      0b0111100,
+      0b0000010,
+      0b0000010,
+      0b1111100,
+    ]),
+    b't' => Some([
+      0b0010000,
+      0b0010000,
+      0b1111100,
+      0b0010000,
+      0b0010000,
+      0b0010000,
+      0b0010010,
+      0b0001100,
+    ]),
+    b'u' => Some([
+      0b0000000,
+      0b0000000,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b1000110,
+      0b0111010,
+    ]),
+    b'v' => Some([
+      0b0000000,
+      0b0000000,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b0100100,
+      0b0100100,
+      0b0011000,
+    ]),
+    b'w' => Some([
+      0b0000000,
+      0b0000000,
+      0b1000010,
+      0b1000010,
+      0b1011010,
+      0b1011010,
+      0b1100110,
+      0b1000010,
+    ]),
+    b'x' => Some([
+      0b0000000,
+      0b0000000,
+      0b1000010,
+      0b0100100,
+      0b0011000,
+      0b0011000,
+      0b0100100,
+      0b1000010,
+    ]),
+    b'y' => Some([
+      0b0000000,
+      0b1000010,
+      0b1000010,
+      0b1000010,
+      0b0111110,
+      0b0000010,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'z' => Some([
+      0b0000000,
+      0b0000000,
+      0b1111110,
+      0b0000100,
+      0b0001000,
+      0b0010000,
+      0b0100000,
+      0b1111110,
+    ]),
+    b':' => Some([
+      0b0000000,
+      0b0011000,
+      0b0011000,
+      0b0000000,
+      0b0000000,
+      0b0011000,
+      0b0011000,
+      0b0000000,
+    ]),
+    b'.' => Some([
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0011000,
+      0b0011000,
+    ]),
+    b' ' => Some([
+      0b00000000,
+      0b00000000,
+      0b00000000,
+      0b00000000,
+      0b00000000,
+      0b00000000,
+      0b00000000,
+      0b00000000,
+    ]),
+    b'%' => Some([
+      0b1100001,
+      0b1100010,
+      0b0000100,
+      0b0001000,
+      0b0010000,
+      0b0100000,
+      0b1000011,
+      0b0000011,
+    ]),
+    b',' => Some([
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0011000,
+      0b0011000,
+      0b0010000,
+    ]),
+    b'$' => Some([
+      0b0001000,
+      0b0111110,
+      0b1001000,
+      0b0111100,
+      0b0001010,
+      0b1111100,
+      0b0001000,
+      0b0000000,
+    ]),
+    b'[' => Some([
+      0b0011100,
+      0b0010000,
+      0b0010000,
+      0b0010000,
+      0b0010000,
+      0b0010000,
+      0b0010000,
+      0b0011100,
+    ]),
+    b']' => Some([
+      0b0011100,
+      0b0000100,
+      0b0000100,
+      0b0000100,
+      0b0000100,
+      0b0000100,
+      0b0000100,
+      0b0011100,
+    ]),
+    b'+' => Some([
+      0b0000000,
+      0b0001000,
+      0b0001000,
+      0b1111110,
+      0b0001000,
+      0b0001000,
+      0b0000000,
+      0b0000000,
+    ]),
+    b'-' => Some([
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b1111110,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+    ]),
+    b'_' => Some([
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b1111110,
+    ]),
+    b'!' => Some([
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0000000,
+      0b0001000,
+      0b0000000,
+    ]),
+    b'"' => Some([
+      0b0010100,
+      0b0010100,
+      0b0010100,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+    ]),
+    b'#' => Some([
+      0b0010100,
+      0b0010100,
+      0b1111110,
+      0b0010100,
+      0b0010100,
+      0b1111110,
+      0b0010100,
+      0b0010100,
+    ]),
+    b'&' => Some([
+      0b0110000,
+      0b1001000,
+      0b1001000,
+      0b0110000,
+      0b1010010,
+      0b1001100,
+      0b1000100,
+      0b0111010,
+    ]),
+    b'\'' => Some([
+      0b0001000,
+      0b0001000,
+      0b0010000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+    ]),
+    b'(' => Some([
+      0b0000100,
+      0b0001000,
+      0b0010000,
+      0b0010000,
+      0b0010000,
+      0b0010000,
+      0b0001000,
+      0b0000100,
+    ]),
+    b')' => Some([
+      0b0010000,
+      0b0001000,
+      0b0000100,
+      0b0000100,
+      0b0000100,
+      0b0000100,
+      0b0001000,
+      0b0010000,
+    ]),
+    b'*' => Some([
+      0b0000000,
+      0b0001000,
+      0b1001001,
+      0b0101010,
+      0b0011100,
+      0b0101010,
+      0b1001001,
+      0b0001000,
+    ]),
+    b'/' => Some([
+      0b0000001,
+      0b0000010,
+      0b0000100,
+      0b0001000,
+      0b0010000,
+      0b0100000,
+      0b1000000,
+      0b0000000,
+    ]),
+    b';' => Some([
+      0b0000000,
+      0b0001100,
+      0b0001100,
+      0b0000000,
+      0b0000000,
+      0b0001100,
+      0b0001100,
+      0b0001000,
+    ]),
+    b'<' => Some([
+      0b0000000,
+      0b0000100,
+      0b0001000,
+      0b0010000,
+      0b0100000,
+      0b0010000,
+      0b0001000,
+      0b0000100,
+    ]),
+    b'=' => Some([
+      0b0000000,
+      0b0000000,
+      0b1111110,
+      0b0000000,
+      0b0000000,
+      0b1111110,
+      0b0000000,
+      0b0000000,
+    ]),
+    b'>' => Some([
+      0b0000000,
+      0b0100000,
+      0b0010000,
+      0b0001000,
+      0b0000100,
+      0b0001000,
+      0b0010000,
+      0b0100000,
+    ]),
+    b'?' => Some([
+      0b0111100,
+      0b1000010,
+      0b0000010,
+      0b0000100,
+      0b0001000,
+      0b0001000,
+      0b0000000,
+      0b0001000,
+    ]),
+    b'@' => Some([
+      0b0111100,
+      0b1000010,
+      0b1011110,
+      0b1010010,
+      0b1011110,
+      0b1000000,
+      0b1000010,
+      0b0111100,
+    ]),
+    b'\\' => Some([
+      0b1000000,
+      0b0100000,
+      0b0010000,
+      0b0001000,
+      0b0000100,
+      0b0000010,
+      0b0000001,
+      0b0000000,
+    ]),
+    b'^' => Some([
+      0b0001000,
+      0b0010100,
+      0b0100010,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+    ]),
+    b'`' => Some([
+      0b0010000,
+      0b0001000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+    ]),
+    b'{' => Some([
+      0b0000110,
+      0b0001000,
+      0b0001000,
+      0b0110000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0000110,
+    ]),
+    b'|' => Some([
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+    ]),
+    b'}' => Some([
+      0b0110000,
+      0b0001000,
+      0b0001000,
+      0b0000110,
+      0b0001000,
+      0b0001000,
+      0b0001000,
+      0b0110000,
+    ]),
+    b'~' => Some([
+      0b0000000,
+      0b0000000,
+      0b0110001,
+      0b1001001,
+      0b1000110,
+      0b0000000,
+      0b0000000,
+      0b0000000,
+    ]),
+    _ => None,
+  }
+}
+
+pub const DEFAULT_CHARACTER_WIDTH: u32 = 7;
+pub const DEFAULT_CHARACTER_HEIGHT: u32 = 8;
@@ -0,0 +1,270 @@
+use gameboy_emu::{
+  accuracy::AccuracyProfile,
+  emulator::Emulator,
+  hardware::{
+    BOOT_ROM_SIZE, Cpu, Hardware, cartridge::CartridgeKind, cpu::CGB_REGISTER_A, joypad::SocdMode,
+  },
+  trace,
+};
+
+use std::{
+  cell::RefCell,
+  collections::BTreeSet,
+  fs, io,
+  panic::{self, AssertUnwindSafe},
+  path::{Path, PathBuf},
+  rc::Rc,
+};
+
+/// The settings from the command line that every loaded game gets set up with, both the first
+/// one and the ones loaded in its place.
+pub struct EmulatorSetup {
+  /// The DMG boot ROM that games start in, if given.
+  pub boot_rom: Option<[u8; BOOT_ROM_SIZE]>,
+  /// Whether games run in CGB mode.
+  pub cgb_mode: bool,
+  /// The preset of accuracy settings.
+  pub accuracy_profile: AccuracyProfile,
+  /// How opposing directions on the D-pad get resolved.
+  pub socd_mode: SocdMode,
+  /// The number of instructions after which the watchdog warns about a stuck CPU, if enabled.
+  pub watchdog_threshold: Option<usize>,
+  /// Whether the last executed instructions are kept, to print them if the emulator panics.
+  pub keep_trace: bool,
+  /// The log that the CPU state gets written to before every instruction, if any.
+  pub trace_log: Option<Rc<RefCell<TraceLog>>>,
+}
+
+/// The log that `--trace` writes the CPU state to, which is shared by every loaded game.
+pub struct TraceLog {
+  /// The path of the log, for error messages.
+  pub path: String,
+  /// The writer of the log file.
+  pub writer: io::BufWriter<fs::File>,
+  /// Whether writing to the log failed, which only gets reported once.
+  pub failed: bool,
+}
+
+impl EmulatorSetup {
+  /// Sets up a newly loaded game with the settings.
+  pub fn apply(&self, emulator: &mut Emulator) {
+    emulator.set_accuracy_profile(self.accuracy_profile);
+    emulator.hardware.joypad.set_socd_mode(self.socd_mode);
+
+    if let Some(threshold) = self.watchdog_threshold {
+      emulator.enable_watchdog(threshold);
+    }
+
+    if self.keep_trace {
+      emulator.enable_trace(TRACE_CAPACITY);
+    }
+
+    if let Some(trace_log) = &self.trace_log {
+      let trace_log = Rc::clone(trace_log);
+
+      emulator.set_instruction_hook(move |cpu, hardware| {
+        let TraceLog {
+          path,
+          writer,
+          failed,
+        } = &mut *trace_log.borrow_mut();
+
+        // Only report the first failure, rather than one for every instruction
+        if let Err(e) = trace::write_doctor_line(writer, cpu, hardware) {
+          if !*failed {
+            eprintln!("Failed to write to the trace log {path}: {e}");
+            *failed = true;
+          }
+        }
+      });
+    }
+  }
+}
+
+/// Loads the game at the path into a new [`Emulator`], overriding the cartridge type in the
+/// header with the [`CartridgeKind`], if given, and sets it up with the [`EmulatorSetup`]. The
+/// game starts in the boot ROM, if given, and the save file next to the game gets loaded too,
+/// if the cartridge has a battery.
+///
+/// In CGB mode, the game gets told that it runs on a CGB and gets rendered in color.
+pub fn load_emulator(
+  path: &Path,
+  cartridge_kind: Option<CartridgeKind>,
+  setup: &EmulatorSetup,
+) -> Result<Emulator, String> {
+  let EmulatorSetup {
+    boot_rom, cgb_mode, ..
+  } = *setup;
+  let rom_bytes = fs::read(path).map_err(|e| e.to_string())?;
+
+  // Anything without a whole cartridge header isn't a ROM, like a file dropped by mistake
+  if rom_bytes.len() < ROM_HEADER_END {
+    return Err("the file is too small to be a ROM".to_string());
+  }
+
+  // The boot ROM starts at address 0 and sets the registers up itself
+  let mut cpu = if boot_rom.is_some() {
+    Cpu::new()
+  } else {
+    Cpu::with_register_defaults()
+  };
+  let mut hardware =
+    Hardware::with_boot_rom(rom_bytes, cartridge_kind, boot_rom).map_err(|e| e.to_string())?;
+
+  if !hardware.cartridge_header().checksum_matches() {
+    eprintln!(
+      "Warning: the header checksum of {} doesn't match, so the ROM may be corrupt",
+      path.display()
+    );
+  }
+
+  if !hardware.cartridge.verify_checksum() {
+    eprintln!(
+      "Warning: the ROM checksum of {} doesn't match, so the ROM may be corrupt or trimmed",
+      path.display()
+    );
+  }
+
+  if cgb_mode {
+    // Games check for `0x11` in A, which the CGB boot ROM leaves behind, to detect a CGB
+    cpu.registers.a = CGB_REGISTER_A;
+    hardware.ppu.set_cgb_mode(true);
+  }
+
+  if hardware.cartridge.has_battery() {
+    match fs::read(path.with_extension(SAVE_EXTENSION)) {
+      Ok(save) => hardware.cartridge.load_ram(&save),
+      // The game hasn't been saved yet
+      Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+      Err(e) => return Err(format!("failed to read the save file: {e}")),
+    }
+  }
+
+  let mut emulator = Emulator::new(cpu, hardware);
+
+  setup.apply(&mut emulator);
+
+  Ok(emulator)
+}
+
+/// Reads a DMG boot ROM, which needs to be exactly [`BOOT_ROM_SIZE`] bytes.
+pub fn load_boot_rom(path: &Path) -> Result<[u8; BOOT_ROM_SIZE], String> {
+  let bytes = fs::read(path).map_err(|e| e.to_string())?;
+
+  bytes
+    .try_into()
+    .map_err(|bytes: Vec<u8>| format!("expected {BOOT_ROM_SIZE} bytes, got {}", bytes.len()))
+}
+
+/// Writes the battery-backed RAM of the game to a save file next to the ROM, if it has any.
+pub fn save_game(emulator: &Emulator, rom_path: &Path) {
+  let Some(ram) = emulator.hardware.cartridge.save_ram() else {
+    return;
+  };
+
+  let save_path = rom_path.with_extension(SAVE_EXTENSION);
+
+  if let Err(e) = fs::write(&save_path, ram) {
+    eprintln!("Failed to save the game to {}: {e}", save_path.display());
+  }
+}
+
+/// Replaces the running game with a new one, keeping the settings that belong to the frontend,
+/// like the volume, the audio output and the link cable.
+fn swap_emulator(emulator: &mut Emulator, mut new_emulator: Emulator) {
+  new_emulator
+    .hardware
+    .inherit_settings(&mut emulator.hardware);
+
+  *emulator = new_emulator;
+}
+
+/// Loads the game at the path in place of the running game, after saving the running game.
+///
+/// The running game keeps going if the new one can't be loaded.
+pub fn switch_game(
+  emulator: &mut Emulator,
+  rom_path: &mut PathBuf,
+  path: PathBuf,
+  setup: &EmulatorSetup,
+) -> Result<(), String> {
+  // Save before loading, in case the same game gets loaded again
+  save_game(emulator, rom_path);
+
+  // `--force-mbc` only applies to the game from the command line
+  let new_emulator = load_emulator(&path, None, setup)
+    .map_err(|e| format!("Failed to load {}: {e}", path.display()))?;
+
+  swap_emulator(emulator, new_emulator);
+  *rom_path = path;
+
+  Ok(())
+}
+
+/// Returns the title of the window while the game is running, which is the title in the
+/// cartridge header, or the name of the ROM for games without one.
+pub fn window_title(emulator: &Emulator, rom_path: &Path) -> String {
+  let title = &emulator.hardware.cartridge_header().title;
+
+  if !title.is_empty() {
+    return format!("Gameboy - {title}");
+  }
+
+  match rom_path.file_stem() {
+    Some(name) => format!("Gameboy - {}", name.to_string_lossy()),
+    None => "Gameboy".to_string(),
+  }
+}
+
+/// Steps a frame of the emulator, printing the instruction trace before continuing to panic
+/// if the emulator panics. Returns whether a frame was completed.
+pub fn step_emulator(emulator: &mut Emulator) -> bool {
+  dump_trace_on_panic(emulator, Emulator::step)
+}
+
+/// Runs exactly a frame's worth of T-cycles, printing the instruction trace before continuing
+/// to panic if the emulator panics.
+pub fn run_emulator_frame(emulator: &mut Emulator) {
+  dump_trace_on_panic(emulator, Emulator::run_frame);
+}
+
+/// Steps the emulator by a frame, stopping early at a breakpoint. Returns whether a breakpoint
+/// was hit.
+pub fn step_emulator_with_breakpoints(
+  emulator: &mut Emulator,
+  breakpoints: &BTreeSet<u16>,
+) -> bool {
+  dump_trace_on_panic(emulator, |emulator| {
+    emulator.step_with_breakpoints(breakpoints)
+  })
+}
+
+/// Steps the emulator to the next instruction, for the debugger.
+pub fn step_instruction(emulator: &mut Emulator) {
+  if dump_trace_on_panic(emulator, Emulator::step_instruction).is_none() {
+    eprintln!("The CPU didn't get to the next instruction, since it's halted or stopped");
+  }
+}
+
+/// Runs the step, printing the trace of the last executed instructions if it panics.
+fn dump_trace_on_panic<T>(emulator: &mut Emulator, step: impl FnOnce(&mut Emulator) -> T) -> T {
+  match panic::catch_unwind(AssertUnwindSafe(|| step(emulator))) {
+    Ok(value) => value,
+    Err(payload) => {
+      if let Some(trace) = emulator.dump_trace() {
+        eprintln!("Last executed instructions, oldest first:\n{trace}");
+      }
+
+      panic::resume_unwind(payload);
+    }
+  }
+}
+
+/// The end of the cartridge header, which every ROM has.
+const ROM_HEADER_END: usize = 0x150;
+
+/// The extension of the save files that are written next to the ROMs.
+const SAVE_EXTENSION: &str = "sav";
+
+/// The number of executed instructions to keep in the trace when debugging.
+const TRACE_CAPACITY: usize = 256;
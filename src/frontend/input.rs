@@ -0,0 +1,180 @@
+use gameboy_emu::hardware::joypad::Button;
+
+use toml_edit::{DocumentMut, Value};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+use std::{collections::HashMap, fs, path::Path};
+
+/// The Gameboy buttons that keys press.
+pub type KeyBindings = HashMap<KeyCode, Button>;
+
+/// Converts a winit key into a Gameboy button, through the key bindings.
+pub fn convert_button(physical_key: &PhysicalKey, key_bindings: &KeyBindings) -> Option<Button> {
+  match physical_key {
+    PhysicalKey::Code(key_code) => key_bindings.get(key_code).copied(),
+    PhysicalKey::Unidentified(_) => None,
+  }
+}
+
+/// Returns the key bindings that get used when there's no key bindings file.
+pub fn default_key_bindings() -> KeyBindings {
+  HashMap::from([
+    (KeyCode::KeyW, Button::Up),
+    (KeyCode::ArrowUp, Button::Up),
+    (KeyCode::KeyS, Button::Down),
+    (KeyCode::ArrowDown, Button::Down),
+    (KeyCode::KeyA, Button::Left),
+    (KeyCode::ArrowLeft, Button::Left),
+    (KeyCode::KeyD, Button::Right),
+    (KeyCode::ArrowRight, Button::Right),
+    (KeyCode::KeyZ, Button::A),
+    (KeyCode::KeyX, Button::B),
+    (KeyCode::Enter, Button::Start),
+    (KeyCode::Backspace, Button::Select),
+  ])
+}
+
+/// Loads the key bindings from a TOML file, where each button is set to a key or a list of
+/// keys, like `a = "KeyZ"` or `up = ["KeyW", "ArrowUp"]`.
+///
+/// The buttons that aren't in the file keep their default keys.
+pub fn load_key_bindings(path: &Path) -> Result<KeyBindings, String> {
+  let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+  let document = text
+    .parse::<DocumentMut>()
+    .map_err(|e| format!("{}: {e}", path.display()))?;
+  let mut key_bindings = default_key_bindings();
+
+  for (name, item) in document.iter() {
+    let button = name
+      .parse::<Button>()
+      .map_err(|_| format!("unknown button `{name}`"))?;
+    let keys = match item.as_value() {
+      Some(Value::String(key)) => vec![key.value().as_str()],
+      Some(Value::Array(keys)) => keys
+        .iter()
+        .map(|key| {
+          key
+            .as_str()
+            .ok_or_else(|| format!("the keys of `{name}` must be strings"))
+        })
+        .collect::<Result<_, _>>()?,
+      _ => return Err(format!("`{name}` must be a key or a list of keys")),
+    };
+
+    // The keys in the file replace the default keys of the button
+    key_bindings.retain(|_, bound_button| *bound_button != button);
+
+    for key in keys {
+      let key_code = parse_key_code(key).ok_or_else(|| format!("unknown key `{key}`"))?;
+
+      key_bindings.insert(key_code, button);
+    }
+  }
+
+  Ok(key_bindings)
+}
+
+/// Parses the name of a [`KeyCode`], which is the same as the name of its variant.
+fn parse_key_code(name: &str) -> Option<KeyCode> {
+  Some(match name {
+    "KeyA" => KeyCode::KeyA,
+    "KeyB" => KeyCode::KeyB,
+    "KeyC" => KeyCode::KeyC,
+    "KeyD" => KeyCode::KeyD,
+    "KeyE" => KeyCode::KeyE,
+    "KeyF" => KeyCode::KeyF,
+    "KeyG" => KeyCode::KeyG,
+    "KeyH" => KeyCode::KeyH,
+    "KeyI" => KeyCode::KeyI,
+    "KeyJ" => KeyCode::KeyJ,
+    "KeyK" => KeyCode::KeyK,
+    "KeyL" => KeyCode::KeyL,
+    "KeyM" => KeyCode::KeyM,
+    "KeyN" => KeyCode::KeyN,
+    "KeyO" => KeyCode::KeyO,
+    "KeyP" => KeyCode::KeyP,
+    "KeyQ" => KeyCode::KeyQ,
+    "KeyR" => KeyCode::KeyR,
+    "KeyS" => KeyCode::KeyS,
+    "KeyT" => KeyCode::KeyT,
+    "KeyU" => KeyCode::KeyU,
+    "KeyV" => KeyCode::KeyV,
+    "KeyW" => KeyCode::KeyW,
+    "KeyX" => KeyCode::KeyX,
+    "KeyY" => KeyCode::KeyY,
+    "KeyZ" => KeyCode::KeyZ,
+    "Digit0" => KeyCode::Digit0,
+    "Digit1" => KeyCode::Digit1,
+    "Digit2" => KeyCode::Digit2,
+    "Digit3" => KeyCode::Digit3,
+    "Digit4" => KeyCode::Digit4,
+    "Digit5" => KeyCode::Digit5,
+    "Digit6" => KeyCode::Digit6,
+    "Digit7" => KeyCode::Digit7,
+    "Digit8" => KeyCode::Digit8,
+    "Digit9" => KeyCode::Digit9,
+    "Numpad0" => KeyCode::Numpad0,
+    "Numpad1" => KeyCode::Numpad1,
+    "Numpad2" => KeyCode::Numpad2,
+    "Numpad3" => KeyCode::Numpad3,
+    "Numpad4" => KeyCode::Numpad4,
+    "Numpad5" => KeyCode::Numpad5,
+    "Numpad6" => KeyCode::Numpad6,
+    "Numpad7" => KeyCode::Numpad7,
+    "Numpad8" => KeyCode::Numpad8,
+    "Numpad9" => KeyCode::Numpad9,
+    "NumpadEnter" => KeyCode::NumpadEnter,
+    "ArrowUp" => KeyCode::ArrowUp,
+    "ArrowDown" => KeyCode::ArrowDown,
+    "ArrowLeft" => KeyCode::ArrowLeft,
+    "ArrowRight" => KeyCode::ArrowRight,
+    "Enter" => KeyCode::Enter,
+    "Backspace" => KeyCode::Backspace,
+    "Space" => KeyCode::Space,
+    "Tab" => KeyCode::Tab,
+    "Escape" => KeyCode::Escape,
+    "ControlLeft" => KeyCode::ControlLeft,
+    "ControlRight" => KeyCode::ControlRight,
+    "AltLeft" => KeyCode::AltLeft,
+    "AltRight" => KeyCode::AltRight,
+    "Comma" => KeyCode::Comma,
+    "Period" => KeyCode::Period,
+    "Slash" => KeyCode::Slash,
+    "Semicolon" => KeyCode::Semicolon,
+    "Quote" => KeyCode::Quote,
+    "BracketLeft" => KeyCode::BracketLeft,
+    "BracketRight" => KeyCode::BracketRight,
+    "Backslash" => KeyCode::Backslash,
+    "Backquote" => KeyCode::Backquote,
+    "Minus" => KeyCode::Minus,
+    "Equal" => KeyCode::Equal,
+    _ => return None,
+  })
+}
+
+/// Returns the hex digit that the key types, if any.
+pub fn hex_digit(key: KeyCode) -> Option<char> {
+  Some(match key {
+    KeyCode::Digit0 => '0',
+    KeyCode::Digit1 => '1',
+    KeyCode::Digit2 => '2',
+    KeyCode::Digit3 => '3',
+    KeyCode::Digit4 => '4',
+    KeyCode::Digit5 => '5',
+    KeyCode::Digit6 => '6',
+    KeyCode::Digit7 => '7',
+    KeyCode::Digit8 => '8',
+    KeyCode::Digit9 => '9',
+    KeyCode::KeyA => 'A',
+    KeyCode::KeyB => 'B',
+    KeyCode::KeyC => 'C',
+    KeyCode::KeyD => 'D',
+    KeyCode::KeyE => 'E',
+    KeyCode::KeyF => 'F',
+    _ => return None,
+  })
+}
+
+/// The key bindings file that gets loaded from the working directory, if it exists.
+pub const DEFAULT_KEY_BINDINGS_PATH: &str = "key_bindings.toml";
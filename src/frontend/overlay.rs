@@ -0,0 +1,805 @@
+use gameboy_emu::{
+  emulator::Emulator,
+  hardware::{apu::Apu, ppu::Ppu},
+};
+
+use std::{
+  collections::{BTreeSet, VecDeque},
+  fmt::Write,
+  time::{Duration, Instant},
+};
+
+use super::{
+  font::{DEFAULT_CHARACTER_HEIGHT, DEFAULT_CHARACTER_WIDTH, get_character_bitmap},
+  render::lerp_color,
+};
+
+/// What the VRAM viewer shows on top of the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VramView {
+  /// The tile data at `0x8000-0x97FF`, as a sheet of 16 by 24 tiles.
+  Tiles,
+  /// The background map at `0x9800-0x9BFF`.
+  LowMap,
+  /// The background map at `0x9C00-0x9FFF`.
+  HighMap,
+}
+
+impl VramView {
+  /// Returns the view that comes after this one, or `None` to close the viewer.
+  pub fn next(self) -> Option<Self> {
+    match self {
+      VramView::Tiles => Some(VramView::LowMap),
+      VramView::LowMap => Some(VramView::HighMap),
+      VramView::HighMap => None,
+    }
+  }
+
+  /// Returns the label that gets drawn above the view.
+  fn label(self) -> &'static str {
+    match self {
+      VramView::Tiles => "TILES",
+      VramView::LowMap => "MAP 9800",
+      VramView::HighMap => "MAP 9C00",
+    }
+  }
+
+  /// Returns the width and height of the view in pixels.
+  fn size(self) -> (u32, u32) {
+    match self {
+      VramView::Tiles => (TILE_SHEET_COLUMNS * 8, TILE_SHEET_ROWS * 8),
+      VramView::LowMap | VramView::HighMap => (BACKGROUND_MAP_SIZE, BACKGROUND_MAP_SIZE),
+    }
+  }
+}
+
+/// The area of a frame that the game gets drawn into.
+pub struct Viewport {
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+  /// How much the game is scaled up by.
+  pub scale: f64,
+}
+
+/// The state of the built-in debugger.
+#[derive(Debug, Default)]
+pub struct Debugger {
+  /// Whether the debugger is open, which pauses the game between instructions.
+  pub is_open: bool,
+  /// The addresses of the instructions that open the debugger before they get executed.
+  pub breakpoints: BTreeSet<u16>,
+  /// The hex digits of the breakpoint address that's being typed.
+  pub address_input: String,
+}
+
+/// A short message shown at the bottom of the game, which fades out once its time is up.
+struct Toast {
+  /// The text of the message.
+  text: String,
+  /// When the message disappears.
+  expires_at: Instant,
+}
+
+/// The messages that give feedback on actions, like saving the state, oldest first.
+pub struct Toasts {
+  toasts: VecDeque<Toast>,
+}
+
+impl Toasts {
+  /// Creates an empty queue of toasts.
+  pub fn new() -> Self {
+    Self {
+      toasts: VecDeque::with_capacity(MAX_TOASTS),
+    }
+  }
+
+  /// Shows the text for the duration, under the toasts that are already shown.
+  pub fn push_toast(&mut self, text: impl Into<String>, duration: Duration) {
+    if self.toasts.len() == MAX_TOASTS {
+      self.toasts.pop_front();
+    }
+
+    self.toasts.push_back(Toast {
+      text: text.into(),
+      expires_at: Instant::now() + duration,
+    });
+  }
+
+  /// Removes the toasts that expired.
+  pub fn remove_expired(&mut self, now: Instant) {
+    self.toasts.retain(|toast| now < toast.expires_at);
+  }
+
+  /// Returns the text of each toast that's shown, with its opacity from 0 to 1, oldest first.
+  fn visible(&self, now: Instant) -> impl DoubleEndedIterator<Item = (&str, f64)> {
+    self.toasts.iter().filter_map(move |toast| {
+      let remaining = toast.expires_at.checked_duration_since(now)?;
+      let opacity = (remaining.as_secs_f64() / TOAST_FADE_DURATION.as_secs_f64()).min(1.0);
+
+      Some((toast.text.as_str(), opacity))
+    })
+  }
+}
+
+/// What gets drawn on top of the game.
+pub struct Overlays<'a> {
+  /// The frames per second to show, if the debug info is shown.
+  pub fps: Option<f64>,
+  /// The emulation speed to show as a percentage of the Gameboy's frame rate, if the debug
+  /// info is shown.
+  pub speed: Option<f64>,
+  /// Whether to show the progress of a running OAM DMA transfer.
+  pub show_dma_progress: bool,
+  /// The toasts to show at the bottom of the game.
+  pub toasts: &'a Toasts,
+  /// The time that the overlays are drawn at, which toasts fade out by.
+  pub now: Instant,
+  /// Whether the game is being recorded into a GIF.
+  pub recording: bool,
+  /// Whether the game is paused.
+  pub paused: bool,
+  /// The debugger, if it's open.
+  pub debugger: Option<&'a Debugger>,
+  /// What the VRAM viewer shows, if it's open.
+  pub vram_view: Option<VramView>,
+  /// The colors of the shades, for drawing the VRAM viewer.
+  pub shade_colors: [u32; 4],
+  /// Whether to show the OAM entries and the sprites on the current scanline.
+  pub show_oam: bool,
+  /// The number of times that the audio device ran out of samples.
+  pub audio_underruns: u64,
+}
+
+/// Draws the overlays into the frame, on top of the game in the viewport.
+pub fn draw_overlays(
+  frame: &mut [u32],
+  frame_width: u32,
+  viewport: &Viewport,
+  emulator: &Emulator,
+  overlays: &Overlays,
+  text_buffer: &mut String,
+) {
+  const RED_COLOR: u32 = 0x00FF0000;
+  const TOAST_TEXT_PADDING: u32 = 2;
+
+  let scale = viewport.scale as u32;
+
+  // The viewer covers the game, so it goes under the rest of the overlays
+  if let Some(view) = overlays.vram_view {
+    draw_vram_view(
+      frame,
+      frame_width,
+      viewport,
+      &emulator.hardware.ppu,
+      view,
+      overlays.shade_colors,
+    );
+  }
+
+  if let Some(fps) = overlays.fps {
+    const FPS_X_POS: u32 = 2;
+    const FPS_Y_POS: u32 = 2;
+
+    text_buffer.clear();
+
+    write!(text_buffer, "FPS: {:.1}", fps).unwrap();
+
+    draw_text(
+      text_buffer,
+      frame,
+      frame_width,
+      FPS_X_POS,
+      FPS_Y_POS,
+      RED_COLOR,
+      scale,
+    );
+
+    const VOLUME_TEXT_PADDING: u32 = 10;
+
+    text_buffer.clear();
+
+    write!(text_buffer, "{} %", get_volume(&emulator.hardware.apu)).unwrap();
+
+    let volume_text_width = get_text_pixel_width(text_buffer, scale);
+    let volume_x = frame_width.saturating_sub(volume_text_width + VOLUME_TEXT_PADDING);
+    let volume_y = 2;
+
+    draw_text(
+      text_buffer,
+      frame,
+      frame_width,
+      volume_x,
+      volume_y,
+      RED_COLOR,
+      scale,
+    );
+
+    let line_height = (DEFAULT_CHARACTER_HEIGHT + 2) * scale;
+
+    // Show how fast the game runs compared to a Gameboy under the FPS, which tells apart a slow
+    // host from a window that redraws less often
+    if let Some(speed) = overlays.speed {
+      text_buffer.clear();
+
+      write!(text_buffer, "SPEED: {speed:.0} %").unwrap();
+
+      draw_text(
+        text_buffer,
+        frame,
+        frame_width,
+        FPS_X_POS,
+        FPS_Y_POS + line_height,
+        RED_COLOR,
+        scale,
+      );
+    }
+
+    // Show how often the audio ran dry or overflowed, under the speed
+    let audio_stats = [
+      ("UNDERRUNS", overlays.audio_underruns),
+      ("OVERRUNS", emulator.hardware.apu.dropped_samples()),
+    ];
+
+    for (line, (label, count)) in audio_stats.into_iter().enumerate() {
+      text_buffer.clear();
+
+      write!(text_buffer, "{label}: {count}").unwrap();
+
+      draw_text(
+        text_buffer,
+        frame,
+        frame_width,
+        FPS_X_POS,
+        FPS_Y_POS + line_height * (line as u32 + 2),
+        RED_COLOR,
+        scale,
+      );
+    }
+  }
+
+  if overlays.show_dma_progress {
+    if let Some(bytes) = emulator.hardware.ppu.dma_bytes_transferred() {
+      // Draw the bar along the bottom of the game, filling a pixel per byte
+      let bar_height = scale.max(1) * 2;
+
+      draw_progress_bar(
+        frame,
+        frame_width,
+        viewport.x,
+        viewport.y + viewport.height - bar_height,
+        viewport.width,
+        bar_height,
+        bytes as f64 / DMA_TRANSFER_BYTES as f64,
+      );
+    }
+  }
+
+  // Stack the toasts up from the bottom of the game, with the newest one at the bottom
+  let line_height = (DEFAULT_CHARACTER_HEIGHT + 2) * scale;
+  let toasts_bottom = (viewport.y + viewport.height)
+    .saturating_sub(DEFAULT_CHARACTER_HEIGHT * scale + TOAST_TEXT_PADDING);
+
+  for (index, (text, opacity)) in overlays.toasts.visible(overlays.now).rev().enumerate() {
+    let text_width = get_text_pixel_width(text, scale);
+    let x = viewport.x + viewport.width.saturating_sub(text_width) / 2;
+    let y = toasts_bottom.saturating_sub(line_height * index as u32);
+
+    for_each_text_pixel(text, x, y, scale, |x, y| {
+      if let Some(pixel) = frame.get_mut((y * frame_width + x) as usize) {
+        *pixel = lerp_color(*pixel, RED_COLOR, opacity);
+      }
+    });
+  }
+
+  if overlays.recording {
+    const REC_TEXT_PADDING: u32 = 2;
+
+    let text_width = get_text_pixel_width("REC", scale);
+
+    draw_text(
+      "REC",
+      frame,
+      frame_width,
+      (viewport.x + viewport.width).saturating_sub(text_width + REC_TEXT_PADDING),
+      (viewport.y + viewport.height)
+        .saturating_sub(DEFAULT_CHARACTER_HEIGHT * scale + REC_TEXT_PADDING),
+      RED_COLOR,
+      scale,
+    );
+  }
+
+  if overlays.paused {
+    let text_width = get_text_pixel_width("PAUSED", scale);
+    let text_height = DEFAULT_CHARACTER_HEIGHT * scale;
+
+    // Center the text in the game
+    draw_text(
+      "PAUSED",
+      frame,
+      frame_width,
+      viewport.x + viewport.width.saturating_sub(text_width) / 2,
+      viewport.y + viewport.height.saturating_sub(text_height) / 2,
+      RED_COLOR,
+      scale,
+    );
+  }
+
+  if overlays.show_oam {
+    draw_oam_inspector(
+      frame,
+      frame_width,
+      viewport,
+      &emulator.hardware.ppu,
+      text_buffer,
+    );
+  }
+
+  if let Some(debugger) = overlays.debugger {
+    draw_debugger(
+      frame,
+      frame_width,
+      viewport,
+      emulator,
+      debugger,
+      text_buffer,
+    );
+  }
+
+  if emulator.hardware.ppu.force_render() {
+    const FORCED_TEXT_PADDING: u32 = 2;
+    const YELLOW_COLOR: u32 = 0x00FFFF00;
+
+    // Make it obvious that what's on screen isn't what the game shows
+    let text_height = DEFAULT_CHARACTER_HEIGHT * scale;
+
+    draw_text(
+      "LCD FORCED",
+      frame,
+      frame_width,
+      viewport.x + FORCED_TEXT_PADDING,
+      (viewport.y + viewport.height).saturating_sub(text_height + FORCED_TEXT_PADDING),
+      YELLOW_COLOR,
+      scale,
+    );
+  }
+}
+
+/// Draws the VRAM view over the game, scaled to fit in the viewport. The shades are the raw
+/// color indexes, without going through a palette register.
+fn draw_vram_view(
+  frame: &mut [u32],
+  frame_width: u32,
+  viewport: &Viewport,
+  ppu: &Ppu,
+  view: VramView,
+  shade_colors: [u32; 4],
+) {
+  const BACKGROUND_COLOR: u32 = 0x00000000;
+  const LABEL_COLOR: u32 = 0x00FF0000;
+  const LABEL_PADDING: u32 = 2;
+
+  let (view_width, view_height) = view.size();
+  let pixels = render_vram_view(ppu, view);
+
+  // Keep the aspect ratio of the view, centered in the viewport
+  let scale =
+    (viewport.width as f64 / view_width as f64).min(viewport.height as f64 / view_height as f64);
+  let scaled_width = (view_width as f64 * scale) as u32;
+  let scaled_height = (view_height as f64 * scale) as u32;
+  let x_offset = viewport.x + (viewport.width - scaled_width) / 2;
+  let y_offset = viewport.y + (viewport.height - scaled_height) / 2;
+
+  for y in viewport.y..viewport.y + viewport.height {
+    for x in viewport.x..viewport.x + viewport.width {
+      let inside = (x_offset..x_offset + scaled_width).contains(&x)
+        && (y_offset..y_offset + scaled_height).contains(&y);
+      let color = if inside {
+        let src_x = (((x - x_offset) as f64 / scale) as u32).min(view_width - 1);
+        let src_y = (((y - y_offset) as f64 / scale) as u32).min(view_height - 1);
+
+        shade_colors[pixels[(src_y * view_width + src_x) as usize] as usize]
+      } else {
+        BACKGROUND_COLOR
+      };
+
+      if let Some(pixel) = frame.get_mut((y * frame_width + x) as usize) {
+        *pixel = color;
+      }
+    }
+  }
+
+  draw_text(
+    view.label(),
+    frame,
+    frame_width,
+    viewport.x + LABEL_PADDING,
+    viewport.y + LABEL_PADDING,
+    LABEL_COLOR,
+    (viewport.scale as u32 / 2).max(1),
+  );
+}
+
+/// Renders the VRAM view into color indexes, row by row.
+fn render_vram_view(ppu: &Ppu, view: VramView) -> Vec<u8> {
+  let (width, height) = view.size();
+  let mut pixels = Vec::with_capacity((width * height) as usize);
+
+  // The maps use the tile data area that the background currently uses
+  let lcdc = ppu.read_register(LCDC_REGISTER);
+  let tile_address = |tile_index: u8| {
+    if lcdc & LCDC_TILE_DATA_MASK != 0 {
+      TILE_DATA_START + tile_index as u16 * TILE_BYTES
+    } else {
+      SIGNED_TILE_DATA_BASE.wrapping_add_signed(tile_index as i8 as i16 * TILE_BYTES as i16)
+    }
+  };
+
+  for y in 0..height {
+    for x in 0..width {
+      let (tile_x, tile_y) = (x / 8, y / 8);
+      let address = match view {
+        VramView::Tiles => {
+          TILE_DATA_START + (tile_y * TILE_SHEET_COLUMNS + tile_x) as u16 * TILE_BYTES
+        }
+        VramView::LowMap | VramView::HighMap => {
+          let map_start = if view == VramView::LowMap {
+            LOW_MAP_START
+          } else {
+            HIGH_MAP_START
+          };
+
+          tile_address(ppu.read_ram(map_start + (tile_y * 32 + tile_x) as u16))
+        }
+      };
+
+      // Each row of a tile is 2 bytes, with the low bits of the pixels first
+      let row_address = address + (y % 8) as u16 * 2;
+      let low = ppu.read_ram(row_address);
+      let high = ppu.read_ram(row_address + 1);
+      let bit = 7 - (x % 8);
+
+      pixels.push((((high >> bit) & 0x01) << 1) | ((low >> bit) & 0x01));
+    }
+  }
+
+  pixels
+}
+
+/// Draws the 40 OAM entries in two columns on the right of the game, highlighting the sprites
+/// that the PPU picks for the current scanline and the ones past the limit of 10.
+fn draw_oam_inspector(
+  frame: &mut [u32],
+  frame_width: u32,
+  viewport: &Viewport,
+  ppu: &Ppu,
+  text_buffer: &mut String,
+) {
+  const OAM_TEXT_PADDING: u32 = 2;
+  const HEADER_COLOR: u32 = 0x00FF0000;
+  const UNUSED_COLOR: u32 = 0x00A0A0A0;
+  const SELECTED_COLOR: u32 = 0x0000FF00;
+  const DROPPED_COLOR: u32 = 0x00FFFF00;
+
+  // Draw it smaller than the other overlays, so that both columns fit next to the debugger
+  let scale = (viewport.scale as u32 / 3).max(1);
+  let line_height = (DEFAULT_CHARACTER_HEIGHT + 2) * scale;
+  // Leave a space between the columns
+  let column_width = get_text_pixel_width("00 00 00 00 00 ", scale);
+  let left = (viewport.x + viewport.width).saturating_sub(column_width * 2 + OAM_TEXT_PADDING);
+  let top = viewport.y + OAM_TEXT_PADDING;
+  let ly = ppu.current_scanline();
+  let selection = ppu.sprite_selection(ly);
+
+  text_buffer.clear();
+  write!(text_buffer, "OAM LY:{ly:02X}").unwrap();
+  draw_text(
+    text_buffer,
+    frame,
+    frame_width,
+    left,
+    top,
+    HEADER_COLOR,
+    scale,
+  );
+
+  for sprite in 0..OAM_SPRITES {
+    let address = OAM_START + sprite as u16 * 4;
+    let color = if selection.selected.contains(&sprite) {
+      SELECTED_COLOR
+    } else if selection.dropped.contains(&sprite) {
+      DROPPED_COLOR
+    } else {
+      UNUSED_COLOR
+    };
+    let (column, row) = (sprite as u32 / OAM_ROWS, sprite as u32 % OAM_ROWS);
+
+    // The position in the OAM, then the Y and X-positions, the tile index and the attributes
+    text_buffer.clear();
+    write!(
+      text_buffer,
+      "{sprite:02} {:02X} {:02X} {:02X} {:02X}",
+      ppu.read_oam(address),
+      ppu.read_oam(address + 1),
+      ppu.read_oam(address + 2),
+      ppu.read_oam(address + 3)
+    )
+    .unwrap();
+    draw_text(
+      text_buffer,
+      frame,
+      frame_width,
+      left + column * column_width,
+      top + (row + 1) * line_height,
+      color,
+      scale,
+    );
+  }
+}
+
+/// Draws the registers, the upcoming instructions and the breakpoints of the debugger, on the
+/// left of the game.
+fn draw_debugger(
+  frame: &mut [u32],
+  frame_width: u32,
+  viewport: &Viewport,
+  emulator: &Emulator,
+  debugger: &Debugger,
+  text_buffer: &mut String,
+) {
+  const DEBUGGER_TEXT_PADDING: u32 = 2;
+  const DEBUGGER_COLOR: u32 = 0x00FF0000;
+
+  // Draw it smaller than the other overlays, so that it fits in the game
+  let scale = (viewport.scale as u32 / 2).max(1);
+  let line_height = (DEFAULT_CHARACTER_HEIGHT + 2) * scale;
+  let registers = &emulator.cpu().registers;
+  let pc = emulator.next_instruction_address();
+  let mut y_pos = viewport.y + DEBUGGER_TEXT_PADDING;
+  let mut draw_line = |text: &str| {
+    draw_text(
+      text,
+      frame,
+      frame_width,
+      viewport.x + DEBUGGER_TEXT_PADDING,
+      y_pos,
+      DEBUGGER_COLOR,
+      scale,
+    );
+
+    y_pos += line_height;
+  };
+
+  text_buffer.clear();
+  write!(
+    text_buffer,
+    "AF:{:02X}{:02X} BC:{:02X}{:02X}",
+    registers.a,
+    emulator.cpu().flags(),
+    registers.b,
+    registers.c
+  )
+  .unwrap();
+  draw_line(text_buffer);
+
+  text_buffer.clear();
+  write!(
+    text_buffer,
+    "DE:{:02X}{:02X} HL:{:02X}{:02X}",
+    registers.d, registers.e, registers.h, registers.l
+  )
+  .unwrap();
+  draw_line(text_buffer);
+
+  text_buffer.clear();
+  write!(
+    text_buffer,
+    "SP:{:04X} PC:{pc:04X} IME:{}",
+    registers.sp,
+    emulator.cpu().interrupt_master_enabled() as u8
+  )
+  .unwrap();
+  draw_line(text_buffer);
+  draw_line("");
+
+  // The next instructions, starting with the one whose opcode was already fetched
+  let mut address = pc;
+
+  for _ in 0..DEBUGGER_SHOWN_INSTRUCTIONS {
+    let (_, mnemonic, length) = emulator.hardware.disassemble(address);
+
+    text_buffer.clear();
+    write!(text_buffer, "{address:04X}: {mnemonic}").unwrap();
+    draw_line(text_buffer);
+
+    address = address.wrapping_add(length as u16);
+  }
+
+  draw_line("");
+
+  text_buffer.clear();
+  text_buffer.push_str("BREAK:");
+
+  for address in debugger.breakpoints.iter().take(DEBUGGER_SHOWN_BREAKPOINTS) {
+    write!(text_buffer, " {address:04X}").unwrap();
+  }
+
+  if debugger.breakpoints.len() > DEBUGGER_SHOWN_BREAKPOINTS {
+    text_buffer.push_str(" ...");
+  }
+
+  draw_line(text_buffer);
+
+  text_buffer.clear();
+  write!(text_buffer, "ADDR: {}_", debugger.address_input).unwrap();
+  draw_line(text_buffer);
+}
+
+/// Draws a progress bar into the buffer, where the progress is between 0 and 1.
+fn draw_progress_bar(
+  buffer: &mut [u32],
+  buffer_width: u32,
+  x_pos: u32,
+  y_pos: u32,
+  bar_width: u32,
+  bar_height: u32,
+  progress: f64,
+) {
+  const FILLED_COLOR: u32 = 0x00FF0000;
+  const EMPTY_COLOR: u32 = 0x00404040;
+
+  let filled_width = (bar_width as f64 * progress.clamp(0.0, 1.0)) as u32;
+
+  for y in y_pos..y_pos + bar_height {
+    for x in x_pos..x_pos + bar_width {
+      let buffer_index = (y * buffer_width + x) as usize;
+
+      if let Some(pixel) = buffer.get_mut(buffer_index) {
+        *pixel = if x - x_pos < filled_width {
+          FILLED_COLOR
+        } else {
+          EMPTY_COLOR
+        };
+      }
+    }
+  }
+}
+
+/// Draws the text into the buffer at the following x and y position.
+fn draw_text(
+  text: &str,
+  buffer: &mut [u32],
+  buffer_width: u32,
+  x_pos: u32,
+  y_pos: u32,
+  color: u32,
+  scale: u32,
+) {
+  for_each_text_pixel(text, x_pos, y_pos, scale, |draw_x, draw_y| {
+    let buffer_index = draw_y * buffer_width + draw_x;
+
+    if buffer_index < buffer.len() as u32 {
+      buffer[buffer_index as usize] = color;
+    }
+  });
+}
+
+/// Calls `plot` with the x and y position of every pixel that the text covers, when drawn at
+/// the following x and y position.
+fn for_each_text_pixel(
+  text: &str,
+  x_pos: u32,
+  y_pos: u32,
+  scale: u32,
+  mut plot: impl FnMut(u32, u32),
+) {
+  for (character_x_pos, bitmap) in text.as_bytes().iter().enumerate().flat_map(|(index, b)| {
+    get_character_bitmap(*b).map(|row| {
+      (
+        x_pos + (index as u32 * DEFAULT_CHARACTER_WIDTH * scale),
+        row,
+      )
+    })
+  }) {
+    for (row, bits) in bitmap.iter().enumerate() {
+      for col in 0..DEFAULT_CHARACTER_WIDTH {
+        let mask = 1 << (DEFAULT_CHARACTER_WIDTH - 1 - col);
+
+        if bits & mask == mask {
+          for dx in 0..scale {
+            for dy in 0..scale {
+              plot(
+                character_x_pos + col * scale + dx,
+                y_pos + row as u32 * scale + dy,
+              );
+            }
+          }
+        }
+      }
+    }
+  }
+}
+
+/// Returns the pixel width of the scaled text.
+fn get_text_pixel_width(text: &str, scale: u32) -> u32 {
+  (DEFAULT_CHARACTER_WIDTH * scale) * text.len() as u32
+}
+
+/// Returns the toast that lists the muted sound channels.
+pub fn muted_channels_text(apu: &Apu) -> String {
+  let mut text = String::with_capacity(TEXT_BUFFER_MAX_LENGTH);
+
+  text.push_str("MUTED:");
+
+  let muted_channels = (1..=4).filter(|&channel| !apu.is_channel_enabled(channel));
+  let mut any_muted = false;
+
+  for channel in muted_channels {
+    write!(text, " {channel}").unwrap();
+    any_muted = true;
+  }
+
+  if !any_muted {
+    text.push_str(" NONE");
+  }
+
+  text
+}
+
+/// Returns the toast that shows the master volume.
+pub fn volume_text(apu: &Apu) -> String {
+  format!("VOLUME: {} %", get_volume(apu))
+}
+
+/// Returns the current volume of the APU as an integer.
+fn get_volume(apu: &Apu) -> u8 {
+  (apu.volume() * 100.0).round() as u8
+}
+
+/// The maximum length of the text buffer used to draw text.
+pub const TEXT_BUFFER_MAX_LENGTH: usize = 16;
+
+/// The number of columns of tiles in the VRAM viewer's tile sheet.
+const TILE_SHEET_COLUMNS: u32 = 16;
+/// The number of rows of tiles in the VRAM viewer's tile sheet, which covers all 384 tiles.
+const TILE_SHEET_ROWS: u32 = 24;
+/// The width and height of a background map in pixels.
+const BACKGROUND_MAP_SIZE: u32 = 256;
+/// The address of the first tile in VRAM.
+const TILE_DATA_START: u16 = 0x8000;
+/// The address that signed tile indexes are relative to.
+const SIGNED_TILE_DATA_BASE: u16 = 0x9000;
+/// The number of bytes in a tile.
+const TILE_BYTES: u16 = 16;
+/// The address of the background map at `0x9800`.
+const LOW_MAP_START: u16 = 0x9800;
+/// The address of the background map at `0x9C00`.
+const HIGH_MAP_START: u16 = 0x9C00;
+/// The address of the LCD control register, `LCDC`.
+const LCDC_REGISTER: u16 = 0xFF40;
+/// The bit of `LCDC` that makes the background use unsigned tile indexes from `0x8000`.
+const LCDC_TILE_DATA_MASK: u8 = 0x10;
+
+/// The number of sprites in the OAM.
+const OAM_SPRITES: u8 = 40;
+/// The address of the OAM.
+const OAM_START: u16 = 0xFE00;
+/// The number of OAM entries in each column of the OAM inspector.
+const OAM_ROWS: u32 = 20;
+
+/// The number of hex digits in a breakpoint address typed into the debugger.
+pub const DEBUGGER_ADDRESS_DIGITS: usize = 4;
+/// The number of upcoming instructions that the debugger shows.
+const DEBUGGER_SHOWN_INSTRUCTIONS: usize = 5;
+/// The number of breakpoints that the debugger lists.
+const DEBUGGER_SHOWN_BREAKPOINTS: usize = 3;
+
+/// The number of bytes that an OAM DMA transfer copies.
+const DMA_TRANSFER_BYTES: u16 = 160;
+/// How long notices, like the name of the palette after switching to it, are shown.
+pub const NOTICE_DURATION: Duration = Duration::from_secs(2);
+/// How long toasts take to fade out at the end of their duration.
+const TOAST_FADE_DURATION: Duration = Duration::from_millis(500);
+/// The most toasts that are shown at once, where pushing another drops the oldest one.
+const MAX_TOASTS: usize = 4;
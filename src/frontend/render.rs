@@ -0,0 +1,271 @@
+use gameboy_emu::{
+  emulator::Emulator,
+  hardware::ppu::{ColorFrame, Frame},
+};
+
+#[cfg(feature = "gpu")]
+use pixels::{Pixels, PixelsBuilder, SurfaceTexture};
+use softbuffer::{Context, Surface};
+use winit::window::Window;
+
+use std::{mem, num::NonZeroU32, rc::Rc};
+
+use super::{GAMEBOY_HEIGHT, GAMEBOY_WIDTH};
+
+/// A filter used when scaling the game to the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayFilter {
+  /// Each pixel gets scaled up as-is, which is blocky at non-integer scales.
+  Nearest,
+  /// Pixels get scaled by the integer part of the scale and only the pixel edges get
+  /// interpolated, which is smooth without blurring the pixels.
+  SharpBilinear,
+}
+
+impl DisplayFilter {
+  /// Returns the filter that comes after this one.
+  pub fn next(self) -> Self {
+    match self {
+      DisplayFilter::Nearest => DisplayFilter::SharpBilinear,
+      DisplayFilter::SharpBilinear => DisplayFilter::Nearest,
+    }
+  }
+}
+
+/// The frame that's shown for the game, which only changes when the emulator completes a
+/// frame, so that blending doesn't depend on how often the window gets redrawn.
+pub struct PresentedFrame {
+  /// The shades of the shown frame.
+  frame: Box<Frame>,
+  /// The colors of the shown frame, in CGB mode.
+  color_frame: Box<ColorFrame>,
+  /// The shades of the frame before the shown one.
+  previous_frame: Box<Frame>,
+  /// The colors of the frame before the shown one, in CGB mode.
+  previous_color_frame: Box<ColorFrame>,
+  /// Whether the shown frame is in color.
+  cgb_mode: bool,
+  /// The colors of the shades that the pixels were picked with.
+  shade_colors: [u32; 4],
+  /// Whether the shown frame is blended with the one before it.
+  blending: bool,
+  /// The final color of each pixel.
+  pixels: Box<[[u32; 160]; 144]>,
+}
+
+impl PresentedFrame {
+  /// Creates a new [`PresentedFrame`] that shows a blank frame.
+  pub fn new() -> Self {
+    Self {
+      frame: Box::new([[0; 160]; 144]),
+      color_frame: Box::new([[0; 160]; 144]),
+      previous_frame: Box::new([[0; 160]; 144]),
+      previous_color_frame: Box::new([[0; 160]; 144]),
+      cgb_mode: false,
+      shade_colors: [0; 4],
+      blending: false,
+      pixels: Box::new([[0; 160]; 144]),
+    }
+  }
+
+  /// Shows the frame that the emulator just completed, blended with the last one if enabled.
+  pub fn push_frame(&mut self, emulator: &Emulator, shade_colors: [u32; 4], blending: bool) {
+    mem::swap(&mut self.frame, &mut self.previous_frame);
+    mem::swap(&mut self.color_frame, &mut self.previous_color_frame);
+
+    *self.frame = *emulator.hardware.frame_buffer();
+    *self.color_frame = *emulator.hardware.color_frame_buffer();
+    self.cgb_mode = emulator.hardware.ppu.cgb_mode();
+
+    self.update_pixels(shade_colors, blending);
+  }
+
+  /// Catches up with changes that didn't come from completing a frame, like rewinding,
+  /// loading a save state or picking another palette.
+  pub fn refresh(&mut self, emulator: &Emulator, shade_colors: [u32; 4], blending: bool) {
+    let frame_changed = self.cgb_mode != emulator.hardware.ppu.cgb_mode()
+      || *self.frame != *emulator.hardware.frame_buffer()
+      || (self.cgb_mode && *self.color_frame != *emulator.hardware.color_frame_buffer());
+
+    if frame_changed {
+      self.push_frame(emulator, shade_colors, blending);
+    } else if self.shade_colors != shade_colors || self.blending != blending {
+      self.update_pixels(shade_colors, blending);
+    }
+  }
+
+  /// Returns the color of the pixel at the coordinates.
+  pub fn pixel(&self, x: usize, y: usize) -> u32 {
+    self.pixels[y][x]
+  }
+
+  /// Picks the color of each pixel, blending the frame with the one before it if enabled.
+  fn update_pixels(&mut self, shade_colors: [u32; 4], blending: bool) {
+    for y in 0..GAMEBOY_HEIGHT as usize {
+      for x in 0..GAMEBOY_WIDTH as usize {
+        let (color, previous_color) = if self.cgb_mode {
+          (
+            rgb555_to_color(self.color_frame[y][x]),
+            rgb555_to_color(self.previous_color_frame[y][x]),
+          )
+        } else {
+          (
+            shade_colors[self.frame[y][x] as usize],
+            shade_colors[self.previous_frame[y][x] as usize],
+          )
+        };
+
+        // The LCD is too slow to fully change pixels within a frame, which games rely on to
+        // make flickering sprites look see-through
+        self.pixels[y][x] = if blending {
+          lerp_color(color, previous_color, 0.5)
+        } else {
+          color
+        };
+      }
+    }
+
+    self.shade_colors = shade_colors;
+    self.blending = blending;
+  }
+}
+
+/// What draws the frames into the window.
+pub enum Renderer {
+  /// Scales the game on the CPU and presents it with `softbuffer`.
+  Software {
+    surface: Surface<Rc<Window>, Rc<Window>>,
+    /// The frame at the size of the window.
+    window_frame: Vec<u32>,
+  },
+  /// Uploads the game as a texture and lets the GPU scale it, using `pixels`.
+  #[cfg(feature = "gpu")]
+  Gpu {
+    pixels: Box<Pixels>,
+    /// The frame at the size of the game, which the overlays get drawn on top of.
+    game_frame: Vec<u32>,
+  },
+}
+
+impl Renderer {
+  /// Resizes the renderer to the new size of the window.
+  pub fn resize(&mut self, width: u32, height: u32) {
+    match self {
+      Renderer::Software {
+        surface,
+        window_frame,
+      } => {
+        surface
+          .resize(
+            NonZeroU32::new(width).unwrap(),
+            NonZeroU32::new(height).unwrap(),
+          )
+          .unwrap();
+
+        window_frame.resize((width * height) as usize, 0);
+      }
+      #[cfg(feature = "gpu")]
+      Renderer::Gpu { pixels, .. } => {
+        if let Err(e) = pixels.resize_surface(width, height) {
+          eprintln!("Failed to resize the GPU surface: {e}");
+        }
+      }
+    }
+  }
+}
+
+/// Creates a [`Renderer`] that scales the game on the GPU, falling back to scaling it on the
+/// CPU if the GPU can't be used.
+#[cfg(feature = "gpu")]
+pub fn create_gpu_renderer(window: &Rc<Window>) -> Renderer {
+  let size = window.inner_size();
+  let surface_texture = SurfaceTexture::new(size.width, size.height, &**window);
+  // Frames get paced by the emulator, so don't wait for vsync on top of that
+  let result = PixelsBuilder::new(GAMEBOY_WIDTH, GAMEBOY_HEIGHT, surface_texture)
+    .enable_vsync(false)
+    .build();
+
+  match result {
+    Ok(pixels) => Renderer::Gpu {
+      pixels: Box::new(pixels),
+      game_frame: vec![0; (GAMEBOY_WIDTH * GAMEBOY_HEIGHT) as usize],
+    },
+    Err(e) => {
+      eprintln!("Failed to set up the GPU renderer, falling back to the CPU: {e}");
+
+      create_software_renderer(window)
+    }
+  }
+}
+
+/// Creates a [`Renderer`] that scales the game on the CPU.
+pub fn create_software_renderer(window: &Rc<Window>) -> Renderer {
+  let size = window.inner_size();
+  let context = Context::new(Rc::clone(window)).unwrap();
+  let mut surface = Surface::new(&context, Rc::clone(window)).unwrap();
+
+  surface
+    .resize(
+      NonZeroU32::new(size.width.max(1)).unwrap(),
+      NonZeroU32::new(size.height.max(1)).unwrap(),
+    )
+    .unwrap();
+
+  Renderer::Software {
+    surface,
+    window_frame: vec![0; (size.width * size.height) as usize],
+  }
+}
+
+/// Converts an RGB555 color from the color frame buffer into a color.
+fn rgb555_to_color(color: u16) -> u32 {
+  // Scale each 5-bit channel up to 8 bits by repeating its top bits
+  let channel = |shift: u16| {
+    let value = ((color >> shift) & 0x1F) as u32;
+
+    (value << 3) | (value >> 2)
+  };
+
+  (channel(0) << 16) | (channel(5) << 8) | channel(10)
+}
+
+/// Returns the 2 source pixels to interpolate between, along with the weight of the second
+/// pixel, for the destination pixel along one axis when using the sharp bilinear filter.
+pub fn sharp_bilinear_axis(dest: u32, scale: f64, src_len: u32) -> (usize, usize, f64) {
+  // The position of the destination pixel's center, in source pixels
+  let texel = (dest as f64 + 0.5) / scale;
+  // The integer scale that pixels get scaled by before interpolating
+  let prescale = scale.floor().max(1.0);
+  // Only the area within this distance from the edge of a source pixel gets interpolated
+  let region_range = 0.5 - 0.5 / prescale;
+  let center_distance = texel.fract() - 0.5;
+  let offset =
+    (center_distance - center_distance.clamp(-region_range, region_range)) * prescale + 0.5;
+
+  // Shift by -0.5 so that the sample is relative to the center of the source pixels
+  let position = (texel.floor() + offset - 0.5).max(0.0);
+  let first = (position as u32).min(src_len - 1);
+  let second = (first + 1).min(src_len - 1);
+
+  (first as usize, second as usize, position.fract())
+}
+
+/// Linearly interpolates between 2 colors, where a weight of 0 returns the first color.
+pub fn lerp_color(first: u32, second: u32, weight: f64) -> u32 {
+  let lerp_channel = |shift: u32| {
+    let a = ((first >> shift) & 0xFF) as f64;
+    let b = ((second >> shift) & 0xFF) as f64;
+
+    ((a + (b - a) * weight).round() as u32) << shift
+  };
+
+  lerp_channel(16) | lerp_channel(8) | lerp_channel(0)
+}
+
+/// Computes the scale factor for the game.
+pub fn compute_scale_factor(window_width: u32, window_height: u32) -> f64 {
+  let scale_x = window_width as f64 / GAMEBOY_WIDTH as f64;
+  let scale_y = window_height as f64 / GAMEBOY_HEIGHT as f64;
+
+  scale_x.min(scale_y).max(1.0)
+}
@@ -2,9 +2,11 @@ pub mod apu;
 pub mod cartridge;
 pub mod clock;
 pub mod cpu;
+pub mod hdma;
 pub mod joypad;
 pub mod ppu;
 pub mod registers;
+pub mod serial;
 pub mod timer;
 
 use std::{
@@ -13,67 +15,181 @@ use std::{
 };
 
 pub use cpu::Cpu;
+pub use hdma::Hdma;
 pub use joypad::Joypad;
+pub use serial::{Serial, SerialLink};
 pub use timer::Timer;
 
 use crate::{
+  disassembler::Instruction,
   hardware::{
     apu::{Apu, AudioSample},
-    cartridge::{Cartridge, Mbc1, RomOnly},
-    clock::SystemClock,
+    cartridge::{Cartridge, CartridgeHeader, CartridgeKind, RomOnly, UnsupportedCartridge},
+    clock::{SystemClock, TCycle},
+    hdma::HDMA_BLOCK_SIZE,
     joypad::{Button, ButtonAction},
-    ppu::{DmaTransfer, DmaTransferProgress, Ppu},
+    ppu::{ColorFrame, DmaTransfer, DmaTransferProgress, Frame, Ppu, PpuMode},
   },
   interrupts::{Interrupt, Interrupts},
 };
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Hardware {
   /// The internal memory available.
-  memory: [u8; MEMORY_SIZE as usize],
+  #[cfg_attr(feature = "serde", serde(with = "crate::serde_arrays::boxed_bytes"))]
+  memory: Box<[u8; MEMORY_SIZE as usize]>,
   /// The high ram.
+  #[cfg_attr(feature = "serde", serde(with = "crate::serde_arrays::bytes"))]
   high_ram: [u8; HIGH_RAM_SIZE as usize],
   /// The input joypad.
   pub joypad: Joypad,
   /// The game cartridge.
   pub cartridge: Cartridge,
+  /// The header of the game cartridge.
+  cartridge_header: CartridgeHeader,
   /// The timer.
   pub timer: Timer,
+  /// The serial port.
+  pub serial: Serial,
   /// The pixel processing unit.
   pub ppu: Ppu,
+  /// The VRAM DMA, in CGB mode.
+  pub hdma: Hdma,
   /// The audio processing unit.
   pub apu: Apu,
   /// The system clock.
   pub sys_clock: SystemClock,
   /// The enableed and requested interrupts.
   interrupts: Interrupts,
+  /// The boot ROM, if one was given.
+  #[cfg_attr(feature = "serde", serde(with = "crate::serde_arrays::optional_bytes"))]
+  boot_rom: Option<[u8; BOOT_ROM_SIZE]>,
+  /// Whether the boot ROM is mapped over the start of the cartridge ROM.
+  boot_rom_active: bool,
 }
 
 impl Hardware {
-  /// Creates a new [`Hardware`] instance from the given bytes.
-  pub fn new(bytes: Vec<u8>) -> Self {
-    let cartridge = match bytes[CARTRIDGE_TYPE as usize] {
-      0x0 => Cartridge::RomOnly(RomOnly::new(bytes)),
-      0x01..=0x03 => Cartridge::Mbc1(Mbc1::new(bytes)),
-      b => panic!("got invalid memory cartridge type: {b:02X}"),
-    };
+  /// Creates a new [`Hardware`] instance from the given bytes, failing if the cartridge type
+  /// in the header isn't supported.
+  pub fn new(bytes: Vec<u8>) -> Result<Self, UnsupportedCartridge> {
+    Self::with_cartridge_kind(bytes, None)
+  }
+
+  /// Creates a new [`Hardware`] instance from the given bytes, using the [`CartridgeKind`]
+  /// instead of the cartridge type in the header, if given.
+  pub fn with_cartridge_kind(
+    bytes: Vec<u8>,
+    kind: Option<CartridgeKind>,
+  ) -> Result<Self, UnsupportedCartridge> {
+    Self::with_boot_rom(bytes, kind, None)
+  }
+
+  /// Creates a new [`Hardware`] instance from the given bytes, running the boot ROM first if
+  /// given.
+  ///
+  /// The boot ROM is mapped over `0x0000..0x0100` until it's unmapped by writing to `0xFF50`,
+  /// so the CPU should start at address `0x0000` instead of with its register defaults.
+  pub fn with_boot_rom(
+    bytes: Vec<u8>,
+    kind: Option<CartridgeKind>,
+    boot_rom: Option<[u8; BOOT_ROM_SIZE]>,
+  ) -> Result<Self, UnsupportedCartridge> {
+    let cartridge = Cartridge::from_bytes(bytes, kind)?;
+
+    Ok(Self::with_cartridge(cartridge, boot_rom))
+  }
+
+  /// Creates a new [`Hardware`] instance around the cartridge, running the boot ROM first if
+  /// given.
+  fn with_cartridge(cartridge: Cartridge, boot_rom: Option<[u8; BOOT_ROM_SIZE]>) -> Self {
+    let mut apu = Apu::new();
+
+    // The boot ROM sets up the APU itself
+    if boot_rom.is_none() {
+      apu.power_on_state();
+    }
 
     Self {
-      memory: [0; MEMORY_SIZE as usize],
+      memory: Box::new([0; MEMORY_SIZE as usize]),
       high_ram: [0; HIGH_RAM_SIZE as usize],
       joypad: Joypad::new(),
       timer: Timer::new(),
+      serial: Serial::new(),
       ppu: Ppu::new(),
-      apu: Apu::new(),
+      hdma: Hdma::new(),
+      apu,
       interrupts: Interrupts::new(),
       sys_clock: SystemClock::new(),
+      cartridge_header: cartridge.header(),
       cartridge,
+      boot_rom_active: boot_rom.is_some(),
+      boot_rom,
     }
   }
 
+  /// Puts the hardware back into the state it powers on in, like turning the Gameboy off and
+  /// on again, but keeping the cartridge and its RAM.
+  ///
+  /// The boot ROM only runs again if `run_boot_rom` is set and one was given. Settings that
+  /// belong to the frontend, like the volume, the audio output and the link cable, are kept.
+  pub fn reset(&mut self, run_boot_rom: bool) {
+    // Take the cartridge out, leaving an empty one behind until the old hardware is dropped
+    let mut cartridge = std::mem::replace(
+      &mut self.cartridge,
+      Cartridge::RomOnly(RomOnly::new(Vec::new())),
+    );
+
+    cartridge.reset();
+
+    let mut hardware = Self::with_cartridge(cartridge, self.boot_rom.filter(|_| run_boot_rom));
+
+    // Keep the boot ROM around for the next reset, even if it isn't running now
+    hardware.boot_rom = self.boot_rom;
+    hardware.ppu.set_cgb_mode(self.ppu.cgb_mode());
+    hardware.inherit_settings(self);
+
+    *self = hardware;
+  }
+
+  /// Takes over the settings that belong to the frontend from the previous [`Hardware`], like
+  /// the volume, the audio output and the link cable.
+  pub fn inherit_settings(&mut self, previous: &mut Hardware) {
+    self.apu.set_volume(previous.apu.volume());
+    self.apu.inherit_audio_output(&mut previous.apu);
+    self.ppu.set_force_render(previous.ppu.force_render());
+    self.joypad.set_socd_mode(previous.joypad.socd_mode());
+
+    if let Some(link) = previous.disconnect_serial() {
+      self.connect_serial(link);
+    }
+  }
+
+  /// Returns the header of the game cartridge.
+  pub fn cartridge_header(&self) -> &CartridgeHeader {
+    &self.cartridge_header
+  }
+
+  /// Returns whether a boot ROM was given.
+  pub fn has_boot_rom(&self) -> bool {
+    self.boot_rom.is_some()
+  }
+
   /// Reads 8 bits of memory from the given address.
   pub fn read_byte(&self, address: u16) -> u8 {
+    // While OAM DMA is running, reading from the bus that it's copying from returns the byte
+    // that it's copying instead
+    self.read_memory(self.dma_bus_conflict(address).unwrap_or(address))
+  }
+
+  /// Reads 8 bits of memory from the given address, without any OAM DMA bus conflicts.
+  fn read_memory(&self, address: u16) -> u8 {
     match address {
+      // Boot ROM
+      0..0x100 if self.boot_rom_active => self
+        .boot_rom
+        .as_ref()
+        .map_or(0xFF, |boot_rom| boot_rom[address as usize]),
       // ROM
       0..0x4000 => self.cartridge.read_rom(address),
       // ROM, bank N
@@ -116,8 +232,22 @@ impl Hardware {
     }
   }
 
+  /// Disassembles the instruction at the address, returning it along with its mnemonic and its
+  /// length in bytes.
+  pub fn disassemble(&self, address: u16) -> (Instruction, String, u8) {
+    let bytes = [0, 1, 2].map(|offset| self.read_byte(address.wrapping_add(offset)));
+    let instruction = Instruction::decode(address, bytes);
+
+    (instruction, instruction.to_string(), instruction.length())
+  }
+
   /// Writes 8-bits to memory at the specified address.
   pub fn write_byte(&mut self, address: u16, value: u8) {
+    // Writes to the bus that OAM DMA is copying from don't go through
+    if self.dma_bus_conflict(address).is_some() {
+      return;
+    }
+
     match address {
       // ROM
       0x0000..0x4000 => self.cartridge.write_rom(address, value),
@@ -156,56 +286,89 @@ impl Hardware {
     }
   }
 
+  /// Reads 16 bits of memory from the given address, in little-endian order.
+  ///
+  /// The upper byte is read from the next address, wrapping around at `0xFFFF`.
+  pub fn read_word(&self, address: u16) -> u16 {
+    let lower = self.read_byte(address);
+    let upper = self.read_byte(address.wrapping_add(1));
+
+    u16::from_le_bytes([lower, upper])
+  }
+
+  /// Writes 16 bits to memory at the specified address, in little-endian order.
+  ///
+  /// The upper byte is written to the next address, wrapping around at `0xFFFF`.
+  pub fn write_word(&mut self, address: u16, value: u16) {
+    let [lower, upper] = value.to_le_bytes();
+
+    self.write_byte(address, lower);
+    self.write_byte(address.wrapping_add(1), upper);
+  }
+
+  /// Returns the address that a running OAM DMA is copying from, if it's on the same memory
+  /// bus as the address.
+  fn dma_bus_conflict(&self, address: u16) -> Option<u16> {
+    let source = self.ppu.dma_transfer.as_ref()?.source;
+    let index = self.ppu.dma_bytes_transferred()?;
+
+    if index >= DMA_TRANSFER_MAX_BYTES {
+      return None;
+    }
+
+    let source_address = ((source as u16) << 8) + index;
+    let bus = MemoryBus::of(address)?;
+
+    (MemoryBus::of(source_address)? == bus).then_some(source_address)
+  }
+
   /// Steps the DMA transfer by one T-cycle.
   pub fn step_dma_transfer(&mut self) {
     const DMA_TRANSFER_DELAY: u8 = 4;
 
     // This looks ugly to satisfy the borrow checker, it struggles with
     // mutable disjoint borrows :(
-    match self.ppu.dma_transfer {
-      Some(DmaTransfer {
-        source,
-        ref progress,
-      }) => {
-        match progress {
-          &DmaTransferProgress::Requested { delay_ticks: ticks } => {
-            let new_ticks = ticks + 1;
-
-            if new_ticks == DMA_TRANSFER_DELAY {
-              self.ppu.dma_transfer = Some(DmaTransfer::starting(source));
-            } else {
-              self.ppu.dma_transfer = Some(DmaTransfer::requested_with_ticks(source, new_ticks));
-            }
+    if let Some(DmaTransfer {
+      source,
+      ref progress,
+    }) = self.ppu.dma_transfer
+    {
+      match *progress {
+        DmaTransferProgress::Requested { delay_ticks: ticks } => {
+          let new_ticks = ticks + 1;
+
+          if new_ticks == DMA_TRANSFER_DELAY {
+            self.ppu.dma_transfer = Some(DmaTransfer::starting(source));
+          } else {
+            self.ppu.dma_transfer = Some(DmaTransfer::requested_with_ticks(source, new_ticks));
           }
-          &DmaTransferProgress::Transferring { ticks } => 'arm: {
-            const CYCLES_PER_TRANSFER: u16 = 4;
-            const DMA_TRANSFER_MAX_BYTES: u16 = 160;
-            const DMA_TRANSFER_DURATION: u16 = DMA_TRANSFER_MAX_BYTES * CYCLES_PER_TRANSFER;
-
-            // Check for this at the start, otherwise we would end the DMA transfer 1 T-cycle
-            // before it should actually be over. This is important to pass `oam_dma_timing`.
-            if ticks == DMA_TRANSFER_DURATION {
-              self.ppu.dma_transfer = None;
-              break 'arm;
-            }
-
-            let new_ticks = ticks + 1;
-
-            // An M-cycle has occured, so transfer a byte now
-            if new_ticks % CYCLES_PER_TRANSFER == 0 {
-              let starting_address = (source as u16) << 8;
-              let index = ticks / CYCLES_PER_TRANSFER;
-              let src_byte = self.read_byte(starting_address + index);
-
-              // Use `Ppu::write_oam` because Hardware::write_byte` checks for active DMA transfers.
-              self.ppu.write_oam(0xFE00 + index, src_byte);
-            }
-
-            self.ppu.dma_transfer = Some(DmaTransfer::starting_with_ticks(source, new_ticks))
+        }
+        DmaTransferProgress::Transferring { ticks } => 'arm: {
+          const CYCLES_PER_TRANSFER: u16 = 4;
+          const DMA_TRANSFER_DURATION: u16 = DMA_TRANSFER_MAX_BYTES * CYCLES_PER_TRANSFER;
+
+          // Check for this at the start, otherwise we would end the DMA transfer 1 T-cycle
+          // before it should actually be over. This is important to pass `oam_dma_timing`.
+          if ticks == DMA_TRANSFER_DURATION {
+            self.ppu.dma_transfer = None;
+            break 'arm;
+          }
+
+          let new_ticks = ticks + 1;
+
+          // An M-cycle has occured, so transfer a byte now
+          if new_ticks % CYCLES_PER_TRANSFER == 0 {
+            let starting_address = (source as u16) << 8;
+            let index = ticks / CYCLES_PER_TRANSFER;
+            let src_byte = self.read_memory(starting_address + index);
+
+            // Use `Ppu::write_oam` because Hardware::write_byte` checks for active DMA transfers.
+            self.ppu.write_oam(0xFE00 + index, src_byte);
           }
+
+          self.ppu.dma_transfer = Some(DmaTransfer::starting_with_ticks(source, new_ticks))
         }
       }
-      None => {}
     }
 
     // Restarted DMA transfers overwrite the previous one 4 T-cycles after requested.
@@ -219,15 +382,44 @@ impl Hardware {
     }
   }
 
+  /// Steps the VRAM DMA by a T-cycle, copying the blocks that are due.
+  pub fn step_hdma(&mut self) {
+    if self.ppu.hblank_started() {
+      self.hdma.start_hblank();
+    }
+
+    // The CPU can only be halted for whole M-cycles
+    if !matches!(self.sys_clock.t_cycle(), TCycle::T4) {
+      return;
+    }
+
+    let blocks = self.hdma.step_m_cycle();
+
+    for _ in 0..blocks as u16 * HDMA_BLOCK_SIZE {
+      let (source, destination) = self.hdma.next_addresses();
+      let byte = self.read_byte(source);
+
+      // Use `Ppu::write_ram` because the VRAM DMA isn't blocked by the PPU
+      self.ppu.write_ram(destination, byte);
+    }
+  }
+
+  /// Returns whether the CPU is halted by the VRAM DMA.
+  pub fn cpu_stalled(&self) -> bool {
+    self.hdma.cpu_stalled()
+  }
+
   /// Reads the I/O registers.
   fn read_io_register(&self, address: u16) -> u8 {
     match address {
       0xFF00 => self.joypad.read_register(),
       // Serial transfer
-      0xFF01 | 0xFF02 => 0x0,
+      0xFF01 | 0xFF02 => self.serial.read_register(address),
       0xFF04..0xFF08 => self.timer.read_register(address),
       0xFF10..0xFF27 | 0xFF30..0xFF40 => self.apu.read_register(address),
-      0xFF40..0xFF4C => self.ppu.read_register(address),
+      0xFF40..0xFF4C | 0xFF4F | 0xFF68..0xFF6C => self.ppu.read_register(address),
+      // The VRAM DMA only exists in CGB mode
+      0xFF51..0xFF56 if self.ppu.cgb_mode() => self.hdma.read_register(address),
       0xFF0F => self.interrupts.requested_bitfield(),
       _ => 0xFF,
     }
@@ -236,13 +428,16 @@ impl Hardware {
   /// Writes to the I/O registers.
   fn write_io_register(&mut self, address: u16, value: u8) {
     match address {
-      0xFF00 => self.joypad.write_register(value),
+      0xFF00 => self.joypad.write_register(&mut self.interrupts, value),
       // Serial transfer
-      0xFF01 | 0xFF02 => {}
+      0xFF01 | 0xFF02 => self.serial.write_register(address, value),
       0xFF04..0xFF08 => self.timer.write_register(address, value),
       0xFF10..0xFF27 | 0xFF30..0xFF40 => self.apu.write_register(address, value),
-      0xFF40..0xFF4C => self.ppu.write_register(address, value),
+      0xFF40..0xFF4C | 0xFF4F | 0xFF68..0xFF6C => self.ppu.write_register(address, value),
+      0xFF51..0xFF56 if self.ppu.cgb_mode() => self.hdma.write_register(address, value),
       0xFF0F => self.interrupts.set_requested(value),
+      // Any non-zero write unmaps the boot ROM until the next power cycle
+      0xFF50 if value != 0 => self.boot_rom_active = false,
       _ => {}
     }
   }
@@ -259,6 +454,11 @@ impl Hardware {
     self.timer.step(&mut self.interrupts, &self.sys_clock);
   }
 
+  /// Steps the serial port by a T-cycle.
+  pub fn step_serial(&mut self) {
+    self.serial.step(&mut self.interrupts);
+  }
+
   /// Steps the PPU by a T-cycle.
   pub fn step_ppu(&mut self) {
     self.ppu.step(&mut self.interrupts);
@@ -266,7 +466,9 @@ impl Hardware {
 
   /// Steps the APU by a T-cycle.
   pub fn step_apu(&mut self) {
-    self.apu.step();
+    let div_apu_ticked = self.timer.take_div_apu_tick();
+
+    self.apu.step(div_apu_ticked);
   }
 
   /// Steps the system clock by a T-cycle.
@@ -279,11 +481,52 @@ impl Hardware {
     self.apu.audio_buffer()
   }
 
+  /// Connects the other side of the link cable to the serial port.
+  pub fn connect_serial(&mut self, link: Box<dyn SerialLink>) {
+    self.serial.connect(link);
+  }
+
+  /// Disconnects the link cable from the serial port, returning the other side, if any.
+  pub fn disconnect_serial(&mut self) -> Option<Box<dyn SerialLink>> {
+    self.serial.disconnect()
+  }
+
+  /// Returns the last bytes that were sent over serial.
+  pub fn serial_output(&self) -> &[u8] {
+    self.serial.output()
+  }
+
+  /// Returns the number of bytes that were sent over serial, including the ones that are no
+  /// longer in [`Hardware::serial_output`].
+  pub fn serial_bytes_sent(&self) -> usize {
+    self.serial.bytes_sent()
+  }
+
   /// Gets the frame buffer from the PPU.
-  pub fn frame_buffer(&self) -> &[[u8; 160]; 144] {
+  pub fn frame_buffer(&self) -> &Frame {
     self.ppu.buffer()
   }
 
+  /// Gets the color frame buffer from the PPU, which is only rendered in CGB mode.
+  pub fn color_frame_buffer(&self) -> &ColorFrame {
+    self.ppu.color_buffer()
+  }
+
+  /// Hashes the last completely rendered frame, for comparing against a known-good frame.
+  pub fn frame_hash(&self) -> u64 {
+    self.ppu.frame_hash()
+  }
+
+  /// Returns the scanline that the PPU is on, `LY`.
+  pub fn current_scanline(&self) -> u8 {
+    self.ppu.current_scanline()
+  }
+
+  /// Returns the mode that the PPU is in.
+  pub fn ppu_mode(&self) -> PpuMode {
+    self.ppu.current_mode()
+  }
+
   /// Checks if there are any pending interrupts.
   pub fn has_pending_interrupts(&self) -> bool {
     self.interrupts.pending_bitfield() != 0
@@ -300,11 +543,55 @@ impl Hardware {
   pub fn clear_interrupt(&mut self, interrupt: Interrupt) {
     self.interrupts.clear_interrupt(interrupt);
   }
+
+  /// Sets the `IE` register directly.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn set_interrupt_enable(&mut self, value: u8) {
+    self.interrupts.set_enabled(value);
+  }
+
+  /// Sets the `IF` register directly.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn set_interrupt_flag(&mut self, value: u8) {
+    self.interrupts.set_requested(value);
+  }
+
+  /// Enables and requests the [`Interrupt`], leaving the other interrupts untouched.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn force_interrupt(&mut self, interrupt: Interrupt) {
+    self
+      .interrupts
+      .set_enabled(self.interrupts.enabled_bitfield() | interrupt as u8);
+    self.interrupts.request_interrupt(interrupt);
+  }
+}
+
+/// The memory buses that the CPU shares with OAM DMA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MemoryBus {
+  /// The bus for the cartridge and work RAM.
+  External,
+  /// The bus for VRAM.
+  Video,
+}
+
+impl MemoryBus {
+  /// Returns the bus that the address is on, or `None` if it's inside the CPU, like OAM, the I/O
+  /// registers and HRAM.
+  fn of(address: u16) -> Option<Self> {
+    match address {
+      0x8000..0xA000 => Some(Self::Video),
+      0x0000..0xFE00 => Some(Self::External),
+      _ => None,
+    }
+  }
 }
 
+/// The size of the DMG boot ROM.
+pub const BOOT_ROM_SIZE: usize = 0x100;
 /// The amount of working memory.
 const MEMORY_SIZE: u16 = 0x2000;
 /// The amount of fast, high memory.
 const HIGH_RAM_SIZE: u16 = 0x7F;
-/// The address where the cartridge type is stored.
-const CARTRIDGE_TYPE: u16 = 0x147;
+/// The number of bytes that OAM DMA copies.
+const DMA_TRANSFER_MAX_BYTES: u16 = 160;
@@ -5,9 +5,15 @@ mod wave_channel;
 
 use std::{
   collections::VecDeque,
+  fmt,
   sync::{Arc, Mutex},
 };
 
+use ringbuf::{
+  HeapCons, HeapProd, HeapRb,
+  traits::{Observer, Producer, Split},
+};
+
 use crate::{
   flags::{add_flag, is_falling_edge, is_flag_set, is_rising_edge},
   hardware::apu::{
@@ -17,6 +23,7 @@ use crate::{
 };
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Apu {
   channel1: PulseSweepChannel,
   channel2: PulseChannel,
@@ -27,14 +34,40 @@ pub struct Apu {
   nr51: u8,
   nr52: u8,
 
-  frame_sequencer_cycles: u16,
   frame_sequencer_step: u8,
 
-  dots: u16,
+  /// The time since the last sample, where a T-cycle is `SAMPLE_RATE` and a sample is due
+  /// every `GAMEBOY_CLOCK_SPEED`, so the fractional T-cycles between samples add up.
+  sample_clock: u32,
+
+  /// The charge of the capacitors of the high-pass filters on the left and right outputs.
+  capacitors: (f32, f32),
 
   volume: f32,
 
+  // The audio output belongs to the frontend, so it isn't part of the saved state
+  #[cfg_attr(feature = "serde", serde(skip))]
   audio_buffer: Arc<Mutex<VecDeque<AudioSample>>>,
+  /// The producer half of the lock-free audio ring buffer, if one is being used.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  audio_producer: Option<AudioProducer>,
+  /// The number of samples that get skipped after each output sample, for speeding up the
+  /// audio along with the emulator.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  skipped_samples: u32,
+  /// The number of samples left to skip before the next output sample.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  samples_until_output: u32,
+  /// The maximum number of samples that the audio buffer holds, if limited.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  max_queued_samples: Option<usize>,
+  /// The number of samples that were dropped because the audio output was full.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  dropped_samples: u64,
+  /// The channels that are left out of the output, as a bit for each channel, with channel
+  /// 1 in bit 0.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  muted_channels: u8,
 }
 
 impl Apu {
@@ -49,34 +82,57 @@ impl Apu {
       nr51: 0,
       nr52: 0,
 
-      frame_sequencer_cycles: 0,
       frame_sequencer_step: 0,
 
-      dots: 0,
+      sample_clock: 0,
+
+      capacitors: (0.0, 0.0),
 
       volume: 0.5,
 
       audio_buffer: Arc::new(Mutex::new(VecDeque::new())),
+      audio_producer: None,
+      skipped_samples: 0,
+      samples_until_output: 0,
+      max_queued_samples: None,
+      dropped_samples: 0,
+      muted_channels: 0,
     }
   }
 
-  /// Steps the APU by a T-cycle.
-  pub fn step(&mut self) {
-    if !self.is_enabled() {
-      return;
-    }
-
-    self.dots += 1;
+  /// Sets the registers to the values that the DMG boot ROM leaves them in.
+  pub fn power_on_state(&mut self) {
+    // Only channel 1 gets configured by the boot ROM, so the other channels read back
+    // their unused bits.
+    self.channel1.power_on_state();
 
-    self.channel1.step();
-    self.channel2.step();
-    self.channel3.step();
-    self.channel4.step();
+    self.nr50 = 0x77;
+    self.nr51 = 0xF3;
+    self.nr52 = APU_ENABLE_MASK;
+  }
 
-    self.step_frame_sequencer();
+  /// Steps the APU by a T-cycle, clocking the frame sequencer if DIV-APU ticked.
+  ///
+  /// Samples keep getting output while the APU is off, as silence, so the audio output and
+  /// anything paced by it keep running in real time.
+  pub fn step(&mut self, div_apu_ticked: bool) {
+    self.sample_clock += SAMPLE_RATE;
+
+    if self.is_enabled() {
+      self.channel1.step();
+      self.channel2.step();
+      self.channel3.step();
+      self.channel4.step();
+
+      if div_apu_ticked {
+        self.step_frame_sequencer();
+      }
+    }
 
-    if self.dots >= SAMPLES_PER_CYCLE {
-      self.dots -= SAMPLES_PER_CYCLE;
+    // There are ~95.1 T-cycles per sample, so most samples are 95 T-cycles apart and some
+    // are 96, for exactly `SAMPLE_RATE` samples per second
+    if self.sample_clock >= GAMEBOY_CLOCK_SPEED {
+      self.sample_clock -= GAMEBOY_CLOCK_SPEED;
 
       self.push_audio_sample();
     }
@@ -207,6 +263,11 @@ impl Apu {
     }
   }
 
+  /// Sets whether triggering the wave channel can corrupt wave RAM, like on DMG.
+  pub fn set_wave_ram_corruption(&mut self, enabled: bool) {
+    self.channel3.set_wave_ram_corruption(enabled);
+  }
+
   /// Increments the master volume by 10%.
   pub fn increment_volume(&mut self) {
     self.set_volume(self.volume() + VOLUME_INCREMENT);
@@ -232,12 +293,116 @@ impl Apu {
     Arc::clone(&self.audio_buffer)
   }
 
+  /// Switches the audio output over to a fixed-capacity, lock-free ring buffer and returns
+  /// the consumer half of it.
+  ///
+  /// Once switched, samples are no longer pushed to [`Apu::audio_buffer`]. Samples are
+  /// dropped while the ring buffer is full, which bounds the latency to its capacity.
+  pub fn use_ring_buffer(&mut self, capacity: usize) -> HeapCons<AudioSample> {
+    let (producer, consumer) = HeapRb::new(capacity).split();
+
+    self.audio_producer = Some(AudioProducer(producer));
+
+    consumer
+  }
+
+  /// Returns the number of samples in the audio output that the audio device hasn't
+  /// consumed yet.
+  pub fn queued_samples(&self) -> usize {
+    match &self.audio_producer {
+      Some(AudioProducer(producer)) => producer.occupied_len(),
+      None => self.audio_buffer.lock().unwrap().len(),
+    }
+  }
+
+  /// Takes over the audio output of a previous [`Apu`], so that an existing audio stream
+  /// keeps receiving samples.
+  pub fn inherit_audio_output(&mut self, previous: &mut Apu) {
+    self.audio_buffer = Arc::clone(&previous.audio_buffer);
+    self.audio_producer = previous.audio_producer.take();
+    self.skipped_samples = previous.skipped_samples;
+    self.max_queued_samples = previous.max_queued_samples;
+    self.dropped_samples = previous.dropped_samples;
+    self.muted_channels = previous.muted_channels;
+  }
+
+  /// Sets how many times faster than real time the emulator runs, so that only every
+  /// `speed`-th sample gets output and the audio device gets samples at the same rate.
+  ///
+  /// The audio plays back faster and at a higher pitch, like when fast-forwarding a tape.
+  pub fn set_output_speed(&mut self, speed: u32) {
+    self.skipped_samples = speed.max(1) - 1;
+    self.samples_until_output = 0;
+  }
+
+  /// Limits the number of samples that the audio buffer from [`Apu::audio_buffer`] holds,
+  /// dropping new samples while it's full, so the latency can't grow without bound when the
+  /// emulator runs faster than the audio device consumes samples.
+  ///
+  /// A ring buffer from [`Apu::use_ring_buffer`] is already limited by its capacity.
+  pub fn set_max_queued_samples(&mut self, max_queued_samples: Option<usize>) {
+    self.max_queued_samples = max_queued_samples;
+  }
+
+  /// Returns the number of samples that were dropped because the audio output was full,
+  /// which happens when the audio device falls behind.
+  pub fn dropped_samples(&self) -> u64 {
+    self.dropped_samples
+  }
+
+  /// Sets whether the channel, from 1 to 4, is part of the output.
+  ///
+  /// Muted channels keep running, so their timers and envelopes are where they should be
+  /// once they get unmuted.
+  pub fn set_channel_enabled(&mut self, channel: u8, enabled: bool) {
+    let mask = channel_mask(channel);
+
+    if enabled {
+      self.muted_channels &= !mask;
+    } else {
+      self.muted_channels |= mask;
+    }
+  }
+
+  /// Returns whether the channel, from 1 to 4, is part of the output.
+  pub fn is_channel_enabled(&self, channel: u8) -> bool {
+    !is_flag_set!(self.muted_channels, channel_mask(channel))
+  }
+
+  /// Queues the number of samples of silence on the audio output, so that the audio device
+  /// starts with some buffered samples instead of running dry until the APU catches up.
+  ///
+  /// This should be called after switching the audio output, since the silence doesn't carry
+  /// over to a ring buffer from [`Apu::use_ring_buffer`].
+  pub fn prefill_audio(&mut self, samples: usize) {
+    for _ in 0..samples {
+      self.output_sample(AudioSample::default());
+    }
+  }
+
   /// Pushes a new audio channel into the audio buffer.
-  fn push_audio_sample(&self) {
-    let ch1 = self.channel1.get_sample();
-    let ch2 = self.channel2.get_sample();
-    let ch3 = self.channel3.get_sample();
-    let ch4 = self.channel4.get_sample();
+  fn push_audio_sample(&mut self) {
+    // Skip the samples that don't get output when running faster than real time
+    if self.samples_until_output > 0 {
+      self.samples_until_output -= 1;
+      return;
+    }
+
+    self.samples_until_output = self.skipped_samples;
+
+    // Muted channels are silent in the mix, but keep running, and every channel is silent
+    // while the APU is off
+    let unless_muted = |channel, sample| {
+      if self.is_enabled() && self.is_channel_enabled(channel) {
+        sample
+      } else {
+        0
+      }
+    };
+    let ch1 = unless_muted(1, self.channel1.get_sample());
+    let ch2 = unless_muted(2, self.channel2.get_sample());
+    let ch3 = unless_muted(3, self.channel3.get_sample());
+    let ch4 = unless_muted(4, self.channel4.get_sample());
 
     // The system device channel outputs
     let mut left = 0.0;
@@ -277,54 +442,74 @@ impl Apu {
     left *= (left_volume + 1) as f32 / 8.0;
     right *= (right_volume + 1) as f32 / 8.0;
 
+    // The outputs go through capacitors that remove the DC offset, like on the DMG
+    left = high_pass(&mut self.capacitors.0, left);
+    right = high_pass(&mut self.capacitors.1, right);
+
     // Scale by the master volume and normalize the outputs
     let volume_scale = self.volume / 4.0;
 
     left *= volume_scale;
     right *= volume_scale;
 
-    self
-      .audio_buffer
-      .lock()
-      .unwrap()
-      .push_back(AudioSample { left, right });
+    self.output_sample(AudioSample { left, right });
   }
 
-  /// Steps the frame sequencer.
-  fn step_frame_sequencer(&mut self) {
-    self.frame_sequencer_cycles += 1;
-
-    if self.frame_sequencer_cycles == FRAME_SEQEUNCER_CYCLES {
-      match self.frame_sequencer_step & (FRAME_SEQUENCER_STEP_COUNT - 1) {
-        step @ (0 | 2 | 4 | 6) => {
-          // Length counters step every even step
-          self.channel1.step_length_timer();
-          self.channel2.step_length_timer();
-          self.channel3.step_length_timer();
-          self.channel4.step_length_timer();
-
-          // Pulse channel steps its sweep every 2nd and 6th step
-          if step == 2 || step == 6 {
-            self.channel1.step_sweep();
-          }
+  /// Sends the sample to the audio output.
+  fn output_sample(&mut self, sample: AudioSample) {
+    match &mut self.audio_producer {
+      Some(AudioProducer(producer)) => {
+        // The consumer is behind, so drop the sample rather than growing the latency
+        if producer.try_push(sample).is_err() {
+          self.dropped_samples += 1;
         }
+      }
+      None => {
+        let mut audio_buffer = self.audio_buffer.lock().unwrap();
+
+        // The consumer is behind, so drop the sample rather than growing the latency
+        if self
+          .max_queued_samples
+          .is_none_or(|max_queued_samples| audio_buffer.len() < max_queued_samples)
+        {
+          audio_buffer.push_back(sample);
+        } else {
+          self.dropped_samples += 1;
+        }
+      }
+    }
+  }
 
-        // Do nothing on 1, 3, and 5
-        1 | 3 | 5 => {}
-
-        // Step the envelopes
-        7 => {
-          self.channel1.step_envelope();
-          self.channel2.step_envelope();
-          self.channel4.step_envelope();
+  /// Steps the frame sequencer, which DIV-APU clocks every 8192 T-cycles.
+  fn step_frame_sequencer(&mut self) {
+    match self.frame_sequencer_step & (FRAME_SEQUENCER_STEP_COUNT - 1) {
+      step @ (0 | 2 | 4 | 6) => {
+        // Length counters step every even step
+        self.channel1.step_length_timer();
+        self.channel2.step_length_timer();
+        self.channel3.step_length_timer();
+        self.channel4.step_length_timer();
+
+        // Pulse channel steps its sweep every 2nd and 6th step
+        if step == 2 || step == 6 {
+          self.channel1.step_sweep();
         }
+      }
 
-        _ => unreachable!(),
+      // Do nothing on 1, 3, and 5
+      1 | 3 | 5 => {}
+
+      // Step the envelopes
+      7 => {
+        self.channel1.step_envelope();
+        self.channel2.step_envelope();
+        self.channel4.step_envelope();
       }
 
-      self.frame_sequencer_cycles = 0;
-      self.frame_sequencer_step = (self.frame_sequencer_step + 1) % FRAME_SEQUENCER_STEP_COUNT;
+      _ => unreachable!(),
     }
+
+    self.frame_sequencer_step = (self.frame_sequencer_step + 1) % FRAME_SEQUENCER_STEP_COUNT;
   }
 
   /// Returns whether the APU is enabled.
@@ -356,6 +541,12 @@ impl Apu {
   }
 }
 
+impl Default for Apu {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 /// An audio sample with a left and right channel.
 #[derive(Debug, Default, Clone)]
 pub struct AudioSample {
@@ -365,6 +556,18 @@ pub struct AudioSample {
   pub right: f32,
 }
 
+/// The producer half of the lock-free audio ring buffer.
+struct AudioProducer(HeapProd<AudioSample>);
+
+impl fmt::Debug for AudioProducer {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("AudioProducer")
+      .field("len", &self.0.occupied_len())
+      .field("capacity", &self.0.capacity())
+      .finish()
+  }
+}
+
 /// The audio channels' outputs.
 #[derive(Debug, Clone, Copy)]
 #[repr(u8)]
@@ -390,14 +593,34 @@ enum EnabledChannels {
   Channel4 = 1 << 3,
 }
 
-/// The samples per cycle.
-const SAMPLES_PER_CYCLE: u16 = (GAMEBOY_CLOCK_SPEED / SAMPLE_RATE) as u16;
+/// Runs the output through a high-pass filter, where the capacitor is charged by the output
+/// and slowly leaks, so only the changes in the output pass through.
+fn high_pass(capacitor: &mut f32, input: f32) -> f32 {
+  let output = input - *capacitor;
+
+  *capacitor = input - output * HIGH_PASS_CHARGE_FACTOR;
+
+  output
+}
+
+/// Returns the bit of the channel, from 1 to 4, in the muted channels.
+fn channel_mask(channel: u8) -> u8 {
+  assert!(
+    (1..=4).contains(&channel),
+    "apu: there's no channel {channel}"
+  );
+
+  1 << (channel - 1)
+}
+
+/// The factor that the capacitors of the high-pass filters keep their charge by, for each
+/// sample. The DMG's capacitors keep `0.999958` of it per T-cycle, which is about `0.996` for
+/// the ~95 T-cycles between samples at 44100 Hz.
+const HIGH_PASS_CHARGE_FACTOR: f32 = 0.996;
 /// The Gameboy's clock speed.
 const GAMEBOY_CLOCK_SPEED: u32 = 4_194_304;
-/// The sample rate.
-const SAMPLE_RATE: u32 = 44_100;
-/// The number of cycles per frame sequencer step.
-const FRAME_SEQEUNCER_CYCLES: u16 = 8192;
+/// The sample rate of the produced audio samples.
+pub const SAMPLE_RATE: u32 = 44_100;
 /// The step count for the frame sequenecer.
 const FRAME_SEQUENCER_STEP_COUNT: u8 = 8;
 /// The bitmask for checking whether the APU is enabled.
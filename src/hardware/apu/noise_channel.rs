@@ -2,6 +2,7 @@ use crate::flags::{is_flag_set, is_rising_edge};
 
 /// The noise channel, known as channel 4.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NoiseChannel {
   /// The length timer.
   nr41: u8,
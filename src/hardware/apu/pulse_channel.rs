@@ -2,6 +2,7 @@ use crate::flags::{is_flag_set, is_rising_edge};
 
 /// A pulse channel, known as channel 2.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PulseChannel {
   /// The sound length and wave pattern duty.
   nr21: u8,
@@ -2,6 +2,7 @@ use crate::flags::{is_flag_set, is_rising_edge};
 
 /// A sweeping pulse channel, known as channel 1.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PulseSweepChannel {
   /// The sweep register.
   nr10: u8,
@@ -56,6 +57,25 @@ impl PulseSweepChannel {
     }
   }
 
+  /// Sets the channel to the state that the DMG boot ROM leaves it in.
+  ///
+  /// The boot ROM plays the startup chime on this channel, which leaves it enabled
+  /// with its envelope having faded the volume out.
+  pub fn power_on_state(&mut self) {
+    self.nr10 = 0x00;
+    self.nr11 = 0x80;
+    self.nr12 = 0xF3;
+    self.nr13 = 0xC1;
+    self.nr14 = 0x87;
+
+    self.volume = 0;
+    self.length_timer = MAX_CHANNEL_TIMER_LENGTH;
+    self.frequency_timer = self.frequency_timer_reload() * DOTS_MULTIPLIER;
+    self.shadow_frequency = self.get_period();
+
+    self.enabled = true;
+  }
+
   /// Steps the sweeping pulse channel.
   pub fn step(&mut self) {
     // Reload the frequency timer.
@@ -391,3 +411,53 @@ const TIMER_LENGTH_ENABLE_MASK: u8 = 0b0100_0000;
 const ENVELOPE_DIRECTION_MASK: u8 = 0b0000_1000;
 /// The bitmask for the direction bit for the sweep.
 const SWEEP_DIRECTION_MASK: u8 = 0b0000_1000;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Triggers the channel at the period, with the sweep clocked every step and increasing the
+  /// period by a fraction of `1 / 2^shift`.
+  fn trigger_sweep(period: u16, shift: u8) -> PulseSweepChannel {
+    let mut channel = PulseSweepChannel::new();
+
+    channel.write_register(0xFF10, 0b0001_0000 | shift, 0);
+    channel.write_register(0xFF12, 0xF0, 0);
+    channel.write_register(0xFF13, (period & 0xFF) as u8, 0);
+    // Trigger with the length enabled, which the period write-back has to leave alone
+    channel.write_register(0xFF14, 0b1100_0000 | (period >> 8) as u8, 0);
+
+    channel
+  }
+
+  #[test]
+  fn sweep_updates_period_until_overflow() {
+    let mut channel = trigger_sweep(0x400, 2);
+
+    assert_eq!(channel.get_period(), 0x400);
+    assert!(channel.enabled());
+
+    for period in [0x500, 0x640] {
+      channel.step_sweep();
+
+      assert_eq!(channel.get_period(), period);
+      assert_eq!(channel.nr14 & 0b1111_1000, 0b1100_0000);
+      assert!(channel.enabled());
+    }
+
+    // 0x7D0 gets written back, but the check after it sees 0x9C4, which overflows
+    channel.step_sweep();
+
+    assert_eq!(channel.get_period(), 0x7D0);
+    assert_eq!(channel.nr14 & 0b1111_1000, 0b1100_0000);
+    assert!(!channel.enabled());
+  }
+
+  #[test]
+  fn sweep_disables_only_past_0x7ff() {
+    // 0x555 + (0x555 >> 1) is exactly 0x7FF
+    assert!(trigger_sweep(0x555, 1).enabled());
+    // 0x556 + (0x556 >> 1) is 0x801
+    assert!(!trigger_sweep(0x556, 1).enabled());
+  }
+}
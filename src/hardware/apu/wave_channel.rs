@@ -2,6 +2,7 @@ use crate::flags::{is_flag_set, is_rising_edge};
 
 /// A wave channel, known as sound channel 3.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WaveChannel {
   /// The DAC.
   nr30: u8,
@@ -23,6 +24,9 @@ pub struct WaveChannel {
   wave_ram_index: u8,
   wave_buffer: u8,
   cycles_since_wave_access: u8,
+
+  /// Whether triggering the channel right before it reads wave RAM corrupts wave RAM.
+  wave_ram_corruption: bool,
 }
 
 impl WaveChannel {
@@ -42,9 +46,16 @@ impl WaveChannel {
       wave_ram_index: 0,
       wave_buffer: 0,
       cycles_since_wave_access: 0,
+
+      wave_ram_corruption: true,
     }
   }
 
+  /// Sets whether triggering the channel can corrupt wave RAM, like on DMG.
+  pub fn set_wave_ram_corruption(&mut self, enabled: bool) {
+    self.wave_ram_corruption = enabled;
+  }
+
   /// Steps the wave channel.
   pub fn step(&mut self) {
     if self.cycles_since_wave_access > 0 {
@@ -229,7 +240,7 @@ impl WaveChannel {
   fn trigger(&mut self) {
     // If the wave channel gets triggered 1 T-cycle before wave RAM is read, then the wave RAM
     // gets corrupted.
-    if self.enabled && self.frequency_timer == 0 {
+    if self.wave_ram_corruption && self.enabled && self.frequency_timer == 0 {
       let offset = (self.wave_ram_index + 1) >> 1;
 
       if offset < 4 {
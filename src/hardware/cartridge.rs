@@ -1,62 +1,265 @@
+mod header;
+
+use std::fmt;
+
+pub use header::CartridgeHeader;
+
+use header::{CARTRIDGE_TYPE, GLOBAL_CHECKSUM};
+
 // A kind of cartridge.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Cartridge {
-  /// A game cartridge that only has 32kB of ROM and no RAM.
+  /// A game cartridge that only has 32kB of ROM and optionally 8kB of RAM.
   RomOnly(RomOnly),
   /// A game cartridge with memory bank controller 1.
   Mbc1(Mbc1),
+  /// A game cartridge with memory bank controller 2.
+  Mbc2(Mbc2),
+  /// A game cartridge with memory bank controller 5.
+  Mbc5(Mbc5),
 }
 
 impl Cartridge {
+  /// Creates the cartridge for the ROM, using the [`CartridgeKind`] instead of the cartridge
+  /// type in the header, if given.
+  pub fn from_bytes(
+    bytes: Vec<u8>,
+    kind: Option<CartridgeKind>,
+  ) -> Result<Cartridge, UnsupportedCartridge> {
+    Ok(match kind {
+      Some(CartridgeKind::RomOnly) => Cartridge::RomOnly(RomOnly::new(bytes)),
+      Some(CartridgeKind::Mbc1) => Cartridge::Mbc1(Mbc1::new(bytes)),
+      Some(CartridgeKind::Mbc2) => Cartridge::Mbc2(Mbc2::new(bytes)),
+      Some(CartridgeKind::Mbc5) => Cartridge::Mbc5(Mbc5::new(bytes)),
+      None => match CartridgeHeader::parse(&bytes).cartridge_type {
+        0x00 => Cartridge::RomOnly(RomOnly::new(bytes)),
+        // ROM + RAM and ROM + RAM + battery
+        0x08 | 0x09 => Cartridge::RomOnly(RomOnly::with_ram(bytes)),
+        0x01..=0x03 => Cartridge::Mbc1(Mbc1::new(bytes)),
+        0x05 | 0x06 => Cartridge::Mbc2(Mbc2::new(bytes)),
+        0x19..=0x1E => Cartridge::Mbc5(Mbc5::new(bytes)),
+        cartridge_type => return Err(UnsupportedCartridge { cartridge_type }),
+      },
+    })
+  }
+
   /// Reads the value specified by the address in ROM.
   pub fn read_rom(&self, address: u16) -> u8 {
     match self {
       Cartridge::RomOnly(cartridge) => cartridge.read_rom(address),
       Cartridge::Mbc1(cartridge) => cartridge.read_rom(address),
+      Cartridge::Mbc2(cartridge) => cartridge.read_rom(address),
+      Cartridge::Mbc5(cartridge) => cartridge.read_rom(address),
     }
   }
 
   /// Writes the value to the address in ROM.
   pub fn write_rom(&mut self, address: u16, value: u8) {
     match self {
-      // Cannot write to ROM-only cartridges
-      Cartridge::RomOnly(_) => {}
+      Cartridge::RomOnly(cartridge) => cartridge.write_rom(address, value),
       Cartridge::Mbc1(cartridge) => cartridge.write_rom(address, value),
+      Cartridge::Mbc2(cartridge) => cartridge.write_rom(address, value),
+      Cartridge::Mbc5(cartridge) => cartridge.write_rom(address, value),
     }
   }
 
   /// Reads the value in RAM of the specified address in RAM.
   pub fn read_ram(&self, address: u16) -> u8 {
     match self {
-      // This cartridge type does not have any RAM
-      Cartridge::RomOnly(_) => 0xFF,
+      Cartridge::RomOnly(cartridge) => cartridge.read_ram(address),
       Cartridge::Mbc1(cartridge) => cartridge.read_ram(address),
+      Cartridge::Mbc2(cartridge) => cartridge.read_ram(address),
+      Cartridge::Mbc5(cartridge) => cartridge.read_ram(address),
     }
   }
 
   /// Writes to the value to address in RAM.
   pub fn write_ram(&mut self, address: u16, value: u8) {
     match self {
-      // No-op because this cartridge type has no RAM
-      Cartridge::RomOnly(_) => {}
+      Cartridge::RomOnly(cartridge) => cartridge.write_ram(address, value),
       Cartridge::Mbc1(cartridge) => cartridge.write_ram(address, value),
+      Cartridge::Mbc2(cartridge) => cartridge.write_ram(address, value),
+      Cartridge::Mbc5(cartridge) => cartridge.write_ram(address, value),
+    }
+  }
+
+  /// Puts the memory bank controller back into the state it powers on in, keeping the RAM.
+  pub fn reset(&mut self) {
+    match self {
+      Cartridge::RomOnly(_) => {}
+      Cartridge::Mbc1(cartridge) => cartridge.reset(),
+      Cartridge::Mbc2(cartridge) => cartridge.reset(),
+      Cartridge::Mbc5(cartridge) => cartridge.reset(),
+    }
+  }
+
+  /// Parses the cartridge header from the ROM.
+  pub fn header(&self) -> CartridgeHeader {
+    CartridgeHeader::parse(self.rom())
+  }
+
+  /// Returns whether the checksum of the whole ROM at `0x014E-0x014F` matches the ROM.
+  ///
+  /// Nothing on the Gameboy checks it, so a mismatch only hints at a corrupt or trimmed dump.
+  pub fn verify_checksum(&self) -> bool {
+    let rom = self.rom();
+    let Some(&[high, low]) = rom.get(GLOBAL_CHECKSUM..GLOBAL_CHECKSUM + 2) else {
+      return false;
+    };
+    let checksum = rom
+      .iter()
+      .enumerate()
+      .filter(|&(address, _)| address != GLOBAL_CHECKSUM && address != GLOBAL_CHECKSUM + 1)
+      .fold(0u16, |sum, (_, &byte)| sum.wrapping_add(byte as u16));
+
+    checksum == u16::from_be_bytes([high, low])
+  }
+
+  /// Returns whether the cartridge has a battery that keeps the RAM around, going by the
+  /// cartridge type in the header.
+  pub fn has_battery(&self) -> bool {
+    // ROM + RAM + battery, MBC1 + RAM + battery, MBC2 + battery, MBC5 + RAM + battery and
+    // MBC5 + rumble + RAM + battery
+    matches!(
+      self.rom().get(CARTRIDGE_TYPE),
+      Some(0x03 | 0x06 | 0x09 | 0x1B | 0x1E)
+    )
+  }
+
+  /// Returns the RAM to save to disk, if the cartridge has RAM and a battery.
+  pub fn save_ram(&self) -> Option<&[u8]> {
+    let ram = self.ram();
+
+    (self.has_battery() && !ram.is_empty()).then_some(ram)
+  }
+
+  /// Loads RAM that was saved from [`Cartridge::save_ram`]. Bytes past the size of the RAM
+  /// are ignored.
+  pub fn load_ram(&mut self, bytes: &[u8]) {
+    let ram = self.ram_mut();
+    let length = ram.len().min(bytes.len());
+
+    ram[..length].copy_from_slice(&bytes[..length]);
+  }
+
+  /// Moves the ROM of the other cartridge into this one, like after restoring a save state,
+  /// which leaves the ROM out.
+  #[cfg(feature = "save-states")]
+  pub(crate) fn take_rom_from(&mut self, other: &mut Cartridge) {
+    *self.rom_mut() = std::mem::take(other.rom_mut());
+  }
+
+  /// Returns the ROM of the cartridge.
+  fn rom(&self) -> &[u8] {
+    match self {
+      Cartridge::RomOnly(cartridge) => &cartridge.rom,
+      Cartridge::Mbc1(cartridge) => &cartridge.rom,
+      Cartridge::Mbc2(cartridge) => &cartridge.rom,
+      Cartridge::Mbc5(cartridge) => &cartridge.rom,
+    }
+  }
+
+  /// Returns the ROM of the cartridge mutably.
+  #[cfg(feature = "save-states")]
+  fn rom_mut(&mut self) -> &mut Vec<u8> {
+    match self {
+      Cartridge::RomOnly(cartridge) => &mut cartridge.rom,
+      Cartridge::Mbc1(cartridge) => &mut cartridge.rom,
+      Cartridge::Mbc2(cartridge) => &mut cartridge.rom,
+      Cartridge::Mbc5(cartridge) => &mut cartridge.rom,
+    }
+  }
+
+  /// Returns the RAM of the cartridge.
+  fn ram(&self) -> &[u8] {
+    match self {
+      Cartridge::RomOnly(cartridge) => &cartridge.ram,
+      Cartridge::Mbc1(cartridge) => &cartridge.ram,
+      Cartridge::Mbc2(cartridge) => &cartridge.ram,
+      Cartridge::Mbc5(cartridge) => &cartridge.ram,
+    }
+  }
+
+  /// Returns the RAM of the cartridge mutably.
+  fn ram_mut(&mut self) -> &mut [u8] {
+    match self {
+      Cartridge::RomOnly(cartridge) => &mut cartridge.ram,
+      Cartridge::Mbc1(cartridge) => &mut cartridge.ram,
+      Cartridge::Mbc2(cartridge) => &mut cartridge.ram,
+      Cartridge::Mbc5(cartridge) => &mut cartridge.ram,
+    }
+  }
+}
+
+/// A kind of cartridge, used to override the cartridge type in the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CartridgeKind {
+  /// A cartridge without a memory bank controller.
+  RomOnly,
+  /// A cartridge with memory bank controller 1.
+  Mbc1,
+  /// A cartridge with memory bank controller 2.
+  Mbc2,
+  /// A cartridge with memory bank controller 5.
+  Mbc5,
+}
+
+/// An error returned when the cartridge type in the header isn't supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsupportedCartridge {
+  /// The cartridge type in the header.
+  pub cartridge_type: u8,
+}
+
+impl UnsupportedCartridge {
+  /// Returns the name of the hardware in the cartridge.
+  pub fn name(&self) -> &'static str {
+    match self.cartridge_type {
+      0x0B..=0x0D => "MMM01",
+      0x0F..=0x13 => "MBC3",
+      0x20 => "MBC6",
+      0x22 => "MBC7",
+      0xFC => "Pocket Camera",
+      0xFD => "Bandai TAMA5",
+      0xFE => "HuC3",
+      0xFF => "HuC1",
+      _ => "unknown",
     }
   }
 }
 
+impl fmt::Display for UnsupportedCartridge {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "unsupported cartridge type 0x{:02X} ({})",
+      self.cartridge_type,
+      self.name()
+    )
+  }
+}
+
+impl std::error::Error for UnsupportedCartridge {}
+
 /// A cartridge with MBC1 controller.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mbc1 {
+  /// The ROM of the cartridge, which save states leave out.
+  #[cfg_attr(feature = "serde", serde(skip))]
   rom: Vec<u8>,
   ram: Vec<u8>,
   rom_bank: usize,
-  ram_bank: usize,
+  /// The secondary 2-bit bank register, which selects the upper ROM bank bits or the RAM bank.
+  secondary_bank: usize,
   ram_enabled: bool,
   banking_mode: BankingMode,
 }
 
 /// The possible banking modes.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum BankingMode {
   /// Address space 0x0000-0x3FFF and 0xA000-0xBFFF are locked to bank 0 & SRAM.
   Simple,
@@ -65,20 +268,41 @@ enum BankingMode {
 }
 
 impl Mbc1 {
+  /// Creates a cartridge with as much RAM as the header specifies.
   pub fn new(rom: Vec<u8>) -> Self {
+    let ram_size = CartridgeHeader::parse(&rom).ram_size;
+
     Self {
       rom,
-      ram: vec![0; 0x8000],
+      ram: vec![0; ram_size],
       rom_bank: 1,
-      ram_bank: 0,
+      secondary_bank: 0,
       ram_enabled: false,
       banking_mode: BankingMode::Simple,
     }
   }
 
+  /// Resets the bank registers to their power-on values, keeping the RAM.
+  pub fn reset(&mut self) {
+    self.rom_bank = 1;
+    self.secondary_bank = 0;
+    self.ram_enabled = false;
+    self.banking_mode = BankingMode::Simple;
+  }
+
   /// Reads an 8-bit value from the provided address in rom.
   pub fn read_rom(&self, address: u16) -> u8 {
-    let bank = if address < 0x4000 { 0 } else { self.rom_bank };
+    let bank = if address < 0x4000 {
+      // Advanced mode maps banks 0x20, 0x40 and 0x60 into the fixed region of large ROMs
+      match self.banking_mode {
+        BankingMode::Simple => 0,
+        BankingMode::Advanced => self.secondary_bank << 5,
+      }
+    } else {
+      (self.secondary_bank << 5) | self.rom_bank
+    };
+    // Bank numbers wrap around to the number of banks in the ROM
+    let bank = bank & (self.rom.len().div_ceil(0x4000).next_power_of_two() - 1);
     let offset = (address as usize) & (0x4000 - 1);
 
     self
@@ -93,68 +317,360 @@ impl Mbc1 {
     if address < 0x2000 {
       self.ram_enabled = value & 0x0F == 0x0A;
     } else if address < 0x4000 {
-      let bank = (value as usize & 0x1F).max(1);
-
-      self.rom_bank = (self.rom_bank & 0x60) | bank;
+      self.rom_bank = (value as usize & 0x1F).max(1);
     } else if address < 0x6000 {
-      let bits = (value as usize) & 0x03;
-
-      match self.banking_mode {
-        BankingMode::Simple => self.rom_bank = (self.rom_bank & 0x1F) | (bits << 5),
-        BankingMode::Advanced => self.ram_bank = bits,
-      }
+      self.secondary_bank = (value as usize) & 0x03;
     } else if address < 0x8000 {
       self.banking_mode = if value & 0x01 == 0 {
-        BankingMode::Advanced
-      } else {
         BankingMode::Simple
+      } else {
+        BankingMode::Advanced
       };
     }
   }
 
+  /// Returns the RAM bank that is mapped to 0xA000-0xBFFF.
+  fn ram_bank(&self) -> usize {
+    match self.banking_mode {
+      BankingMode::Simple => 0,
+      BankingMode::Advanced => self.secondary_bank,
+    }
+  }
+
+  /// Returns the offset into RAM of the address, if the cartridge has RAM.
+  fn ram_offset(&self, address: u16) -> Option<usize> {
+    let offset = self.ram_bank() * 0x2000 + ((address as usize) & (0x2000 - 1));
+
+    // RAM that's smaller than the banked range doesn't see the upper address lines, so it
+    // repeats, like an 8kB RAM on a large ROM in advanced mode
+    (!self.ram.is_empty()).then(|| offset % self.ram.len())
+  }
+
   /// Reads the 8-bit value at the provided address in RAM.
   pub fn read_ram(&self, address: u16) -> u8 {
-    if self.ram_enabled {
-      let offset = (address as usize) & (0x2000 - 1);
-
-      self
-        .ram
-        .get(self.ram_bank * 0x2000 + offset)
-        .copied()
-        .unwrap_or(0xFF)
-    } else {
-      0xFF
+    match self.ram_offset(address) {
+      Some(offset) if self.ram_enabled => self.ram[offset],
+      _ => 0xFF,
     }
   }
 
   /// Writes the 8-bit to RAM at the provided address.
   pub fn write_ram(&mut self, address: u16, value: u8) {
-    if !self.ram_enabled {
+    if let Some(offset) = self.ram_offset(address).filter(|_| self.ram_enabled) {
+      self.ram[offset] = value;
+    }
+  }
+}
+
+/// A cartridge with MBC2 controller, which supports up to 256kB of ROM and has 512 half-bytes
+/// of RAM built in.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mbc2 {
+  /// The ROM of the cartridge, which save states leave out.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  rom: Vec<u8>,
+  /// The built-in RAM, where only the lower 4 bits of each byte are used.
+  ram: Vec<u8>,
+  /// The 4-bit ROM bank that is mapped to 0x4000-0x7FFF.
+  rom_bank: usize,
+  /// Whether the RAM can be accessed.
+  ram_enabled: bool,
+}
+
+impl Mbc2 {
+  /// Creates a cartridge with the built-in RAM.
+  pub fn new(rom: Vec<u8>) -> Self {
+    Self {
+      rom,
+      ram: vec![0; MBC2_RAM_SIZE],
+      rom_bank: 1,
+      ram_enabled: false,
+    }
+  }
+
+  /// Resets the bank registers to their power-on values, keeping the RAM.
+  pub fn reset(&mut self) {
+    self.rom_bank = 1;
+    self.ram_enabled = false;
+  }
+
+  /// Reads an 8-bit value from the provided address in rom.
+  pub fn read_rom(&self, address: u16) -> u8 {
+    let bank = if address < 0x4000 { 0 } else { self.rom_bank };
+    // Bank numbers wrap around to the number of banks in the ROM
+    let bank = bank & (self.rom.len().div_ceil(0x4000).next_power_of_two() - 1);
+    let offset = (address as usize) & (0x4000 - 1);
+
+    self
+      .rom
+      .get(bank * 0x4000 + offset)
+      .copied()
+      .unwrap_or(0xFF)
+  }
+
+  /// "Writes" a value to ROM at the provided address.
+  pub fn write_rom(&mut self, address: u16, value: u8) {
+    if address >= 0x4000 {
       return;
     }
 
-    let offset = (address as usize) & (0x2000 - 1);
+    // Bit 8 of the address picks between the ROM bank and the RAM enable register
+    if address & 0x100 != 0 {
+      // Like MBC1, bank 0 gets mapped as bank 1
+      self.rom_bank = ((value & 0x0F) as usize).max(1);
+    } else {
+      self.ram_enabled = value & 0x0F == 0x0A;
+    }
+  }
 
-    if let Some(byte) = self.ram.get_mut(self.ram_bank * 0x2000 + offset) {
-      *byte = value;
+  /// Reads the 8-bit value at the provided address in RAM.
+  pub fn read_ram(&self, address: u16) -> u8 {
+    if !self.ram_enabled {
+      return 0xFF;
+    }
+
+    // The RAM repeats across 0xA000-0xBFFF, and the upper 4 bits aren't connected
+    0xF0 | self.ram[(address as usize) & (MBC2_RAM_SIZE - 1)]
+  }
+
+  /// Writes the lower 4 bits of the value to RAM at the provided address.
+  pub fn write_ram(&mut self, address: u16, value: u8) {
+    if self.ram_enabled {
+      self.ram[(address as usize) & (MBC2_RAM_SIZE - 1)] = value & 0x0F;
+    }
+  }
+}
+
+/// A cartridge with MBC5 controller, which supports up to 8MB of ROM and 128kB of RAM.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mbc5 {
+  /// The ROM of the cartridge, which save states leave out.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  rom: Vec<u8>,
+  /// The external RAM of the cartridge.
+  ram: Vec<u8>,
+  /// The 9-bit ROM bank that is mapped to 0x4000-0x7FFF.
+  rom_bank: usize,
+  /// The 4-bit RAM bank that is mapped to 0xA000-0xBFFF.
+  ram_bank: usize,
+  /// Whether the RAM can be accessed.
+  ram_enabled: bool,
+}
+
+impl Mbc5 {
+  /// Creates a cartridge with as much RAM as the header specifies.
+  pub fn new(rom: Vec<u8>) -> Self {
+    let ram_size = CartridgeHeader::parse(&rom).ram_size;
+
+    Self {
+      rom,
+      ram: vec![0; ram_size],
+      rom_bank: 1,
+      ram_bank: 0,
+      ram_enabled: false,
+    }
+  }
+
+  /// Resets the bank registers to their power-on values, keeping the RAM.
+  pub fn reset(&mut self) {
+    self.rom_bank = 1;
+    self.ram_bank = 0;
+    self.ram_enabled = false;
+  }
+
+  /// Reads an 8-bit value from the provided address in rom.
+  pub fn read_rom(&self, address: u16) -> u8 {
+    let bank = if address < 0x4000 { 0 } else { self.rom_bank };
+    // Bank numbers wrap around to the number of banks in the ROM
+    let bank = bank & (self.rom.len().div_ceil(0x4000).next_power_of_two() - 1);
+    let offset = (address as usize) & (0x4000 - 1);
+
+    self
+      .rom
+      .get(bank * 0x4000 + offset)
+      .copied()
+      .unwrap_or(0xFF)
+  }
+
+  /// "Writes" a value to ROM at the provided address.
+  pub fn write_rom(&mut self, address: u16, value: u8) {
+    if address < 0x2000 {
+      self.ram_enabled = value & 0x0F == 0x0A;
+    } else if address < 0x3000 {
+      // Unlike MBC1, bank 0 can be mapped to 0x4000-0x7FFF
+      self.rom_bank = (self.rom_bank & 0x100) | value as usize;
+    } else if address < 0x4000 {
+      self.rom_bank = (self.rom_bank & 0xFF) | ((value as usize & 0x01) << 8);
+    } else if address < 0x6000 {
+      // Rumble cartridges wire bit 3 to the motor, so only 3 bits select the RAM bank
+      let mask = if self.has_rumble() { 0x07 } else { 0x0F };
+
+      self.ram_bank = (value as usize) & mask;
+    }
+  }
+
+  /// Returns whether the cartridge has a rumble motor, going by the cartridge type in the
+  /// header.
+  fn has_rumble(&self) -> bool {
+    // MBC5 + rumble, MBC5 + rumble + RAM and MBC5 + rumble + RAM + battery
+    matches!(self.rom.get(CARTRIDGE_TYPE), Some(0x1C..=0x1E))
+  }
+
+  /// Returns the offset into RAM of the address, if the cartridge has RAM.
+  fn ram_offset(&self, address: u16) -> Option<usize> {
+    let offset = self.ram_bank * 0x2000 + ((address as usize) & (0x2000 - 1));
+
+    // Bank numbers wrap around to the number of RAM banks, and RAM that's smaller than a
+    // bank repeats
+    (!self.ram.is_empty()).then(|| offset % self.ram.len())
+  }
+
+  /// Reads the 8-bit value at the provided address in RAM.
+  pub fn read_ram(&self, address: u16) -> u8 {
+    match self.ram_offset(address) {
+      Some(offset) if self.ram_enabled => self.ram[offset],
+      _ => 0xFF,
+    }
+  }
+
+  /// Writes the 8-bit to RAM at the provided address.
+  pub fn write_ram(&mut self, address: u16, value: u8) {
+    if let Some(offset) = self.ram_offset(address).filter(|_| self.ram_enabled) {
+      self.ram[offset] = value;
     }
   }
 }
 
-/// A cartridge that only has ROM.
+/// A cartridge that only has ROM, and possibly RAM, without a memory bank controller.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RomOnly {
-  /// The ROM of the cartridge.
+  /// The ROM of the cartridge, which save states leave out.
+  #[cfg_attr(feature = "serde", serde(skip))]
   rom: Vec<u8>,
+  /// The external RAM of the cartridge, which is empty if the cartridge has no RAM.
+  ram: Vec<u8>,
 }
 
 impl RomOnly {
+  /// Creates a cartridge with no RAM.
   pub fn new(rom: Vec<u8>) -> Self {
-    Self { rom }
+    Self {
+      rom,
+      ram: Vec::new(),
+    }
+  }
+
+  /// Creates a cartridge with 8kB of unbanked RAM.
+  pub fn with_ram(rom: Vec<u8>) -> Self {
+    Self {
+      rom,
+      ram: vec![0; 0x2000],
+    }
   }
 
   /// Reads from the ROM.
   pub fn read_rom(&self, address: u16) -> u8 {
-    self.rom.get(address as usize).copied().unwrap()
+    // ROMs smaller than 32kB leave the rest of the address space floating
+    self.rom.get(address as usize).copied().unwrap_or(0xFF)
+  }
+
+  /// "Writes" a value to ROM, which is ignored since there is no memory bank controller.
+  pub fn write_rom(&mut self, _address: u16, _value: u8) {}
+
+  /// Reads the 8-bit value at the provided address in RAM.
+  pub fn read_ram(&self, address: u16) -> u8 {
+    let offset = (address as usize) & (0x2000 - 1);
+
+    // Cartridges without RAM leave the data bus floating
+    self.ram.get(offset).copied().unwrap_or(0xFF)
+  }
+
+  /// Writes the 8-bit to RAM at the provided address.
+  pub fn write_ram(&mut self, address: u16, value: u8) {
+    let offset = (address as usize) & (0x2000 - 1);
+
+    if let Some(byte) = self.ram.get_mut(offset) {
+      *byte = value;
+    }
+  }
+}
+
+/// The number of half-bytes of RAM that MBC2 has built in.
+const MBC2_RAM_SIZE: usize = 0x200;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Creates an empty 32kB ROM without a memory bank controller.
+  fn empty_rom() -> Vec<u8> {
+    vec![0; 0x8000]
+  }
+
+  #[test]
+  fn checksum_matches_hand_computed_sum() {
+    let mut rom = empty_rom();
+
+    rom[0x0100] = 0x12;
+    rom[0x0200] = 0x34;
+    rom[0x7FFF] = 0xFF;
+    // 0x12 + 0x34 + 0xFF, which leaves out the checksum's own bytes
+    rom[GLOBAL_CHECKSUM] = 0x01;
+    rom[GLOBAL_CHECKSUM + 1] = 0x45;
+
+    assert!(Cartridge::RomOnly(RomOnly::new(rom.clone())).verify_checksum());
+
+    rom[0x4000] = 0x01;
+
+    assert!(!Cartridge::RomOnly(RomOnly::new(rom)).verify_checksum());
+  }
+
+  #[test]
+  fn checksum_fails_without_a_header() {
+    assert!(!Cartridge::RomOnly(RomOnly::new(vec![0; GLOBAL_CHECKSUM])).verify_checksum());
+  }
+
+  #[test]
+  fn mbc2_ram_only_keeps_lower_nibble() {
+    let mut cartridge = Mbc2::new(empty_rom());
+
+    // Bit 8 of the address is clear, so this enables the RAM
+    cartridge.write_rom(0x0000, 0x0A);
+    cartridge.write_ram(0xA000, 0xFF);
+
+    // Only the lower 4 bits get stored, and the upper 4 bits always read as set
+    assert_eq!(cartridge.ram[0], 0x0F);
+    assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+
+    cartridge.write_ram(0xA001, 0x5A);
+
+    assert_eq!(cartridge.read_ram(0xA001), 0xFA);
+    // The 512 half-bytes repeat across the whole RAM area
+    assert_eq!(cartridge.read_ram(0xA201), 0xFA);
+
+    // Writes while the RAM is disabled are ignored
+    cartridge.write_rom(0x0000, 0x00);
+    cartridge.write_ram(0xA001, 0x03);
+    cartridge.write_rom(0x0000, 0x0A);
+
+    assert_eq!(cartridge.read_ram(0xA001), 0xFA);
+  }
+
+  #[test]
+  fn rom_only_ignores_rom_writes() {
+    let rom: Vec<u8> = (0..0x8000)
+      .map(|address: usize| (address >> 8) as u8)
+      .collect();
+    let mut cartridge = Cartridge::RomOnly(RomOnly::new(rom.clone()));
+
+    // On an MBC, this would switch the ROM bank at 0x4000-0x7FFF
+    cartridge.write_rom(0x2000, 0x02);
+
+    assert_eq!(cartridge.rom(), &rom[..]);
+    assert_eq!(cartridge.read_rom(0x2000), 0x20);
+    assert_eq!(cartridge.read_rom(0x4000), 0x40);
   }
 }
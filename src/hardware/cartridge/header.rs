@@ -0,0 +1,108 @@
+/// The cartridge header at `0x0100-0x014F` of the ROM, which describes the game and the
+/// hardware in the cartridge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CartridgeHeader {
+  /// The title of the game, without the padding.
+  pub title: String,
+  /// The CGB flag, which tells whether the game supports or requires a CGB.
+  pub cgb_flag: u8,
+  /// The SGB flag, which tells whether the game supports the SGB.
+  pub sgb_flag: u8,
+  /// The cartridge type, which tells the memory bank controller and the extra hardware.
+  pub cartridge_type: u8,
+  /// The size of the ROM in bytes, or 0 if the header has an unknown size.
+  pub rom_size: usize,
+  /// The size of the external RAM in bytes.
+  pub ram_size: usize,
+  /// The checksum of the header, which the boot ROM verifies.
+  pub header_checksum: u8,
+  /// The checksum of the header, as computed from the ROM.
+  computed_checksum: u8,
+}
+
+impl CartridgeHeader {
+  /// Parses the header of the ROM. Bytes past the end of the ROM are read as `0`.
+  pub fn parse(rom: &[u8]) -> Self {
+    let byte = |address: usize| rom.get(address).copied().unwrap_or(0);
+
+    let computed_checksum = (TITLE_START..HEADER_CHECKSUM).fold(0u8, |checksum, address| {
+      checksum.wrapping_sub(byte(address)).wrapping_sub(1)
+    });
+
+    Self {
+      title: parse_title(rom.get(TITLE_START..=TITLE_END).unwrap_or_default()),
+      cgb_flag: byte(CGB_FLAG),
+      sgb_flag: byte(SGB_FLAG),
+      cartridge_type: byte(CARTRIDGE_TYPE),
+      rom_size: match byte(ROM_SIZE) {
+        size @ 0x00..=0x08 => 0x8000 << size,
+        _ => 0,
+      },
+      ram_size: match byte(RAM_SIZE) {
+        0x01 => 0x800,
+        0x02 => 0x2000,
+        0x03 => 0x8000,
+        0x04 => 0x20000,
+        0x05 => 0x10000,
+        _ => 0,
+      },
+      header_checksum: byte(HEADER_CHECKSUM),
+      computed_checksum,
+    }
+  }
+
+  /// Returns whether the header checksum matches the header. The boot ROM refuses to start
+  /// games where it doesn't, so a mismatch usually means a corrupt or hacked ROM.
+  pub fn checksum_matches(&self) -> bool {
+    self.header_checksum == self.computed_checksum
+  }
+
+  /// Returns whether the game has CGB features.
+  pub fn supports_cgb(&self) -> bool {
+    self.cgb_flag & 0x80 != 0
+  }
+
+  /// Returns whether the game only runs on a CGB.
+  pub fn requires_cgb(&self) -> bool {
+    self.cgb_flag == 0xC0
+  }
+
+  /// Returns whether the game has SGB features.
+  pub fn supports_sgb(&self) -> bool {
+    self.sgb_flag == 0x03
+  }
+}
+
+/// Parses the title from its bytes in the header.
+fn parse_title(title: &[u8]) -> String {
+  // Titles are padded with zeroes, and newer cartridges reuse the last bytes of the title
+  // for the manufacturer code and CGB flag
+  let length = title
+    .iter()
+    .position(|&byte| !byte.is_ascii_graphic() && byte != b' ')
+    .unwrap_or(title.len());
+
+  String::from_utf8_lossy(&title[..length])
+    .trim_end()
+    .to_string()
+}
+
+/// The address where the title starts in the cartridge header.
+const TITLE_START: usize = 0x134;
+/// The address of the last byte of the title in the cartridge header.
+const TITLE_END: usize = 0x143;
+/// The address of the CGB flag in the cartridge header, which overlaps with the title.
+const CGB_FLAG: usize = 0x143;
+/// The address of the SGB flag in the cartridge header.
+const SGB_FLAG: usize = 0x146;
+/// The address of the cartridge type in the cartridge header.
+pub(super) const CARTRIDGE_TYPE: usize = 0x147;
+/// The address of the ROM size in the cartridge header.
+const ROM_SIZE: usize = 0x148;
+/// The address of the RAM size in the cartridge header.
+const RAM_SIZE: usize = 0x149;
+/// The address of the header checksum, which covers `0x0134-0x014C`.
+const HEADER_CHECKSUM: usize = 0x14D;
+/// The address of the big-endian checksum of the whole ROM, which leaves out its own 2 bytes.
+pub(super) const GLOBAL_CHECKSUM: usize = 0x14E;
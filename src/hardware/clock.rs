@@ -1,5 +1,6 @@
 /// The internal system clock.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SystemClock(usize);
 
 /// A possible cycle.
@@ -39,3 +40,9 @@ impl SystemClock {
     self.0 = self.0.wrapping_add(1);
   }
 }
+
+impl Default for SystemClock {
+  fn default() -> Self {
+    Self::new()
+  }
+}
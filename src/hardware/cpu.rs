@@ -10,7 +10,8 @@ use crate::{
 use macros::*;
 
 /// A state that the CPU can be in.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CpuState {
   /// The CPU is processing instructions.
   Running,
@@ -23,6 +24,7 @@ pub enum CpuState {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cpu {
   /// The enabled flags.
   flags: u8,
@@ -50,6 +52,7 @@ pub struct Cpu {
 
 /// A machine cycle when stepping the CPU's instruction or interrupt handler.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum CpuCycle {
   // Machine cycle 1.
   M1,
@@ -92,8 +95,7 @@ impl Cpu {
     cpu
   }
 
-  // TODO: Once we have a boot rom, we shouldn't need this function.
-  /// Sets the default register values.
+  /// Sets the register values that the DMG boot ROM leaves behind, for running without one.
   pub fn set_register_defaults(&mut self) {
     // These values were taken from "The Cycle-Accurate Game Boy Docs"
     self.registers.a = 0x01;
@@ -113,6 +115,33 @@ impl Cpu {
     self.registers.pc = 0x100;
   }
 
+  /// Returns the flags register, `F`.
+  pub fn flags(&self) -> u8 {
+    self.flags
+  }
+
+  /// Returns the state that the CPU is in.
+  pub fn state(&self) -> CpuState {
+    self.state
+  }
+
+  /// Returns whether the master interrupt flag, `IME`, is set.
+  pub fn interrupt_master_enabled(&self) -> bool {
+    self.interrupt_master_enabled
+  }
+
+  /// Returns whether the CPU finished an instruction and fetched the next opcode during the
+  /// current M-cycle.
+  pub fn fetched_instruction(&self) -> bool {
+    self.should_check_interrupts
+  }
+
+  /// Sets the master interrupt flag directly, without the delay of `EI`.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn set_interrupt_master_enabled(&mut self, enabled: bool) {
+    self.interrupt_master_enabled = enabled;
+  }
+
   /// Steps the CPU by 1 T-cycle.
   pub fn step(&mut self, hardware: &mut Hardware) {
     match hardware.sys_clock.t_cycle() {
@@ -157,7 +186,9 @@ impl Cpu {
             // Exit out of HALT mode if we have any pending interrupts
             if hardware.has_pending_interrupts() {
               // Don't start doing the work immediately, since there's an M-cycle
-              // delay when the CPU exits HALT mode.
+              // delay when the CPU exits HALT mode. With IME set, the dispatch takes
+              // 6 M-cycles from the wake instead of the usual 5, and with it unset, the
+              // instruction after `HALT` starts an M-cycle late.
               if self.interrupt_master_enabled {
                 self.state = CpuState::HandlingInterrupts;
               } else {
@@ -165,7 +196,12 @@ impl Cpu {
               }
             }
           }
-          CpuState::Stopped => {}
+          CpuState::Stopped => {
+            // `STOP` only ends when a selected button is held, regardless of `IE` and `IME`
+            if hardware.joypad.selected_button_held() {
+              self.state = CpuState::Running;
+            }
+          }
         }
       }
     }
@@ -1710,8 +1746,14 @@ impl Cpu {
       // STOP
       (false, 0x10) => {
         if matches!(self.cycle, M1) {
-          // NOTE: `STOP` needs to be followed by another byte
-          self.fetch_byte(hardware);
+          // `STOP` is 2 bytes long and skips the byte after it, whatever its value, but
+          // when an interrupt is pending, only the opcode gets consumed.
+          if !hardware.has_pending_interrupts() {
+            self.fetch_byte(hardware);
+          }
+
+          // Entering `STOP` resets `DIV`
+          hardware.write_byte(0xFF04, 0);
 
           self.state = CpuState::Stopped;
 
@@ -1729,7 +1771,14 @@ impl Cpu {
           //
           // In this bugged state, the program counter is NOT incremented after
           // fetching the next byte.
-          if !self.interrupt_master_enabled && hardware.has_pending_interrupts() {
+          //
+          // The same happens when `EI` came right before, even though the master
+          // interrupt flag is now enabled. The interrupt gets dispatched, and since the
+          // program counter wasn't incremented, it returns to the `HALT` itself.
+          let ei_before_halt = self.last_instruction == 0xFB;
+
+          if (!self.interrupt_master_enabled || ei_before_halt) && hardware.has_pending_interrupts()
+          {
             self.halt_bug = true;
             // The CPU doesn't actually enter a halted state in the case of a bugged
             // halt instruction.
@@ -1851,9 +1900,9 @@ impl Cpu {
         if matches!(self.cycle, M1) {
           self.interrupt_master_enabled = false;
 
-          // Use `complete_cycle` instead of `fetch_cycle` since the IME is disabled,
-          // thus no interrupts can occur.
-          self.complete_cycle(hardware);
+          // No interrupts can occur since the IME is disabled, but this still marks the
+          // end of the instruction.
+          self.fetch_cycle(hardware);
         }
       }
       // EI
@@ -2601,6 +2650,16 @@ impl Cpu {
   }
 }
 
+impl Default for Cpu {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// The value of the A register that the CGB boot ROM leaves behind, which games check to
+/// detect a CGB.
+pub const CGB_REGISTER_A: u8 = 0x11;
+
 mod macros {
   /// Calls a function passing the value of the register.
   macro_rules! perform_with_register {
@@ -2712,3 +2771,189 @@ mod macros {
   pub(crate) use perform_with_register;
   pub(crate) use write_to_register;
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::emulator::{Emulator, Executed};
+
+  /// The values of `SP` around the boundaries of its nibbles and bytes.
+  const SP_BOUNDARIES: [u16; 12] = [
+    0x0000, 0x0001, 0x000F, 0x0010, 0x00FF, 0x0100, 0x0FFF, 0x7FFF, 0x8000, 0xFF00, 0xFFF0, 0xFFFF,
+  ];
+
+  /// Runs `LD SP, sp` followed by the instruction with each of the 256 8-bit operands, and
+  /// checks that `SP`, `HL` and the flags match what the hardware ends up with.
+  fn check_sp_offset_instruction(opcode: u8) {
+    for sp in SP_BOUNDARIES {
+      let [sp_low, sp_high] = sp.to_le_bytes();
+      let program: Vec<u8> = (0..=u8::MAX)
+        .flat_map(|offset| [0x31, sp_low, sp_high, opcode, offset])
+        .collect();
+      let mut emulator = Emulator::with_program(&program);
+
+      for offset in 0..=u8::MAX {
+        // Start with every flag set, so that the ones that get cleared are checked too
+        emulator.cpu_mut().flags = 0xF0;
+        emulator.cpu_mut().registers.h = 0x12;
+        emulator.cpu_mut().registers.l = 0x34;
+
+        emulator.step_instruction().unwrap();
+        emulator.step_instruction().unwrap();
+
+        let result = sp.wrapping_add(offset as i8 as u16);
+        let half_carry = (sp & 0x0F) + (offset as u16 & 0x0F) > 0x0F;
+        let carry = (sp & 0xFF) + offset as u16 > 0xFF;
+        let expected_flags = (half_carry as u8 * Flag::H as u8) | (carry as u8 * Flag::C as u8);
+        let cpu = emulator.cpu();
+        let hl = u16::from_be_bytes([cpu.registers.h, cpu.registers.l]);
+        let (expected_sp, expected_hl) = if opcode == 0xE8 {
+          (result, 0x1234)
+        } else {
+          (sp, result)
+        };
+
+        assert_eq!(
+          (cpu.registers.sp, hl, cpu.flags()),
+          (expected_sp, expected_hl, expected_flags),
+          "opcode {opcode:#04X}, SP {sp:#06X}, offset {offset:#04X}"
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn add_sp_imm8() {
+    check_sp_offset_instruction(0xE8);
+  }
+
+  #[test]
+  fn ld_hl_sp_plus_imm8() {
+    check_sp_offset_instruction(0xF8);
+  }
+
+  /// The result of `DAA` on `A` and the flags, in the form that's known to match the hardware.
+  fn reference_daa(a: u8, subtracted: bool, half_carried: bool, carried: bool) -> (u8, u8) {
+    let mut result = a;
+    let mut carry = carried;
+
+    if subtracted {
+      if carried {
+        result = result.wrapping_sub(0x60);
+      }
+      if half_carried {
+        result = result.wrapping_sub(0x06);
+      }
+    } else {
+      if carried || a > 0x99 {
+        result = result.wrapping_add(0x60);
+        carry = true;
+      }
+      if half_carried || (a & 0x0F) > 0x09 {
+        result = result.wrapping_add(0x06);
+      }
+    }
+
+    let flags = ((result == 0) as u8 * Flag::Z as u8)
+      | (subtracted as u8 * Flag::N as u8)
+      | (carry as u8 * Flag::C as u8);
+
+    (result, flags)
+  }
+
+  #[test]
+  fn daa_matches_reference() {
+    const FLAG_COMBINATIONS: u8 = 8;
+
+    let program = [0x27; 256 * FLAG_COMBINATIONS as usize];
+    let mut emulator = Emulator::with_program(&program);
+
+    for a in 0..=u8::MAX {
+      for combination in 0..FLAG_COMBINATIONS {
+        let subtracted = is_flag_set!(combination, 0b100);
+        let half_carried = is_flag_set!(combination, 0b010);
+        let carried = is_flag_set!(combination, 0b001);
+
+        emulator.cpu_mut().registers.a = a;
+        emulator.cpu_mut().flags = (subtracted as u8 * Flag::N as u8)
+          | (half_carried as u8 * Flag::H as u8)
+          | (carried as u8 * Flag::C as u8);
+
+        emulator.step_instruction().unwrap();
+
+        let cpu = emulator.cpu();
+
+        assert_eq!(
+          (cpu.registers.a, cpu.flags()),
+          reference_daa(a, subtracted, half_carried, carried),
+          "A {a:#04X}, N {subtracted}, H {half_carried}, C {carried}"
+        );
+      }
+    }
+  }
+
+  /// The address that the return address gets pushed to, in WRAM.
+  const STACK_START: u16 = 0xCFFE;
+  /// The most instructions that a return program runs before the interrupt should have been
+  /// dispatched.
+  const MAX_RETURN_PROGRAM_STEPS: usize = 4;
+
+  /// Runs the program, which returns to the `NOP`s at `return_offset` into it, with the timer
+  /// interrupt pending and `IME` cleared. Returns what the CPU executed at each step, until it
+  /// dispatched the interrupt.
+  fn run_return_program(program: &[u8], return_offset: u16) -> Vec<Executed> {
+    let mut emulator = Emulator::with_program(program);
+    let return_address = emulator.next_instruction_address() + return_offset;
+    let [low, high] = return_address.to_le_bytes();
+
+    emulator.cpu_mut().registers.sp = STACK_START;
+    emulator.hardware.write_byte(STACK_START, low);
+    emulator.hardware.write_byte(STACK_START + 1, high);
+    emulator.hardware.force_interrupt(Interrupt::Timer);
+
+    let mut executed = Vec::new();
+
+    // Give up after a few instructions, in case the interrupt never gets dispatched
+    for _ in 0..MAX_RETURN_PROGRAM_STEPS {
+      let step = emulator.step_instruction().unwrap();
+
+      executed.push(step.executed);
+
+      if matches!(step.executed, Executed::Interrupt(_)) {
+        // The interrupt returns to the `NOP`s that the program returned to
+        let sp = emulator.cpu().registers.sp;
+        let pushed = u16::from_le_bytes([
+          emulator.hardware.read_byte(sp),
+          emulator.hardware.read_byte(sp + 1),
+        ]);
+
+        assert_eq!(pushed, return_address);
+
+        break;
+      }
+    }
+
+    executed
+  }
+
+  #[test]
+  fn reti_enables_ime_immediately() {
+    // RETI, then NOPs to return to
+    let executed = run_return_program(&[0xD9, 0x00, 0x00], 1);
+
+    assert_eq!(executed.len(), 2);
+    assert!(matches!(executed[0], Executed::Instruction(_)));
+    assert_eq!(executed[1], Executed::Interrupt(Some(Interrupt::Timer)));
+  }
+
+  #[test]
+  fn ei_ret_enables_ime_after_ret() {
+    // EI, RET, then NOPs to return to
+    let executed = run_return_program(&[0xFB, 0xC9, 0x00, 0x00], 2);
+
+    assert_eq!(executed.len(), 3);
+    assert!(matches!(executed[0], Executed::Instruction(_)));
+    assert!(matches!(executed[1], Executed::Instruction(_)));
+    assert_eq!(executed[2], Executed::Interrupt(Some(Interrupt::Timer)));
+  }
+}
@@ -0,0 +1,160 @@
+use crate::flags::is_flag_set;
+
+/// The VRAM DMA of the CGB, which copies blocks of `0x10` bytes from ROM or RAM into VRAM.
+///
+/// General purpose DMA copies every block at once, while HBlank DMA copies a block at the
+/// start of each HBlank. The CPU is halted while blocks get copied.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hdma {
+  /// The address to copy the next block from.
+  source: u16,
+  /// The address in VRAM to copy the next block to.
+  destination: u16,
+  /// The number of blocks that are left to copy.
+  remaining_blocks: u8,
+  /// The running transfer, if any.
+  transfer: Option<HdmaTransfer>,
+  /// Whether the running HBlank DMA transfer was stopped by the game.
+  stopped: bool,
+  /// The number of M-cycles that the CPU is halted for.
+  stalled_m_cycles: u16,
+}
+
+/// The kind of a running VRAM DMA transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum HdmaTransfer {
+  /// Every block gets copied at once.
+  GeneralPurpose,
+  /// A block gets copied at the start of each HBlank, if `block_due` is set.
+  HBlank { block_due: bool },
+}
+
+impl Hdma {
+  /// Creates a new [`Hdma`].
+  pub fn new() -> Self {
+    Self {
+      source: 0,
+      destination: 0,
+      remaining_blocks: 0,
+      transfer: None,
+      stopped: false,
+      stalled_m_cycles: 0,
+    }
+  }
+
+  /// Reads from the VRAM DMA's registers.
+  pub fn read_register(&self, address: u16) -> u8 {
+    match address {
+      // The source and destination are write-only
+      0xFF51..0xFF55 => 0xFF,
+      // Bit 7 is clear while a transfer is running, and the lower bits hold the remaining
+      // number of blocks minus 1
+      0xFF55 => match (self.transfer, self.stopped) {
+        (Some(_), _) => self.remaining_blocks.wrapping_sub(1) & 0x7F,
+        (None, true) => 0x80 | (self.remaining_blocks.wrapping_sub(1) & 0x7F),
+        (None, false) => 0xFF,
+      },
+      _ => unreachable!(),
+    }
+  }
+
+  /// Writes to the VRAM DMA's registers.
+  pub fn write_register(&mut self, address: u16, value: u8) {
+    match address {
+      0xFF51 => self.source = (self.source & 0x00FF) | ((value as u16) << 8),
+      // The lower 4 bits are ignored, since blocks are aligned to `0x10` bytes
+      0xFF52 => self.source = (self.source & 0xFF00) | (value & 0xF0) as u16,
+      // The destination is always in VRAM
+      0xFF53 => self.destination = (self.destination & 0x00FF) | (((value & 0x1F) as u16) << 8),
+      0xFF54 => self.destination = (self.destination & 0xFF00) | (value & 0xF0) as u16,
+      0xFF55 => {
+        let hblank_mode = is_flag_set!(value, HBLANK_MODE_MASK);
+
+        // Writing with bit 7 clear during an HBlank DMA transfer stops it instead
+        if !hblank_mode && matches!(self.transfer, Some(HdmaTransfer::HBlank { .. })) {
+          self.transfer = None;
+          self.stopped = true;
+
+          return;
+        }
+
+        self.remaining_blocks = (value & 0x7F) + 1;
+        self.stopped = false;
+        self.transfer = Some(if hblank_mode {
+          HdmaTransfer::HBlank { block_due: false }
+        } else {
+          HdmaTransfer::GeneralPurpose
+        });
+      }
+      _ => unreachable!(),
+    }
+  }
+
+  /// Lets a running HBlank DMA transfer copy its next block, since HBlank started.
+  pub fn start_hblank(&mut self) {
+    if let Some(HdmaTransfer::HBlank { block_due }) = &mut self.transfer {
+      *block_due = true;
+    }
+  }
+
+  /// Advances by an M-cycle, returning the number of blocks that need to be copied now.
+  ///
+  /// The CPU gets halted for the M-cycles that it takes to copy those blocks.
+  pub fn step_m_cycle(&mut self) -> u8 {
+    self.stalled_m_cycles = self.stalled_m_cycles.saturating_sub(1);
+
+    if self.stalled_m_cycles > 0 {
+      return 0;
+    }
+
+    let blocks = match self.transfer {
+      Some(HdmaTransfer::GeneralPurpose) => self.remaining_blocks,
+      Some(HdmaTransfer::HBlank { block_due: true }) => {
+        self.transfer = Some(HdmaTransfer::HBlank { block_due: false });
+
+        1
+      }
+      _ => return 0,
+    };
+
+    self.remaining_blocks -= blocks;
+    self.stalled_m_cycles = blocks as u16 * M_CYCLES_PER_BLOCK;
+
+    if self.remaining_blocks == 0 {
+      self.transfer = None;
+    }
+
+    blocks
+  }
+
+  /// Returns the next source and destination addresses to copy a byte between, advancing
+  /// both of them.
+  pub fn next_addresses(&mut self) -> (u16, u16) {
+    let addresses = (self.source, 0x8000 | self.destination);
+
+    self.source = self.source.wrapping_add(1);
+    self.destination = (self.destination + 1) & 0x1FFF;
+
+    addresses
+  }
+
+  /// Returns whether the CPU is halted while blocks get copied.
+  pub fn cpu_stalled(&self) -> bool {
+    self.stalled_m_cycles > 0
+  }
+}
+
+impl Default for Hdma {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// The bit mask for the HDMA5 register for using HBlank DMA.
+const HBLANK_MODE_MASK: u8 = 0x80;
+/// The number of M-cycles it takes to copy a block of `0x10` bytes.
+const M_CYCLES_PER_BLOCK: u16 = 8;
+/// The number of bytes in each block.
+pub const HDMA_BLOCK_SIZE: u16 = 0x10;
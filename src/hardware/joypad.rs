@@ -1,19 +1,42 @@
+use std::{fmt, str::FromStr};
+
 use crate::{
-  flags::{add_flag, is_falling_edge, remove_flag},
+  flags::{add_flag, remove_flag},
   interrupts::{Interrupt, Interrupts},
 };
 
 /// The input controller used to interact with the game.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Joypad {
   /// The buttons that are pressed.
   pressed: u8,
   /// The group of buttons that are pressed.
   button_group: u8,
+  /// How opposing directions that are pressed together get resolved.
+  socd_mode: SocdMode,
+  /// The horizontal direction that was pressed last.
+  last_horizontal: Button,
+  /// The vertical direction that was pressed last.
+  last_vertical: Button,
+}
+
+/// How simultaneous opposing cardinal directions (SOCD) on the d-pad get resolved.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SocdMode {
+  /// Opposing directions are reported as-is, like the d-pad on real hardware.
+  #[default]
+  Hardware,
+  /// The direction that was pressed last wins.
+  LastInputWins,
+  /// Opposing directions cancel each other out.
+  Neutral,
 }
 
 /// The set of buttons on the joypad.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Button {
   /// The `A` button.
@@ -34,6 +57,83 @@ pub enum Button {
   Down = 1 << 7,
 }
 
+impl Button {
+  /// Returns every [`Button`], in bit order.
+  pub const fn all() -> [Button; 8] {
+    [
+      Button::A,
+      Button::B,
+      Button::Select,
+      Button::Start,
+      Button::Right,
+      Button::Left,
+      Button::Up,
+      Button::Down,
+    ]
+  }
+
+  /// Returns the name of the [`Button`].
+  pub const fn name(&self) -> &'static str {
+    match self {
+      Button::A => "A",
+      Button::B => "B",
+      Button::Select => "Select",
+      Button::Start => "Start",
+      Button::Right => "Right",
+      Button::Left => "Left",
+      Button::Up => "Up",
+      Button::Down => "Down",
+    }
+  }
+}
+
+impl fmt::Display for Button {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.name())
+  }
+}
+
+impl FromStr for Button {
+  type Err = ParseButtonError;
+
+  /// Parses a [`Button`] from its name, ignoring case.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Button::all()
+      .into_iter()
+      .find(|button| button.name().eq_ignore_ascii_case(s))
+      .ok_or_else(|| ParseButtonError(s.to_string()))
+  }
+}
+
+/// A set of pressed buttons, as a bitfield of [`Button`]s.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ButtonState(pub u8);
+
+impl ButtonState {
+  /// Returns the [`ButtonState`] with the [`Button`] pressed as well.
+  pub const fn with(self, button: Button) -> Self {
+    Self(self.0 | button as u8)
+  }
+
+  /// Returns whether the [`Button`] is pressed.
+  pub const fn is_pressed(self, button: Button) -> bool {
+    self.0 & button as u8 != 0
+  }
+}
+
+/// An error returned when a string isn't the name of a [`Button`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseButtonError(String);
+
+impl fmt::Display for ParseButtonError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "unknown button: {}", self.0)
+  }
+}
+
+impl std::error::Error for ParseButtonError {}
+
 impl Joypad {
   /// Creates a new [`Joypad`] in a released state.
   pub const fn new() -> Self {
@@ -42,9 +142,22 @@ impl Joypad {
       pressed: 0xFF,
       // Mark the groups as unselected
       button_group: 0x30,
+      socd_mode: SocdMode::Hardware,
+      last_horizontal: Button::Right,
+      last_vertical: Button::Down,
     }
   }
 
+  /// Sets how simultaneous opposing directions get resolved.
+  pub fn set_socd_mode(&mut self, socd_mode: SocdMode) {
+    self.socd_mode = socd_mode;
+  }
+
+  /// Returns how simultaneous opposing directions get resolved.
+  pub fn socd_mode(&self) -> SocdMode {
+    self.socd_mode
+  }
+
   /// Reads the value of the [`Joypad`].
   pub fn read_register(&self) -> u8 {
     let lower_nibble = self.register_value();
@@ -53,10 +166,20 @@ impl Joypad {
     0b1100_0000 | self.button_group | lower_nibble
   }
 
+  /// Returns whether a button in the selected groups is held, which ends `STOP`.
+  pub fn selected_button_held(&self) -> bool {
+    self.register_value() != 0x0F
+  }
+
   /// Updates the [`Joypad`] button group.
-  pub fn write_register(&mut self, value: u8) {
+  pub fn write_register(&mut self, interrupts: &mut Interrupts, value: u8) {
+    let before_lower_nibble = self.register_value();
+
     // Only bits 4 and 5 are writeable
     self.button_group = value & 0b0011_0000;
+
+    // Selecting a group with a button that's held down also pulls its line low
+    request_on_falling_edge(interrupts, before_lower_nibble, self.register_value());
   }
 
   /// Updates the button's state.
@@ -74,28 +197,89 @@ impl Joypad {
       ButtonAction::Released => add_flag!(&mut self.pressed, button as u8),
     }
 
-    let after_lower_nibble = self.register_value();
-
-    // Interrupts are ONLY fired if there is a falling edge in the lower nibble
-    if is_falling_edge!(before_lower_nibble != 0, !after_lower_nibble != 0) {
-      interrupts.request_interrupt(Interrupt::Joypad);
+    if matches!(button_state, ButtonAction::Pressed) {
+      match button {
+        Button::Left | Button::Right => self.last_horizontal = button,
+        Button::Up | Button::Down => self.last_vertical = button,
+        _ => {}
+      }
     }
+
+    request_on_falling_edge(interrupts, before_lower_nibble, self.register_value());
   }
 
   /// Returns the lower nibble of the selected group of buttons.
   const fn register_value(&self) -> u8 {
+    let pressed = self.resolved_buttons();
+
     match (self.button_group >> 4) & 0x3 {
       // The action group was selected, if the 5th bit was 0
-      0b01 => self.pressed & 0x0F,
+      0b01 => pressed & 0x0F,
       // The d-pad group was selected, if the 4th bit was 0
-      0b10 => (self.pressed & 0xF0) >> 4,
+      0b10 => (pressed & 0xF0) >> 4,
       // If the 4th and 5th bits are 0, then both groups are combined
-      0b00 => (self.pressed & 0x0F) & ((self.pressed & 0xF0) >> 4),
+      0b00 => (pressed & 0x0F) & ((pressed & 0xF0) >> 4),
       // No button group was selected
       0b11 => 0x0F,
       _ => unreachable!(),
     }
   }
+
+  /// Returns the pressed buttons after resolving opposing directions with the [`SocdMode`].
+  const fn resolved_buttons(&self) -> u8 {
+    let horizontal = resolve_opposing_directions(
+      self.pressed,
+      self.socd_mode,
+      Button::Left,
+      Button::Right,
+      self.last_horizontal,
+    );
+
+    resolve_opposing_directions(
+      horizontal,
+      self.socd_mode,
+      Button::Up,
+      Button::Down,
+      self.last_vertical,
+    )
+  }
+}
+
+impl Default for Joypad {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Requests the joypad interrupt if there's a falling edge on one of the selected lines in the
+/// lower nibble, which is the only time that it's requested.
+fn request_on_falling_edge(interrupts: &mut Interrupts, before: u8, after: u8) {
+  if before & !after != 0 {
+    interrupts.request_interrupt(Interrupt::Joypad);
+  }
+}
+
+/// Resolves a pair of opposing directions that are pressed at the same time.
+const fn resolve_opposing_directions(
+  pressed: u8,
+  socd_mode: SocdMode,
+  first: Button,
+  second: Button,
+  last_pressed: Button,
+) -> u8 {
+  let both_mask = first as u8 | second as u8;
+
+  // Both directions need to be pressed, which is when both bits are 0
+  if pressed & both_mask != 0 {
+    return pressed;
+  }
+
+  match socd_mode {
+    SocdMode::Hardware => pressed,
+    // Release the direction that wasn't pressed last
+    SocdMode::LastInputWins => pressed | (both_mask & !(last_pressed as u8)),
+    SocdMode::Neutral => pressed | both_mask,
+  }
 }
 
 /// A button action.
@@ -106,3 +290,133 @@ pub enum ButtonAction {
   /// The button was released.
   Released,
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    emulator::{Emulator, Executed},
+    hardware::cpu::CpuState,
+  };
+
+  /// The number of T-cycles to check that the CPU stays halted for, which is a few scanlines.
+  const HALTED_CYCLES: usize = 1000;
+
+  #[test]
+  fn register_write_only_keeps_group_bits() {
+    let mut joypad = Joypad::new();
+    let mut interrupts = Interrupts::new();
+
+    // Neither group is selected, so the written button bits read back as released
+    joypad.write_register(&mut interrupts, 0xFF);
+
+    assert_eq!(joypad.read_register(), 0xFF);
+
+    joypad.update_button_state(&mut interrupts, Button::A, ButtonAction::Pressed);
+
+    assert_eq!(joypad.read_register(), 0xFF);
+
+    // The button bits come from the pressed buttons, not from the written `0xF`
+    joypad.write_register(&mut interrupts, 0xDF);
+
+    assert_eq!(joypad.read_register(), 0xDE);
+
+    joypad.update_button_state(&mut interrupts, Button::A, ButtonAction::Released);
+
+    assert_eq!(joypad.read_register(), 0xDF);
+
+    // The unused upper 2 bits always read as set
+    joypad.write_register(&mut interrupts, 0x10);
+
+    assert_eq!(joypad.read_register(), 0xDF);
+  }
+
+  /// Returns whether the joypad interrupt was requested.
+  fn joypad_interrupt_requested(interrupts: &Interrupts) -> bool {
+    interrupts.requested_bitfield() & Interrupt::Joypad as u8 != 0
+  }
+
+  #[test]
+  fn direction_press_only_interrupts_with_directions_selected() {
+    let mut joypad = Joypad::new();
+    let mut interrupts = Interrupts::new();
+
+    // Only the action buttons are selected
+    joypad.write_register(&mut interrupts, 0x10);
+    joypad.update_button_state(&mut interrupts, Button::Up, ButtonAction::Pressed);
+
+    assert!(!joypad_interrupt_requested(&interrupts));
+
+    joypad.update_button_state(&mut interrupts, Button::Up, ButtonAction::Released);
+
+    // Only the directions are selected
+    joypad.write_register(&mut interrupts, 0x20);
+    joypad.update_button_state(&mut interrupts, Button::Up, ButtonAction::Pressed);
+
+    assert!(joypad_interrupt_requested(&interrupts));
+  }
+
+  #[test]
+  fn only_new_presses_interrupt() {
+    let mut joypad = Joypad::new();
+    let mut interrupts = Interrupts::new();
+
+    joypad.write_register(&mut interrupts, 0x20);
+    joypad.update_button_state(&mut interrupts, Button::Up, ButtonAction::Pressed);
+    interrupts.clear_interrupt(Interrupt::Joypad);
+
+    // Pressing a held button again and releasing it aren't high-to-low transitions
+    joypad.update_button_state(&mut interrupts, Button::Up, ButtonAction::Pressed);
+    joypad.update_button_state(&mut interrupts, Button::Up, ButtonAction::Released);
+
+    assert!(!joypad_interrupt_requested(&interrupts));
+  }
+
+  #[test]
+  fn selecting_group_with_held_button_interrupts() {
+    let mut joypad = Joypad::new();
+    let mut interrupts = Interrupts::new();
+
+    joypad.write_register(&mut interrupts, 0x10);
+    joypad.update_button_state(&mut interrupts, Button::Up, ButtonAction::Pressed);
+
+    assert!(!joypad_interrupt_requested(&interrupts));
+
+    // Selecting the directions pulls the line of the held button low
+    joypad.write_register(&mut interrupts, 0x20);
+
+    assert!(joypad_interrupt_requested(&interrupts));
+  }
+
+  #[test]
+  fn button_press_wakes_cpu_from_halt() {
+    // Select the directions, then HALT
+    let mut emulator = Emulator::with_program(&[0x3E, 0x20, 0xE0, 0x00, 0x76, 0x00]);
+
+    emulator.cpu_mut().set_interrupt_master_enabled(true);
+    emulator
+      .hardware
+      .write_byte(0xFFFF, Interrupt::Joypad as u8);
+
+    emulator.step_instruction().unwrap();
+    emulator.step_instruction().unwrap();
+
+    // The CPU stays halted for as long as nothing gets pressed
+    let mut cycles = 0;
+
+    emulator.step_until(|_| {
+      cycles += 1;
+      cycles == HALTED_CYCLES
+    });
+
+    assert_eq!(emulator.cpu().state(), CpuState::Halted);
+
+    emulator
+      .hardware
+      .update_button(Button::Down, ButtonAction::Pressed);
+
+    let step = emulator.step_instruction().unwrap();
+
+    assert_eq!(step.executed, Executed::Interrupt(Some(Interrupt::Joypad)));
+  }
+}
@@ -3,16 +3,49 @@ use crate::{
   interrupts::{Interrupt, Interrupts},
 };
 use arrayvec::ArrayVec;
+use fifo::PixelTransfer;
+
+mod fifo;
+
+/// A frame of shades, which are between 0 and 3.
+pub type Frame = [[u8; 160]; 144];
+
+/// A frame of RGB555 colors, which is only rendered in CGB mode.
+pub type ColorFrame = [[u16; 160]; 144];
 
 /// The pixel processing unit.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ppu {
-  /// The working memory for the PPU.
-  memory: [u8; VIDEO_RAM_SIZE as usize],
+  /// The working memory for the PPU, which has a second bank in CGB mode.
+  #[cfg_attr(feature = "serde", serde(with = "crate::serde_arrays::boxed_bytes"))]
+  memory: Box<[u8; VIDEO_RAM_SIZE as usize * 2]>,
   /// The object attribute map.
+  #[cfg_attr(feature = "serde", serde(with = "crate::serde_arrays::bytes"))]
   oam: [u8; OAM_SIZE as usize],
-  /// The frame buffer.
-  buffer: [[u8; 160]; 144],
+  /// The frame buffer that scanlines get rendered into.
+  ///
+  /// The frame buffers are boxed to keep the PPU small, since it gets moved around when
+  /// creating and deserializing the hardware. The back buffers are left out of save states,
+  /// since they match the front buffers at the end of every frame.
+  #[cfg_attr(feature = "serde", serde(skip, default = "blank_frame"))]
+  back_buffer: Box<Frame>,
+  /// The last complete frame, which gets updated when entering VBlank.
+  #[cfg_attr(feature = "serde", serde(with = "crate::serde_arrays::frame"))]
+  front_buffer: Box<Frame>,
+  /// The color frame buffer that scanlines get rendered into, in CGB mode.
+  #[cfg_attr(feature = "serde", serde(skip, default = "blank_color_frame"))]
+  back_color_buffer: Box<ColorFrame>,
+  /// The last complete color frame, in CGB mode.
+  #[cfg_attr(feature = "serde", serde(with = "crate::serde_arrays::color_frame"))]
+  front_color_buffer: Box<ColorFrame>,
+  /// Whether a frame was completed during the last step.
+  frame_completed: bool,
+  /// Whether HBlank started during the last step.
+  hblank_started: bool,
+  /// Whether scanlines get rendered even when the LCD is off, as a debug override.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  force_render: bool,
 
   /// The LCD byte that controls what gets shown on the screen.
   lcdc: u8,
@@ -26,6 +59,9 @@ pub struct Ppu {
   ly: u8,
   /// The window's scanline.
   wly: u8,
+  /// Whether `LY` was equal to `WY` during an OAM scan this frame, which the window needs
+  /// to be drawn.
+  wy_triggered: bool,
   /// LY Compare.
   lyc: u8,
   /// Background and Window palette.
@@ -39,8 +75,32 @@ pub struct Ppu {
   /// Window X position.
   wx: u8,
 
+  /// Whether the PPU runs in CGB mode, where colors come from the CGB palettes.
+  cgb_mode: bool,
+  /// The VRAM bank that the CPU accesses, in CGB mode.
+  vram_bank: u8,
+  /// The background palette specification, which selects the byte of the background
+  /// palettes that `BCPD` accesses.
+  bcps: u8,
+  /// The object palette specification, which selects the byte of the object palettes that
+  /// `OCPD` accesses.
+  ocps: u8,
+  /// The 8 background palettes of 4 RGB555 colors each, in CGB mode.
+  #[cfg_attr(feature = "serde", serde(with = "crate::serde_arrays::bytes"))]
+  bg_palettes: [u8; CGB_PALETTES_SIZE],
+  /// The 8 object palettes of 4 RGB555 colors each, in CGB mode.
+  #[cfg_attr(feature = "serde", serde(with = "crate::serde_arrays::bytes"))]
+  obj_palettes: [u8; CGB_PALETTES_SIZE],
+
+  /// The state of the pixel transfer on the current scanline.
+  pixel_transfer: PixelTransfer,
+
   /// Internal counter for tracking cycles.
   counter: usize,
+  /// The number of T-cycles left until `LY==LYC` gets compared, after `LY` changes.
+  lyc_compare_delay: u8,
+  /// Whether the STAT interrupt line was high after the last step.
+  stat_line: bool,
 
   /// The last value set when executing a DMA transfer,
   dma: u8,
@@ -52,6 +112,7 @@ pub struct Ppu {
 
 /// The state of a direct memory transfer.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DmaTransfer {
   /// The source address of where to copy from, for this the DMA transfer.
   pub source: u8,
@@ -61,6 +122,7 @@ pub struct DmaTransfer {
 
 /// The progress of an existing DMA transfer.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DmaTransferProgress {
   /// A DMA transfer was requested and is going to begin after an M-cycle has elapsed.
   Requested { delay_ticks: u8 },
@@ -70,6 +132,7 @@ pub enum DmaTransferProgress {
 
 /// A DMA transfer when one is already running.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RestartedDmaTransfer {
   /// The source address of where to copy from, for this the DMA transfer.
   pub source: u8,
@@ -88,6 +151,7 @@ impl Ppu {
       scx: 0,
       ly: 0,
       wly: 0,
+      wy_triggered: false,
       lyc: 0,
       bgp: 0,
       obp0: 0,
@@ -95,72 +159,119 @@ impl Ppu {
       wy: 0,
       wx: 0,
 
+      cgb_mode: false,
+      vram_bank: 0,
+      bcps: 0,
+      ocps: 0,
+      bg_palettes: [0xFF; CGB_PALETTES_SIZE],
+      obj_palettes: [0xFF; CGB_PALETTES_SIZE],
+
+      pixel_transfer: PixelTransfer::new(ArrayVec::new(), 0),
+
       counter: 0,
+      lyc_compare_delay: 0,
+      stat_line: false,
 
       dma: 0,
       dma_transfer: None,
       restarted_dma_transfer: None,
 
-      memory: [0; VIDEO_RAM_SIZE as usize],
+      memory: Box::new([0; VIDEO_RAM_SIZE as usize * 2]),
       oam: [0; OAM_SIZE as usize],
-      buffer: [[0; 160]; 144],
+      back_buffer: Box::new([[0; 160]; 144]),
+      front_buffer: Box::new([[0; 160]; 144]),
+      back_color_buffer: Box::new([[WHITE; 160]; 144]),
+      front_color_buffer: Box::new([[WHITE; 160]; 144]),
+      frame_completed: false,
+      hblank_started: false,
+      force_render: false,
     }
   }
 
   /// Steps the PPU by a T-cycle.
   pub fn step(&mut self, interrupts: &mut Interrupts) {
     self.counter += 1;
+    self.frame_completed = false;
+    self.hblank_started = false;
+
+    // The PPU is idle while the LCD is off, but blank frames still get completed at the usual
+    // rate, so that whatever waits for frames keeps going
+    if !self.display_enabled() {
+      if self.counter >= FRAME_CYCLES {
+        self.counter -= FRAME_CYCLES;
+        self.frame_completed = true;
+
+        if self.force_render {
+          self.render_forced_frame();
+        } else {
+          self.blank_screen();
+        }
+      }
 
-    // `LY==LYC` needs to be checked every cycle.
-    if self.ly == self.lyc {
-      if !is_flag_set!(self.stat, StatFlag::Coincidence as u8) {
-        add_flag!(&mut self.stat, StatFlag::Coincidence as u8);
+      self.update_stat_line(interrupts);
 
-        if is_flag_set!(self.stat, StatFlag::LycInterrupt as u8) {
-          interrupts.request_interrupt(Interrupt::Lcd);
-        }
+      return;
+    }
+
+    // `LY==LYC` needs to be checked every cycle, except right after `LY` changes, where
+    // the comparison only happens an M-cycle later.
+    self.lyc_compare_delay = self.lyc_compare_delay.saturating_sub(1);
+
+    if self.lyc_compare_delay == 0 {
+      if self.ly == self.lyc {
+        add_flag!(&mut self.stat, StatFlag::Coincidence as u8);
+      } else {
+        remove_flag!(&mut self.stat, StatFlag::Coincidence as u8);
       }
-    } else {
-      remove_flag!(&mut self.stat, StatFlag::Coincidence as u8);
     }
 
     match self.current_mode() {
       // OAM scan lasts for 80 cycles
       PpuMode::OamScan => {
+        // The window's Y position is only compared during OAM scan, and stays triggered for
+        // the rest of the frame, even if `WY` changes afterwards
+        if self.ly == self.wy {
+          self.wy_triggered = true;
+        }
+
         if self.counter >= 80 {
           self.counter -= 80;
           self.set_current_mode(PpuMode::PixelTransfer);
-
-          if is_flag_set!(self.stat, StatFlag::OamInterrupt as u8) {
-            interrupts.request_interrupt(Interrupt::Lcd)
-          }
+          self.start_pixel_transfer();
         }
       }
-      // Pixel transfer lasts for 172 cycles
+      // Pixel transfer lasts for 172 to 289 cycles, depending on the scrolling, the window
+      // and the sprites on the scanline
       PpuMode::PixelTransfer => {
-        if self.counter >= 172 {
-          self.counter -= 172;
+        self.step_pixel_transfer();
+
+        if self.pixel_transfer.is_done() {
+          self.counter = 0;
           self.set_current_mode(PpuMode::HBlank);
-          self.render_scanline();
+          self.hblank_started = true;
 
-          if is_flag_set!(self.stat, StatFlag::HBlankInterrupt as u8) {
-            interrupts.request_interrupt(Interrupt::Lcd);
+          // The window's internal counter is only incremented after window rendering
+          if self.pixel_transfer.window_active() {
+            self.wly = self.wly.wrapping_add(1);
           }
         }
       }
-      // HBlank last for 204 cycles
+      // HBlank lasts for the rest of the 376 cycles after OAM scan
       PpuMode::HBlank => {
-        if self.counter >= 204 {
-          self.counter -= 204;
-          self.ly = self.ly.wrapping_add(1);
+        let hblank_cycles = (SCANLINE_DRAWING_CYCLES - self.pixel_transfer.dots()) as usize;
+
+        if self.counter >= hblank_cycles {
+          self.counter -= hblank_cycles;
+          self.set_ly(self.ly + 1);
 
           if self.ly == 144 {
+            // The frame is complete, so present it
+            *self.front_buffer = *self.back_buffer;
+            *self.front_color_buffer = *self.back_color_buffer;
+            self.frame_completed = true;
+
             interrupts.request_interrupt(Interrupt::VBlank);
             self.set_current_mode(PpuMode::VBlank);
-
-            if is_flag_set!(self.stat, StatFlag::VBlankInterrupt as u8) {
-              interrupts.request_interrupt(Interrupt::Lcd);
-            }
           } else {
             self.set_current_mode(PpuMode::OamScan);
           }
@@ -168,18 +279,29 @@ impl Ppu {
       }
       // VBlank last for 456 cycles
       PpuMode::VBlank => {
+        // `LY` only reads as 153 for the first M-cycle of the last scanline, and as 0 for the
+        // rest of it. The flag from comparing against 153 holds until `LY` gets compared again.
+        if self.ly == 153 && self.counter == LINE_153_CYCLES {
+          self.ly = 0;
+          self.lyc_compare_delay = LYC_COMPARE_DELAY;
+        }
+
         if self.counter >= 456 {
           self.counter -= 456;
-          self.ly = self.ly.wrapping_add(1);
 
-          if self.ly > 153 {
-            self.ly = 0;
+          // `LY` is already 0 at the end of the last scanline
+          if self.ly == 0 {
             self.wly = 0;
+            self.wy_triggered = false;
             self.set_current_mode(PpuMode::OamScan);
+          } else {
+            self.set_ly(self.ly + 1);
           }
         }
       }
     }
+
+    self.update_stat_line(interrupts);
   }
 
   /// Reads the value of the register referencing the address.
@@ -197,6 +319,20 @@ impl Ppu {
       0xFF49 => self.obp1,
       0xFF4A => self.wy,
       0xFF4B => self.wx,
+      // Only bit 0 of VBK is used, and it only exists in CGB mode
+      0xFF4F if self.cgb_mode => self.vram_bank | 0xFE,
+      0xFF4F => 0xFF,
+      // The palette registers only exist in CGB mode, and bit 6 is unused
+      0xFF68 if self.cgb_mode => self.bcps | 0x40,
+      0xFF6A if self.cgb_mode => self.ocps | 0x40,
+      // The palettes can't be read while the PPU is drawing
+      0xFF69 if self.cgb_mode && self.can_access_vram() => {
+        self.bg_palettes[(self.bcps & PALETTE_INDEX_MASK) as usize]
+      }
+      0xFF6B if self.cgb_mode && self.can_access_vram() => {
+        self.obj_palettes[(self.ocps & PALETTE_INDEX_MASK) as usize]
+      }
+      0xFF68..0xFF6C => 0xFF,
       _ => unreachable!(),
     }
   }
@@ -205,17 +341,28 @@ impl Ppu {
   pub fn write_register(&mut self, address: u16, value: u8) {
     match address {
       0xFF40 => {
-        // Reset the line counter when the window gets disabled
-        if is_flag_set!(self.lcdc, LcdControl::LcdDisplay as u8)
-          && !is_flag_set!(value, LcdControl::LcdDisplay as u8)
-        {
+        let was_enabled = self.display_enabled();
+
+        self.lcdc = value;
+
+        if was_enabled && !self.display_enabled() {
+          // Turning the LCD off blanks it right away, and stops the PPU at the start of the
+          // frame in HBlank
           self.ly = 0;
           self.wly = 0;
+          self.wy_triggered = false;
+          self.counter = 0;
+          self.set_current_mode(PpuMode::HBlank);
+          self.blank_screen();
 
           remove_flag!(&mut self.stat, StatFlag::Coincidence as u8);
+        } else if !was_enabled && self.display_enabled() {
+          // The PPU restarts from OAM scan on the first scanline, which is a few T-cycles
+          // shorter than the others
+          self.counter = LCD_ENABLE_SKIPPED_CYCLES;
+          self.set_current_mode(PpuMode::OamScan);
+          self.set_ly(0);
         }
-
-        self.lcdc = value;
       }
       // Preserve the PPU mode in the lower 2 bits
       0xFF41 => self.stat = (value & 0b0111_1100) | self.current_mode() as u8,
@@ -238,18 +385,35 @@ impl Ppu {
       0xFF49 => self.obp1 = value,
       0xFF4A => self.wy = value,
       0xFF4B => self.wx = value,
+      0xFF4F if self.cgb_mode => self.vram_bank = value & 0x01,
+      0xFF4F => {}
+      0xFF68 if self.cgb_mode => self.bcps = value & (PALETTE_AUTO_INCREMENT | PALETTE_INDEX_MASK),
+      0xFF6A if self.cgb_mode => self.ocps = value & (PALETTE_AUTO_INCREMENT | PALETTE_INDEX_MASK),
+      0xFF69 if self.cgb_mode => {
+        let can_access = self.can_access_vram();
+
+        write_palette_data(&mut self.bcps, &mut self.bg_palettes, value, can_access);
+      }
+      0xFF6B if self.cgb_mode => {
+        let can_access = self.can_access_vram();
+
+        write_palette_data(&mut self.ocps, &mut self.obj_palettes, value, can_access);
+      }
+      0xFF68..0xFF6C => {}
       _ => unreachable!(),
     }
   }
 
   /// Reads the 8-bit value in memory at the provided address.
   pub fn read_ram(&self, address: u16) -> u8 {
-    self.memory[(address - 0x8000) as usize]
+    self.read_vram(self.vram_bank, address)
   }
 
   /// Writes 8-bits of memory to the provided address.
   pub fn write_ram(&mut self, address: u16, value: u8) {
-    self.memory[(address - 0x8000) as usize] = value;
+    let index = vram_index(self.vram_bank, address);
+
+    self.memory[index] = value;
   }
 
   /// Reads 8-bits of OAM memory at the provided address.
@@ -267,11 +431,38 @@ impl Ppu {
     PpuMode::try_from(self.stat & 0x03).unwrap()
   }
 
+  /// Returns the current scanline, `LY`.
+  pub fn current_scanline(&self) -> u8 {
+    self.ly
+  }
+
   /// Returns whether the LCD is enabled.
   pub fn display_enabled(&self) -> bool {
     is_flag_set!(self.lcdc, LcdControl::LcdDisplay as u8)
   }
 
+  /// Sets whether scanlines get rendered even when the game turned the LCD off, so that the
+  /// contents of VRAM can be seen. This doesn't change `LCDC`.
+  pub fn set_force_render(&mut self, force_render: bool) {
+    self.force_render = force_render;
+  }
+
+  /// Returns whether scanlines get rendered even when the LCD is off.
+  pub fn force_render(&self) -> bool {
+    self.force_render
+  }
+
+  /// Sets whether the PPU runs in CGB mode, where the CGB palettes are accessible and
+  /// frames are also rendered in color.
+  pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+    self.cgb_mode = cgb_mode;
+  }
+
+  /// Returns whether the PPU runs in CGB mode.
+  pub fn cgb_mode(&self) -> bool {
+    self.cgb_mode
+  }
+
   /// Returns whether the OAM can be accessed by the CPU.
   pub fn can_access_oam(&self) -> bool {
     // The PPU can only read from OAM if the LCD is off or the PPU is not in
@@ -294,270 +485,326 @@ impl Ppu {
     )
   }
 
+  /// Returns the number of bytes that the running DMA transfer copied into OAM, if any.
+  pub fn dma_bytes_transferred(&self) -> Option<u16> {
+    match self.dma_transfer {
+      Some(DmaTransfer {
+        progress: DmaTransferProgress::Transferring { ticks },
+        ..
+      }) => Some(ticks / DMA_CYCLES_PER_BYTE),
+      _ => None,
+    }
+  }
+
   /// Returns whether the OAM can accessed by the CPU.
   pub fn can_access_vram(&self) -> bool {
     // The PPU can only read VRAM if the LCD is off or the PPU is not in pixel transfer.
     !self.display_enabled() || !matches!(self.current_mode(), PpuMode::PixelTransfer)
   }
 
-  /// Gets the frame buffer.
-  pub fn buffer(&self) -> &[[u8; 160]; 144] {
-    &self.buffer
+  /// Gets the last completely rendered frame.
+  pub fn buffer(&self) -> &Frame {
+    &self.front_buffer
   }
 
-  /// Sets the mode of the PPU.
-  fn set_current_mode(&mut self, mode: PpuMode) {
-    // The 7th bit is unused and the lower 2 bits store the mode
-    self.stat = (self.stat & 0b0111_1100) | mode as u8;
+  /// Gets the last completely rendered color frame, which is only rendered in CGB mode.
+  pub fn color_buffer(&self) -> &ColorFrame {
+    &self.front_color_buffer
   }
 
-  /// Fetches a pixel from the given tile index, row (0-7 inclusive),
-  /// and X-coordinate (0-7 inclusive).
-  fn get_tile_pixel(&self, tile_index: u8, row: u8, x: u8) -> u8 {
-    let (base_addr, tile_index) = if is_flag_set!(self.lcdc, LcdControl::BackgroundTileData as u8) {
-      (0x8000, tile_index)
+  /// Copies the last complete frames into the frame buffers that get rendered into, which save
+  /// states leave out.
+  #[cfg(feature = "save-states")]
+  pub(crate) fn restore_back_buffers(&mut self) {
+    *self.back_buffer = *self.front_buffer;
+    *self.back_color_buffer = *self.front_color_buffer;
+  }
+
+  /// Hashes the last completely rendered frame with FNV-1a, which is cheap enough to do every
+  /// frame. The color frame gets hashed instead in CGB mode.
+  pub fn frame_hash(&self) -> u64 {
+    let fnv1a = |hash: u64, byte: u8| (hash ^ byte as u64).wrapping_mul(FNV_PRIME);
+
+    if self.cgb_mode {
+      self
+        .front_color_buffer
+        .as_flattened()
+        .iter()
+        .flat_map(|color| color.to_le_bytes())
+        .fold(FNV_OFFSET_BASIS, fnv1a)
     } else {
-      // Tile indices in this region are signed, so we need to offset them accordingly.
-      //
-      // Tile indices [0, 128) reference sprites starting from 0x9000 and
-      // tile indices [128, 255) reference sprites starting from 0x8800.
-      if tile_index < 128 {
-        (0x9000, tile_index)
+      self
+        .front_buffer
+        .as_flattened()
+        .iter()
+        .copied()
+        .fold(FNV_OFFSET_BASIS, fnv1a)
+    }
+  }
+
+  /// Returns which sprites the OAM scan picks for the scanline.
+  pub fn sprite_selection(&self, ly: u8) -> SpriteSelection {
+    let mut selection = SpriteSelection::default();
+
+    for sprite in self.sprites_on_line(ly) {
+      if selection.selected.is_full() {
+        selection.dropped.push(sprite.oam_position);
       } else {
-        (0x8800, tile_index - 128)
+        selection.selected.push(sprite.oam_position);
       }
-    };
-
-    // Tiles are stored in 16 bytes
-    let tile_offset = tile_index as u16 * 16;
-    let row_offset = row as u16 * 2;
-    let lower = self.read_ram(base_addr + tile_offset + row_offset);
-    let upper = self.read_ram(base_addr + tile_offset + row_offset + 1);
-    let bit = 7 - (x % 8);
+    }
 
-    (((upper >> bit) & 1) << 1) | ((lower >> bit) & 1)
+    selection
   }
 
-  /// Fetches a sprite's pixel from the given tile index, row (0, 7 inclusive),
-  /// and X-coordinate (0-7 inclusive).
-  fn get_sprite_pixel(&self, tile_index: u8, row: u8, x: u8, flip_x: bool) -> u8 {
-    // Sprites are stored in 16 bytes
-    let tile_offset = tile_index as u16 * 16;
-    let row_offset = row as u16 * 2;
-    let lower = self.read_ram(0x8000 + tile_offset + row_offset);
-    let upper = self.read_ram(0x8000 + tile_offset + row_offset + 1);
-    let bit = if flip_x { x } else { 7 - x };
+  /// Returns whether a frame was completed during the last step.
+  pub fn frame_completed(&self) -> bool {
+    self.frame_completed
+  }
 
-    (((upper >> bit) & 1) << 1) | ((lower >> bit) & 1)
+  /// Returns whether HBlank started during the last step.
+  pub fn hblank_started(&self) -> bool {
+    self.hblank_started
   }
 
-  /// Renders a complete scanline into the frame buffer.
-  fn render_scanline(&mut self) {
-    // Only render if the LCD is enabled.
-    if !is_flag_set!(self.lcdc, LcdControl::LcdDisplay as u8) {
-      return;
+  /// Places the PPU at the exact scanline, mode, and number of T-cycles into that mode.
+  ///
+  /// This bypasses the normal timing and the interrupts that would have been requested on
+  /// the way there, so it should only be used for tests.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn force_state(&mut self, ly: u8, mode: PpuMode, counter: usize) {
+    self.ly = ly;
+    self.counter = counter;
+    self.set_current_mode(mode);
+
+    // Catch the pixel transfer up to the number of T-cycles into it
+    if mode == PpuMode::PixelTransfer {
+      self.start_pixel_transfer();
+
+      for _ in 0..counter {
+        self.step_pixel_transfer();
+      }
     }
+  }
 
-    let mut scanline = [0; 160];
-
-    // Render background if enabled
-    if is_flag_set!(self.lcdc, LcdControl::BackgroundDisplay as u8) {
-      self.render_background(&mut scanline);
-    }
+  /// Sets the `LCDC` register directly, without any of the side effects of a write.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn set_lcdc(&mut self, value: u8) {
+    self.lcdc = value;
+  }
 
-    // Render window if enabled
-    if is_flag_set!(self.lcdc, LcdControl::WindowDisplay as u8) {
-      self.render_window(&mut scanline);
-    }
+  /// Sets the `STAT` register directly, preserving the PPU mode in the lower 2 bits.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn set_stat(&mut self, value: u8) {
+    self.stat = (value & 0b0111_1100) | self.current_mode() as u8;
+  }
 
-    // Render sprites if enabled
-    if is_flag_set!(self.lcdc, LcdControl::SpriteDisplay as u8) {
-      self.render_sprites(&mut scanline);
-    }
+  /// Sets the `SCX` register directly.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn set_scx(&mut self, value: u8) {
+    self.scx = value;
+  }
 
-    self.buffer[self.ly as usize] = scanline;
+  /// Sets the `SCY` register directly.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn set_scy(&mut self, value: u8) {
+    self.scy = value;
   }
 
-  /// Renders the background into the provided scanline.
-  fn render_background(&mut self, scanline: &mut [u8; 160]) {
-    let bg_tile_map = if is_flag_set!(self.lcdc, LcdControl::BackgroundTileMap as u8) {
-      0x9C00
-    } else {
-      0x9800
-    };
+  /// Sets the `BGP` register directly.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn set_bgp(&mut self, value: u8) {
+    self.bgp = value;
+  }
 
-    let y = (self.ly as u16).wrapping_add(self.scy as u16);
-    // Background tile map have 32 tiles per row
-    let tile_row = (y / 8) % 32 * 32;
+  /// Sets the `OBP0` register directly.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn set_obp0(&mut self, value: u8) {
+    self.obp0 = value;
+  }
 
-    for (x, pixel) in scanline.iter_mut().enumerate() {
-      let x_pos = (x as u16).wrapping_add(self.scx as u16);
-      let tile_col = (x_pos / 8) % 32;
-      let tile_index = self.read_ram(bg_tile_map + tile_row + tile_col);
-      let raw_pixel = self.get_tile_pixel(tile_index, (y % 8) as u8, (x_pos % 8) as u8);
+  /// Sets the `OBP1` register directly.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn set_obp1(&mut self, value: u8) {
+    self.obp1 = value;
+  }
 
-      *pixel = (self.bgp >> (raw_pixel * 2)) & 0x03;
-    }
+  /// Sets the `WX` register directly.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn set_wx(&mut self, value: u8) {
+    self.wx = value;
   }
 
-  /// Renders the window into the scanline.
-  fn render_window(&mut self, scanline: &mut [u8; 160]) {
-    // The window is only drawn on scanlines at or below the window Y-position
-    if self.ly < self.wy {
-      return;
-    }
+  /// Sets the `WY` register directly.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn set_wy(&mut self, value: u8) {
+    self.wy = value;
+  }
 
-    // Offset by -7 because thats where the window starts
-    let window_x = self.wx.saturating_sub(7);
+  /// Copies the bytes into VRAM, starting at `0x8000`.
+  ///
+  /// Panics if there are more bytes than fit in VRAM.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn load_vram(&mut self, bytes: &[u8]) {
+    self.memory[..bytes.len()].copy_from_slice(bytes);
+  }
 
-    if window_x >= 160 {
-      return;
-    }
+  /// Copies the bytes into OAM, starting at `0xFE00`.
+  ///
+  /// Panics if there are more bytes than fit in OAM.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn load_oam(&mut self, bytes: &[u8]) {
+    self.oam[..bytes.len()].copy_from_slice(bytes);
+  }
 
-    let window_tile_map = if is_flag_set!(self.lcdc, LcdControl::WindowTileMap as u8) {
-      0x9C00
-    } else {
-      0x9800
-    };
+  /// Renders the scanline with the current registers and memory, and returns it.
+  #[cfg(any(test, feature = "testing"))]
+  pub fn render_line(&mut self, ly: u8) -> [u8; 160] {
+    self.render_whole_line(ly);
 
-    let window_y = self.wly as u16;
-    // Window tile map have 32 tiles per row
-    let tile_row = (window_y / 8) * 32;
+    self.back_buffer[ly as usize]
+  }
 
-    // Render the rest of the tiles on this scanline
-    for x in window_x..160 {
-      let window_x = (x - window_x) as u16;
-      let tile_col = window_x / 8;
-      let tile_index = self.read_ram(window_tile_map + tile_row + tile_col);
-      let raw_pixel = self.get_tile_pixel(tile_index, (window_y % 8) as u8, (window_x % 8) as u8);
+  /// Runs the whole pixel transfer of the scanline at once, into the back buffers.
+  fn render_whole_line(&mut self, ly: u8) {
+    self.ly = ly;
+    self.wy_triggered |= ly == self.wy;
+    self.start_pixel_transfer();
 
-      scanline[x as usize] = (self.bgp >> (raw_pixel * 2)) & 0x03;
+    while !self.pixel_transfer.is_done() {
+      self.step_pixel_transfer();
     }
 
-    // The window's internal counter is only incremented after window rendering
-    self.wly = self.wly.wrapping_add(1);
+    if self.pixel_transfer.window_active() {
+      self.wly = self.wly.wrapping_add(1);
+    }
   }
 
-  /// Renders sprites into the scanline.
-  fn render_sprites(&mut self, scanline: &mut [u8; 160]) {
-    // The Gameboy can only draw 10 sprites per scanline.
-    const MAX_SCANLINE_SPRITES: usize = 10;
+  /// Renders a whole frame from VRAM and presents it while the LCD is off, for debugging.
+  ///
+  /// This only touches the frame buffers, so `LY`, `STAT` and the interrupts keep behaving
+  /// like the LCD is off.
+  fn render_forced_frame(&mut self) {
+    self.wly = 0;
+    self.wy_triggered = false;
 
-    // Bit 2 determines the sprite's height
-    let sprite_height = if is_flag_set!(self.lcdc, LcdControl::SpriteDimensions as u8) {
-      16
-    } else {
-      8
-    };
+    for ly in 0..144 {
+      self.render_whole_line(ly);
+    }
 
-    let mut sprites = ArrayVec::<SpriteEntry, MAX_SCANLINE_SPRITES>::new();
+    *self.front_buffer = *self.back_buffer;
+    *self.front_color_buffer = *self.back_color_buffer;
 
-    // TODO: The PPU can be blocked from OAM during DMA transfers as well
-    for chunk in self.oam.chunks_exact(4) {
-      if sprites.len() >= MAX_SCANLINE_SPRITES {
-        break;
-      }
+    // Leave the PPU where turning the LCD off left it
+    self.ly = 0;
+    self.wly = 0;
+    self.wy_triggered = false;
+  }
 
-      // NOTE: We have to do this because `array_chunks` isn't stable. It's a bit ugly,
-      // but its looks better than iterating over the indices and offsetting into the OAM
-      let (raw_y, raw_x, tile_index, attributes) = match chunk {
-        &[a, b, c, d] => (a, b, c, d),
-        _ => unreachable!(),
-      };
-
-      // A Y-coordinate of 16 means the sprite is fully visible, so offset it by -16
-      // Wrapping subtraction is fine here, since we'll still be out of bounds
-      let sprite_y = raw_y.wrapping_sub(16);
-
-      // Ignore invisible sprites (those not within the bounds of the screen or not on
-      // the current scanline) and don't count it towards the sprite limit.
-      if raw_y == 0 || raw_y >= 160 || self.ly < sprite_y || self.ly >= sprite_y + sprite_height {
-        continue;
-      }
+  /// Moves to the scanline, delaying the `LY==LYC` comparison by an M-cycle.
+  fn set_ly(&mut self, ly: u8) {
+    self.ly = ly;
+    self.lyc_compare_delay = LYC_COMPARE_DELAY;
 
-      // Offset by -8 because a sprite is fully visibile at position 8
-      let sprite_x = raw_x.wrapping_sub(8);
-      let oam_index = sprites.len();
-
-      sprites.push(SpriteEntry {
-        x: sprite_x,
-        y: sprite_y,
-        tile_index,
-        attributes,
-        oam_position: oam_index as u8,
-      });
-    }
+    // The coincidence flag reads as clear until the comparison happens
+    remove_flag!(&mut self.stat, StatFlag::Coincidence as u8);
+  }
 
-    // Sort in reverse order, based on the position it was in OAM if the sprite's X-coordinates
-    // were equal, otherwise by the X-coordinates.
-    sprites.sort_by(|sprite_a, sprite_b| {
-      if sprite_a.x == sprite_b.x {
-        sprite_b.oam_position.cmp(&sprite_a.oam_position)
-      } else {
-        sprite_b.x.cmp(&sprite_a.x)
-      }
-    });
+  /// Updates the STAT interrupt line, which is high while any enabled STAT source is active.
+  /// The LCD interrupt is only requested when the line goes from low to high, so sources
+  /// that overlap only request one interrupt.
+  fn update_stat_line(&mut self, interrupts: &mut Interrupts) {
+    let mode_source = match self.current_mode() {
+      PpuMode::HBlank => Some(StatFlag::HBlankInterrupt),
+      PpuMode::VBlank => Some(StatFlag::VBlankInterrupt),
+      PpuMode::OamScan => Some(StatFlag::OamInterrupt),
+      PpuMode::PixelTransfer => None,
+    };
 
-    // Draw the 10 sprites
-    for sprite in &sprites {
-      // Get the row where the sprite should be drawn
-      let row = {
-        let line = self.ly.wrapping_sub(sprite.y);
+    let stat_line = self.display_enabled()
+      && (mode_source.is_some_and(|source| is_flag_set!(self.stat, source as u8))
+        || (is_flag_set!(self.stat, StatFlag::Coincidence as u8)
+          && is_flag_set!(self.stat, StatFlag::LycInterrupt as u8)));
 
-        if is_flag_set!(sprite.attributes, SpriteAttributes::YFlip as u8) {
-          sprite_height - 1 - line
-        } else {
-          line
-        }
-      };
+    if stat_line && !self.stat_line {
+      interrupts.request_interrupt(Interrupt::Lcd);
+    }
 
-      let (tile_to_use, tile_row) = if sprite_height == 16 {
-        // For 8×16 sprites, we need to clear the LSB of the top tile
-        // and set it for the bottom tile
-        let cleared_tile = sprite.tile_index & 0xFE;
+    self.stat_line = stat_line;
+  }
 
-        if row < 8 {
-          (cleared_tile, row)
-        } else {
-          (cleared_tile | 0x01, row - 8)
-        }
-      } else {
-        (sprite.tile_index, row)
-      };
+  /// Blanks the presented frame, which is what the LCD shows while it's off.
+  fn blank_screen(&mut self) {
+    *self.front_buffer = [[0; 160]; 144];
+    *self.front_color_buffer = [[WHITE; 160]; 144];
+  }
 
-      // Render the 8 pixels in each tile
-      for x_offset in 0..8 {
-        let screen_x = sprite.x.wrapping_add(x_offset);
+  /// Sets the mode of the PPU.
+  fn set_current_mode(&mut self, mode: PpuMode) {
+    // The 7th bit is unused and the lower 2 bits store the mode
+    self.stat = (self.stat & 0b0111_1100) | mode as u8;
+  }
 
-        // Don't draw sprites that are off the screen
-        if screen_x >= 160 {
-          continue;
-        }
+  /// Reads the 8-bit value at the provided address in the VRAM bank.
+  fn read_vram(&self, bank: u8, address: u16) -> u8 {
+    self.memory[vram_index(bank, address)]
+  }
 
-        let pixel = &mut scanline[screen_x as usize];
+  /// Returns the attributes of the background or window tile at the tile map address, which
+  /// are stored in VRAM bank 1 in CGB mode.
+  fn tile_attributes(&self, map_address: u16) -> u8 {
+    if self.cgb_mode {
+      self.read_vram(1, map_address)
+    } else {
+      0
+    }
+  }
 
-        // Don't draw over the background if the sprite has lower priority.
-        if is_flag_set!(sprite.attributes, SpriteAttributes::Priority as u8) && *pixel != 0 {
-          continue;
-        }
+  /// Returns the VRAM bank and address of a row (0-7 inclusive) of the background or window
+  /// tile, with the tile attributes.
+  fn tile_data_address(&self, tile_index: u8, attributes: u8, row: u8) -> (u8, u16) {
+    let (base_addr, tile_index) = if is_flag_set!(self.lcdc, LcdControl::BackgroundTileData as u8) {
+      (0x8000, tile_index)
+    } else {
+      // Tile indices in this region are signed, so we need to offset them accordingly.
+      //
+      // Tile indices [0, 128) reference sprites starting from 0x9000 and
+      // tile indices [128, 255) reference sprites starting from 0x8800.
+      if tile_index < 128 {
+        (0x9000, tile_index)
+      } else {
+        (0x8800, tile_index - 128)
+      }
+    };
 
-        let flip_x = is_flag_set!(sprite.attributes, SpriteAttributes::XFlip as u8);
-        let color = self.get_sprite_pixel(tile_to_use, tile_row, x_offset, flip_x);
+    let bank = is_flag_set!(attributes, TileAttributes::VramBank as u8) as u8;
+    let row = if is_flag_set!(attributes, TileAttributes::YFlip as u8) {
+      7 - row
+    } else {
+      row
+    };
 
-        if color == 0 {
-          continue;
-        }
+    // Tiles are stored in 16 bytes
+    (bank, base_addr + tile_index as u16 * 16 + row as u16 * 2)
+  }
 
-        let palette = if is_flag_set!(sprite.attributes, SpriteAttributes::DmgPalette as u8) {
-          self.obp1
-        } else {
-          self.obp0
-        };
+  /// Fetches a sprite's pixel from the given VRAM bank, tile index, row (0, 7 inclusive),
+  /// and X-coordinate (0-7 inclusive).
+  fn get_sprite_pixel(&self, bank: u8, tile_index: u8, row: u8, x: u8, flip_x: bool) -> u8 {
+    // Sprites are stored in 16 bytes
+    let tile_offset = tile_index as u16 * 16;
+    let row_offset = row as u16 * 2;
+    let lower = self.read_vram(bank, 0x8000 + tile_offset + row_offset);
+    let upper = self.read_vram(bank, 0x8000 + tile_offset + row_offset + 1);
+    let bit = if flip_x { x } else { 7 - x };
 
-        // Map the raw sprite color using the selected palette.
-        *pixel = (palette >> (color * 2)) & 0x03;
-      }
-    }
+    (((upper >> bit) & 1) << 1) | ((lower >> bit) & 1)
+  }
+}
+
+impl Default for Ppu {
+  fn default() -> Self {
+    Self::new()
   }
 }
 
@@ -605,8 +852,18 @@ impl RestartedDmaTransfer {
   }
 }
 
+/// The sprites that the OAM scan finds on a scanline, by their position in the OAM.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpriteSelection {
+  /// The first 10 sprites on the scanline in OAM order, which the PPU draws.
+  pub selected: ArrayVec<u8, MAX_SCANLINE_SPRITES>,
+  /// The sprites on the scanline past the limit of 10, which get dropped.
+  pub dropped: Vec<u8>,
+}
+
 /// A sprite entry from the OAM.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct SpriteEntry {
   /// The X-position of the sprite.
   pub x: u8,
@@ -650,6 +907,10 @@ pub enum PpuMode {
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
 enum SpriteAttributes {
+  /// The color palette for the sprite (CGB), in the lower 3 bits.
+  CgbPalette = 0b111,
+  /// The VRAM bank of the sprite's tile (CGB).
+  CgbVramBank = 1 << 3,
   /// The color palette for the sprite (DMG).
   DmgPalette = 1 << 4,
   /// Whether the sprite should be flipped vertically.
@@ -661,6 +922,22 @@ enum SpriteAttributes {
   Priority = 1 << 7,
 }
 
+/// Attributes of background and window tiles, which are stored in VRAM bank 1 (CGB).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+enum TileAttributes {
+  /// The color palette for the tile, in the lower 3 bits.
+  Palette = 0b111,
+  /// The VRAM bank of the tile's data.
+  VramBank = 1 << 3,
+  /// Whether the tile should be flipped horizontally.
+  XFlip = 1 << 5,
+  /// Whether the tile should be flipped vertically.
+  YFlip = 1 << 6,
+  /// Whether the tile's non-zero colors get drawn over sprites.
+  Priority = 1 << 7,
+}
+
 /// The LCD control byte.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
@@ -700,7 +977,79 @@ impl TryFrom<u8> for PpuMode {
   }
 }
 
-/// The amount of memory available to the PPU.
+/// Returns the index into the PPU's memory for the address in the VRAM bank.
+fn vram_index(bank: u8, address: u16) -> usize {
+  bank as usize * VIDEO_RAM_SIZE as usize + (address - 0x8000) as usize
+}
+
+/// Returns the RGB555 color of the palette in the CGB palette memory.
+fn palette_color(palettes: &[u8; CGB_PALETTES_SIZE], palette: u8, color: u8) -> u16 {
+  // Each palette has 4 little-endian colors
+  let index = palette as usize * 8 + color as usize * 2;
+
+  u16::from_le_bytes([palettes[index], palettes[index + 1]]) & 0x7FFF
+}
+
+/// Writes to the CGB palette memory at the index in the palette specification, which gets
+/// incremented afterwards if auto-increment is enabled.
+///
+/// The index still gets incremented when the palettes can't be accessed.
+fn write_palette_data(
+  specification: &mut u8,
+  palettes: &mut [u8; CGB_PALETTES_SIZE],
+  value: u8,
+  can_access: bool,
+) {
+  let index = *specification & PALETTE_INDEX_MASK;
+
+  if can_access {
+    palettes[index as usize] = value;
+  }
+
+  if is_flag_set!(*specification, PALETTE_AUTO_INCREMENT) {
+    *specification = PALETTE_AUTO_INCREMENT | (index.wrapping_add(1) & PALETTE_INDEX_MASK);
+  }
+}
+
+/// Returns an empty frame buffer, for the buffers that save states leave out.
+#[cfg(feature = "serde")]
+fn blank_frame() -> Box<Frame> {
+  Box::new([[0; 160]; 144])
+}
+
+/// Returns an empty color frame buffer, for the buffers that save states leave out.
+#[cfg(feature = "serde")]
+fn blank_color_frame() -> Box<ColorFrame> {
+  Box::new([[WHITE; 160]; 144])
+}
+
+/// The starting value of the 64-bit FNV-1a hash.
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+/// The prime that the 64-bit FNV-1a hash multiplies by.
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+/// The amount of memory in each VRAM bank.
 const VIDEO_RAM_SIZE: u16 = 0x2000;
 /// The amount of memory available for the sprites.
 const OAM_SIZE: u16 = 0xA0;
+/// The number of T-cycles it takes a DMA transfer to copy a byte.
+const DMA_CYCLES_PER_BYTE: u16 = 4;
+/// The number of T-cycles after `LY` changes before it gets compared against `LYC`.
+const LYC_COMPARE_DELAY: u8 = 4;
+/// The number of T-cycles in a frame.
+const FRAME_CYCLES: usize = 70224;
+/// The number of T-cycles that the first scanline is shorter by after the LCD is turned on.
+const LCD_ENABLE_SKIPPED_CYCLES: usize = 4;
+/// The number of T-cycles that `LY` reads as 153 for, at the start of the last scanline.
+const LINE_153_CYCLES: usize = 4;
+/// The number of T-cycles that the pixel transfer and HBlank take together.
+const SCANLINE_DRAWING_CYCLES: u16 = 376;
+/// The maximum number of sprites that can be drawn on a scanline.
+const MAX_SCANLINE_SPRITES: usize = 10;
+/// The size of the CGB background or object palette memory.
+const CGB_PALETTES_SIZE: usize = 64;
+/// The bit mask for the palette specification registers for auto-incrementing the index.
+const PALETTE_AUTO_INCREMENT: u8 = 0x80;
+/// The bit mask for the palette specification registers for the index.
+const PALETTE_INDEX_MASK: u8 = 0x3F;
+/// White in RGB555.
+const WHITE: u16 = 0x7FFF;
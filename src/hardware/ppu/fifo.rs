@@ -0,0 +1,584 @@
+use arrayvec::ArrayVec;
+
+use super::{
+  LcdControl, MAX_SCANLINE_SPRITES, Ppu, SpriteAttributes, SpriteEntry, TileAttributes, WHITE,
+  palette_color,
+};
+use crate::flags::is_flag_set;
+
+/// The state of the pixel transfer on the current scanline, where the pixel fetcher fills the
+/// background FIFO and the FIFOs get shifted out to the LCD, a pixel per dot.
+///
+/// Registers are read when the fetcher or the LCD uses them, so writes partway through a
+/// scanline affect the rest of it, like on hardware.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(super) struct PixelTransfer {
+  /// The X-coordinate of the next pixel that gets pushed to the LCD.
+  lx: u8,
+  /// The number of dots since the pixel transfer started.
+  dots: u16,
+  /// The number of dots left before the fetcher starts, for the first tile fetch that gets
+  /// thrown away.
+  startup_dots: u8,
+  /// The number of background pixels left to discard for the fine scrolling of `SCX`.
+  discard: u8,
+  /// Whether the fetcher switched to fetching the window on this scanline.
+  window_active: bool,
+  /// The background and window pixels that are waiting to be pushed to the LCD.
+  bg_fifo: Fifo<BgPixel>,
+  /// The sprite pixels that get mixed with the next background pixels. Transparent pixels
+  /// mean that there's no sprite.
+  sprite_fifo: [SpritePixel; 8],
+  /// The background and window fetcher.
+  fetcher: Fetcher,
+  /// The sprites on this scanline that haven't been fetched yet, from left to right.
+  sprites: ArrayVec<SpriteEntry, MAX_SCANLINE_SPRITES>,
+  /// The number of dots left in the running sprite fetch, which pauses the fetcher and the
+  /// LCD.
+  sprite_fetch_dots: u8,
+}
+
+/// A fixed-capacity FIFO of the pixels of a tile row.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Fifo<T> {
+  /// The pixels, where the first `len` are in the FIFO, oldest first.
+  pixels: [T; 8],
+  /// The number of pixels in the FIFO.
+  len: u8,
+}
+
+/// A background or window pixel.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct BgPixel {
+  /// The color index, between 0 and 3.
+  color: u8,
+  /// The attributes of the tile, in CGB mode.
+  attributes: u8,
+}
+
+/// A sprite pixel.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct SpritePixel {
+  /// The color index, between 0 and 3, where 0 is transparent.
+  color: u8,
+  /// The attributes of the sprite.
+  attributes: u8,
+}
+
+/// The background and window pixel fetcher, which fetches a tile row every 6 dots and
+/// pushes it once the background FIFO is empty.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Fetcher {
+  /// The step that the fetcher is on.
+  step: FetcherStep,
+  /// The number of dots spent on the current step.
+  ticks: u8,
+  /// The tile column to fetch next, relative to the start of the background or window.
+  tile_x: u8,
+  /// The fetched tile map address.
+  map_address: u16,
+  /// The fetched tile index.
+  tile_index: u8,
+  /// The fetched tile attributes, in CGB mode.
+  attributes: u8,
+  /// The fetched lower bit plane of the tile row.
+  low: u8,
+  /// The fetched upper bit plane of the tile row.
+  high: u8,
+}
+
+/// The steps of the [`Fetcher`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum FetcherStep {
+  /// Fetches the tile index from the tile map.
+  Tile,
+  /// Fetches the lower bit plane of the tile row.
+  DataLow,
+  /// Fetches the upper bit plane of the tile row.
+  DataHigh,
+  /// Waits for the background FIFO to be empty and pushes the tile row into it.
+  Push,
+}
+
+impl PixelTransfer {
+  /// Creates a new [`PixelTransfer`] for a scanline with the sprites on it, discarding the
+  /// first `discard` background pixels.
+  pub(super) fn new(sprites: ArrayVec<SpriteEntry, MAX_SCANLINE_SPRITES>, discard: u8) -> Self {
+    Self {
+      lx: 0,
+      dots: 0,
+      startup_dots: FETCHER_STARTUP_DOTS,
+      discard,
+      window_active: false,
+      bg_fifo: Fifo::default(),
+      sprite_fifo: [SpritePixel::default(); 8],
+      fetcher: Fetcher::new(),
+      sprites,
+      sprite_fetch_dots: 0,
+    }
+  }
+
+  /// Returns whether every pixel of the scanline was pushed to the LCD.
+  pub(super) fn is_done(&self) -> bool {
+    self.lx as usize == SCREEN_WIDTH
+  }
+
+  /// Returns the number of dots that the pixel transfer took so far.
+  pub(super) fn dots(&self) -> u16 {
+    self.dots
+  }
+
+  /// Returns whether the window was drawn on this scanline.
+  pub(super) fn window_active(&self) -> bool {
+    self.window_active
+  }
+}
+
+impl<T: Copy + Default> Fifo<T> {
+  /// Returns whether the FIFO is empty.
+  fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Fills the empty FIFO with a row of pixels.
+  fn push_row(&mut self, row: [T; 8]) {
+    self.pixels = row;
+    self.len = 8;
+  }
+
+  /// Removes the oldest pixel from the FIFO.
+  fn pop(&mut self) -> Option<T> {
+    if self.is_empty() {
+      return None;
+    }
+
+    let pixel = self.pixels[0];
+
+    self.pixels.copy_within(1.., 0);
+    self.len -= 1;
+
+    Some(pixel)
+  }
+
+  /// Empties the FIFO.
+  fn clear(&mut self) {
+    self.len = 0;
+  }
+}
+
+impl Fetcher {
+  /// Creates a new [`Fetcher`] that starts at the first tile column.
+  fn new() -> Self {
+    Self {
+      step: FetcherStep::Tile,
+      ticks: 0,
+      tile_x: 0,
+      map_address: 0,
+      tile_index: 0,
+      attributes: 0,
+      low: 0,
+      high: 0,
+    }
+  }
+}
+
+impl Ppu {
+  /// Starts the pixel transfer of the current scanline, picking up to 10 sprites on it.
+  pub(super) fn start_pixel_transfer(&mut self) {
+    let sprites = self.scanline_sprites();
+
+    self.pixel_transfer = PixelTransfer::new(sprites, self.scx % 8);
+  }
+
+  /// Steps the pixel transfer by a dot, pushing at most a single pixel to the LCD.
+  pub(super) fn step_pixel_transfer(&mut self) {
+    if self.pixel_transfer.is_done() {
+      return;
+    }
+
+    self.pixel_transfer.dots += 1;
+
+    if self.pixel_transfer.startup_dots > 0 {
+      self.pixel_transfer.startup_dots -= 1;
+      return;
+    }
+
+    self.check_window_start();
+
+    if self.step_sprite_fetch() {
+      return;
+    }
+
+    self.step_fetcher();
+    self.shift_pixel();
+  }
+
+  /// Switches the fetcher to the window once the LCD reaches it.
+  fn check_window_start(&mut self) {
+    // Offset by -7 because thats where the window starts
+    let window_x = self.wx.saturating_sub(7);
+    let transfer = &mut self.pixel_transfer;
+
+    if transfer.window_active
+      || !is_flag_set!(self.lcdc, LcdControl::WindowDisplay as u8)
+      || !self.wy_triggered
+      || window_x as usize >= SCREEN_WIDTH
+      || transfer.lx < window_x
+    {
+      return;
+    }
+
+    // The background pixels that were already fetched are thrown away
+    transfer.window_active = true;
+    transfer.discard = 0;
+    transfer.bg_fifo.clear();
+    transfer.fetcher = Fetcher::new();
+  }
+
+  /// Fetches the next sprite once the LCD reaches it, returning whether a sprite is being
+  /// fetched, which pauses the fetcher and the LCD.
+  fn step_sprite_fetch(&mut self) -> bool {
+    let transfer = &mut self.pixel_transfer;
+
+    if transfer.sprite_fetch_dots == 0 {
+      // Sprites are fully visible at X-coordinate 8, so the sprite at the front is due when
+      // its left edge reaches the LCD, including sprites that are partially off the left edge.
+      let Some(sprite) = transfer.sprites.first() else {
+        return false;
+      };
+
+      if sprite.x.wrapping_add(8) > transfer.lx + 8 {
+        return false;
+      }
+
+      if !is_flag_set!(self.lcdc, LcdControl::SpriteDisplay as u8) {
+        transfer.sprites.remove(0);
+        return false;
+      }
+
+      transfer.sprite_fetch_dots = SPRITE_FETCH_DOTS;
+    }
+
+    transfer.sprite_fetch_dots -= 1;
+
+    if transfer.sprite_fetch_dots == 0 {
+      let sprite = transfer.sprites.remove(0);
+
+      self.merge_sprite(&sprite);
+    }
+
+    true
+  }
+
+  /// Mixes the sprite's tile row into the sprite FIFO, below the sprites that are already
+  /// in it.
+  fn merge_sprite(&mut self, sprite: &SpriteEntry) {
+    let sprite_height = self.sprite_height();
+
+    // Get the row where the sprite should be drawn
+    let row = {
+      let line = self.ly.wrapping_sub(sprite.y);
+
+      if is_flag_set!(sprite.attributes, SpriteAttributes::YFlip as u8) {
+        sprite_height - 1 - line
+      } else {
+        line
+      }
+    };
+
+    let (tile_to_use, tile_row) = if sprite_height == 16 {
+      // For 8×16 sprites, we need to clear the LSB of the top tile
+      // and set it for the bottom tile
+      let cleared_tile = sprite.tile_index & 0xFE;
+
+      if row < 8 {
+        (cleared_tile, row)
+      } else {
+        (cleared_tile | 0x01, row - 8)
+      }
+    } else {
+      (sprite.tile_index, row)
+    };
+
+    let flip_x = is_flag_set!(sprite.attributes, SpriteAttributes::XFlip as u8);
+    let bank =
+      (self.cgb_mode && is_flag_set!(sprite.attributes, SpriteAttributes::CgbVramBank as u8)) as u8;
+    // Skip the pixels that are off the left edge of the LCD
+    let hidden = self
+      .pixel_transfer
+      .lx
+      .wrapping_add(8)
+      .wrapping_sub(sprite.x.wrapping_add(8));
+
+    for x_offset in hidden..8 {
+      let color = self.get_sprite_pixel(bank, tile_to_use, tile_row, x_offset, flip_x);
+      let slot = &mut self.pixel_transfer.sprite_fifo[(x_offset - hidden) as usize];
+
+      // Sprites that were fetched earlier have priority over this one
+      if slot.color == 0 {
+        *slot = SpritePixel {
+          color,
+          attributes: sprite.attributes,
+        };
+      }
+    }
+  }
+
+  /// Steps the background and window fetcher by a dot.
+  fn step_fetcher(&mut self) {
+    let mut fetcher = self.pixel_transfer.fetcher;
+
+    fetcher.ticks += 1;
+
+    match fetcher.step {
+      // Every step except pushing takes 2 dots
+      FetcherStep::Tile | FetcherStep::DataLow | FetcherStep::DataHigh if fetcher.ticks < 2 => {}
+      FetcherStep::Tile => {
+        fetcher.map_address = self.fetcher_map_address(fetcher.tile_x);
+        fetcher.tile_index = self.read_vram(0, fetcher.map_address);
+        fetcher.attributes = self.tile_attributes(fetcher.map_address);
+        fetcher.step = FetcherStep::DataLow;
+        fetcher.ticks = 0;
+      }
+      FetcherStep::DataLow => {
+        let (bank, address) = self.fetcher_data_address(&fetcher);
+
+        fetcher.low = self.read_vram(bank, address);
+        fetcher.step = FetcherStep::DataHigh;
+        fetcher.ticks = 0;
+      }
+      FetcherStep::DataHigh => {
+        let (bank, address) = self.fetcher_data_address(&fetcher);
+
+        fetcher.high = self.read_vram(bank, address + 1);
+        fetcher.step = FetcherStep::Push;
+        fetcher.ticks = 0;
+      }
+      FetcherStep::Push => {
+        if self.pixel_transfer.bg_fifo.is_empty() {
+          let flip_x = is_flag_set!(fetcher.attributes, TileAttributes::XFlip as u8);
+          let row = std::array::from_fn(|x| {
+            let bit = if flip_x { x } else { 7 - x };
+
+            BgPixel {
+              color: (((fetcher.high >> bit) & 1) << 1) | ((fetcher.low >> bit) & 1),
+              attributes: fetcher.attributes,
+            }
+          });
+
+          self.pixel_transfer.bg_fifo.push_row(row);
+          fetcher.tile_x = fetcher.tile_x.wrapping_add(1);
+          fetcher.step = FetcherStep::Tile;
+          fetcher.ticks = 0;
+        }
+      }
+    }
+
+    self.pixel_transfer.fetcher = fetcher;
+  }
+
+  /// Returns the tile map address of the tile column that the fetcher is on.
+  fn fetcher_map_address(&self, tile_x: u8) -> u16 {
+    if self.pixel_transfer.window_active {
+      let window_tile_map = if is_flag_set!(self.lcdc, LcdControl::WindowTileMap as u8) {
+        0x9C00
+      } else {
+        0x9800
+      };
+
+      // Window tile maps have 32 tiles per row
+      window_tile_map + (self.wly / 8) as u16 * 32 + (tile_x & 31) as u16
+    } else {
+      let bg_tile_map = if is_flag_set!(self.lcdc, LcdControl::BackgroundTileMap as u8) {
+        0x9C00
+      } else {
+        0x9800
+      };
+
+      // The background is 256x256 pixels and wraps around, so the coordinates wrap at 256
+      let y = self.ly.wrapping_add(self.scy);
+      let tile_col = (self.scx / 8).wrapping_add(tile_x) & 31;
+
+      bg_tile_map + (y / 8) as u16 * 32 + tile_col as u16
+    }
+  }
+
+  /// Returns the VRAM bank and address of the tile row that the fetcher is on.
+  fn fetcher_data_address(&self, fetcher: &Fetcher) -> (u8, u16) {
+    let row = if self.pixel_transfer.window_active {
+      self.wly % 8
+    } else {
+      self.ly.wrapping_add(self.scy) % 8
+    };
+
+    self.tile_data_address(fetcher.tile_index, fetcher.attributes, row)
+  }
+
+  /// Pushes the next pixel to the LCD, if there is one, mixing the background and sprite
+  /// pixels.
+  fn shift_pixel(&mut self) {
+    let transfer = &mut self.pixel_transfer;
+
+    let Some(bg_pixel) = transfer.bg_fifo.pop() else {
+      return;
+    };
+
+    if transfer.discard > 0 {
+      transfer.discard -= 1;
+      return;
+    }
+
+    let sprite_pixel = transfer.sprite_fifo[0];
+
+    transfer.sprite_fifo.copy_within(1.., 0);
+    transfer.sprite_fifo[7] = SpritePixel::default();
+
+    let x = transfer.lx as usize;
+
+    transfer.lx += 1;
+
+    // Only draw if the LCD is enabled, unless rendering is forced for debugging.
+    if !self.display_enabled() && !self.force_render {
+      return;
+    }
+
+    let (shade, color) = self.mix_pixel(bg_pixel, sprite_pixel);
+
+    self.back_buffer[self.ly as usize][x] = shade;
+
+    if self.cgb_mode {
+      self.back_color_buffer[self.ly as usize][x] = color;
+    }
+  }
+
+  /// Mixes a background and a sprite pixel into a shade and an RGB555 color, with the
+  /// palettes at this point in the scanline.
+  fn mix_pixel(&self, bg_pixel: BgPixel, sprite_pixel: SpritePixel) -> (u8, u16) {
+    let bg_enabled = is_flag_set!(self.lcdc, LcdControl::BackgroundDisplay as u8);
+
+    // In CGB mode, the background is always drawn, and the background display bit instead
+    // decides whether the background can be drawn over sprites
+    let (bg_index, bg_shade, bg_color) = if bg_enabled || self.cgb_mode {
+      (
+        bg_pixel.color,
+        (self.bgp >> (bg_pixel.color * 2)) & 0x03,
+        palette_color(
+          &self.bg_palettes,
+          bg_pixel.attributes & TileAttributes::Palette as u8,
+          bg_pixel.color,
+        ),
+      )
+    } else {
+      (0, 0, WHITE)
+    };
+
+    let bg_has_priority = bg_enabled
+      && (is_flag_set!(sprite_pixel.attributes, SpriteAttributes::Priority as u8)
+        || (self.cgb_mode && is_flag_set!(bg_pixel.attributes, TileAttributes::Priority as u8)));
+
+    // Sprites only get drawn behind the background over its color 0, regardless of the
+    // shade that the palette maps it to
+    let sprite_hidden = sprite_pixel.color == 0
+      || !is_flag_set!(self.lcdc, LcdControl::SpriteDisplay as u8)
+      || (bg_has_priority && bg_index != 0);
+
+    if sprite_hidden {
+      return (bg_shade, bg_color);
+    }
+
+    let palette = if is_flag_set!(sprite_pixel.attributes, SpriteAttributes::DmgPalette as u8) {
+      self.obp1
+    } else {
+      self.obp0
+    };
+
+    (
+      (palette >> (sprite_pixel.color * 2)) & 0x03,
+      palette_color(
+        &self.obj_palettes,
+        sprite_pixel.attributes & SpriteAttributes::CgbPalette as u8,
+        sprite_pixel.color,
+      ),
+    )
+  }
+
+  /// Returns the sprites on the current scanline, up to the first 10 in OAM, in the order
+  /// that they get drawn from left to right.
+  fn scanline_sprites(&self) -> ArrayVec<SpriteEntry, MAX_SCANLINE_SPRITES> {
+    let mut sprites = self
+      .sprites_on_line(self.ly)
+      .take(MAX_SCANLINE_SPRITES)
+      .collect::<ArrayVec<_, MAX_SCANLINE_SPRITES>>();
+
+    // Sprites with an X-coordinate of 0 are off the screen, but still count towards the limit
+    sprites.retain(|sprite| sprite.x.wrapping_add(8) != 0);
+
+    // Sprites further left get drawn over the ones to their right, and sprites that come
+    // first in the OAM win ties.
+    sprites.sort_by_key(|sprite| (sprite.x.wrapping_add(8), sprite.oam_position));
+
+    sprites
+  }
+
+  /// Returns every sprite that overlaps the scanline in OAM order, without the limit of 10.
+  pub(super) fn sprites_on_line(&self, ly: u8) -> impl Iterator<Item = SpriteEntry> + '_ {
+    let sprite_height = self.sprite_height();
+
+    // TODO: The PPU can be blocked from OAM during DMA transfers as well
+    self
+      .oam
+      .chunks_exact(4)
+      .enumerate()
+      .filter_map(move |(oam_position, chunk)| {
+        // NOTE: We have to do this because `array_chunks` isn't stable. It's a bit ugly,
+        // but its looks better than iterating over the indices and offsetting into the OAM
+        let (raw_y, raw_x, tile_index, attributes) = match chunk {
+          &[a, b, c, d] => (a, b, c, d),
+          _ => unreachable!(),
+        };
+
+        // A Y-coordinate of 16 means the sprite is fully visible, so offset it by -16
+        // Wrapping subtraction is fine here, since we'll still be out of bounds
+        let sprite_y = raw_y.wrapping_sub(16);
+
+        // Ignore invisible sprites (those not within the bounds of the screen or not on
+        // the current scanline) and don't count it towards the sprite limit.
+        if raw_y == 0 || raw_y >= 160 || ly < sprite_y || ly >= sprite_y + sprite_height {
+          return None;
+        }
+
+        Some(SpriteEntry {
+          // Offset by -8 because a sprite is fully visibile at position 8
+          x: raw_x.wrapping_sub(8),
+          y: sprite_y,
+          tile_index,
+          attributes,
+          oam_position: oam_position as u8,
+        })
+      })
+  }
+
+  /// Returns the height of the sprites, which is 8 or 16 pixels.
+  fn sprite_height(&self) -> u8 {
+    // Bit 2 determines the sprite's height
+    if is_flag_set!(self.lcdc, LcdControl::SpriteDimensions as u8) {
+      16
+    } else {
+      8
+    }
+  }
+}
+
+/// The width of the LCD in pixels.
+const SCREEN_WIDTH: usize = 160;
+/// The number of dots that the first tile fetch of a scanline takes before it gets thrown
+/// away.
+const FETCHER_STARTUP_DOTS: u8 = 6;
+/// The number of dots that fetching a sprite pauses the pixel transfer for.
+const SPRITE_FETCH_DOTS: u8 = 6;
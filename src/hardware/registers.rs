@@ -28,6 +28,7 @@ pub const REGISTER_M: u8 = 0b110;
 
 /// The status of the registers.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Registers {
   /// The `A` register.
   pub a: u8,
@@ -0,0 +1,409 @@
+use std::{
+  fmt,
+  io::{self, Read, Write},
+  net::{TcpListener, TcpStream, ToSocketAddrs},
+  sync::mpsc::{self, Receiver, Sender, TryRecvError},
+  thread,
+  time::{Duration, Instant},
+};
+
+use crate::{
+  flags::{is_flag_set, remove_flag},
+  interrupts::{Interrupt, Interrupts},
+};
+
+/// The serial port, which is used for the link cable.
+///
+/// Without a [`SerialLink`], nothing is plugged into the port, so transfers read back `0xFF`.
+/// The last bytes that got sent are kept, since test ROMs print their results over serial.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Serial {
+  /// The serial transfer data (SB).
+  data: u8,
+  /// The serial transfer control (SC).
+  control: u8,
+  /// The number of T-cycles into the running transfer, if any.
+  transfer_ticks: Option<u16>,
+  /// The other side of the link cable, if connected.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  link: Option<Link>,
+  /// The last bytes that were sent over serial, up to [`SERIAL_OUTPUT_CAPACITY`].
+  #[cfg_attr(feature = "serde", serde(skip))]
+  output: Vec<u8>,
+  /// The number of bytes that were sent over serial, including the ones that were dropped
+  /// from the output.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  bytes_sent: usize,
+}
+
+/// The other side of a link cable, which exchanges a byte for each transfer.
+///
+/// The side that uses the internal clock starts transfers with [`SerialLink::send`] and waits
+/// for the answer with [`SerialLink::receive`], while the side that waits on the external clock
+/// checks for them with [`SerialLink::poll`]. None of these should block.
+pub trait SerialLink {
+  /// Sends a byte to the other side, starting a transfer.
+  fn send(&mut self, out: u8);
+
+  /// Returns the byte that the other side answered the last sent byte with, once it did.
+  fn receive(&mut self) -> Option<u8>;
+
+  /// Checks whether the other side started a transfer, answering with `out` and returning
+  /// the byte that it sent, if it did.
+  fn poll(&mut self, out: u8) -> Option<u8> {
+    let _ = out;
+
+    None
+  }
+}
+
+/// A [`SerialLink`] to another emulator over TCP.
+///
+/// The socket is read and written on worker threads, so the emulator never waits on the
+/// network. Each transfer is tagged with a sequence number, and an answer to a transfer that
+/// was already given up on is dropped. A transfer that isn't answered in time, or that fails,
+/// reads back `0xFF` as if the cable was unplugged.
+#[derive(Debug)]
+pub struct TcpLink {
+  /// The messages for the writer thread to send to the other emulator.
+  outgoing: Sender<LinkMessage>,
+  /// The transfers that the other side started.
+  requests: Receiver<LinkMessage>,
+  /// The answers to the transfers that this side started.
+  replies: Receiver<LinkMessage>,
+  /// The sequence number of the last transfer that this side started.
+  sequence: u8,
+  /// When the running transfer was started, if it wasn't answered yet.
+  sent_at: Option<Instant>,
+}
+
+/// A message between two [`TcpLink`]s, which is sent as 3 bytes.
+#[derive(Debug, Clone, Copy)]
+struct LinkMessage {
+  /// Whether the message starts a transfer or answers one.
+  kind: LinkMessageKind,
+  /// The sequence number of the transfer.
+  sequence: u8,
+  /// The byte that gets transferred.
+  byte: u8,
+}
+
+/// The kind of a [`LinkMessage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkMessageKind {
+  /// Starts a transfer from the side that uses the internal clock.
+  Request,
+  /// Answers a transfer from the side that uses the external clock.
+  Reply,
+}
+
+/// The [`SerialLink`] that's connected to the serial port.
+struct Link(Box<dyn SerialLink>);
+
+impl fmt::Debug for Link {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("Link")
+  }
+}
+
+impl Serial {
+  /// Creates a new [`Serial`].
+  pub fn new() -> Self {
+    Self {
+      data: 0,
+      control: 0,
+      transfer_ticks: None,
+      link: None,
+      output: Vec::new(),
+      bytes_sent: 0,
+    }
+  }
+
+  /// Steps the serial port by a T-cycle.
+  pub fn step(&mut self, interrupts: &mut Interrupts) {
+    let Some(ticks) = &mut self.transfer_ticks else {
+      return;
+    };
+
+    *ticks += 1;
+
+    if *ticks % CYCLES_PER_BIT != 0 {
+      return;
+    }
+
+    if !is_flag_set!(self.control, CLOCK_SELECT_MASK) {
+      // The other side provides the clock, so check on it at the rate it would clock bits in
+      *ticks = 0;
+
+      let received = self
+        .link
+        .as_mut()
+        .and_then(|Link(link)| link.poll(self.data));
+
+      if let Some(byte) = received {
+        self.data = byte;
+        self.finish_transfer(interrupts);
+      }
+
+      return;
+    }
+
+    if *ticks <= CYCLES_PER_BIT * 8 {
+      // Shift out the top bit and shift in a 1, until the other side's byte arrives
+      self.data = (self.data << 1) | 0x01;
+    }
+
+    if *ticks < CYCLES_PER_BIT * 8 {
+      return;
+    }
+
+    // Keep checking for the other side's answer at the bit rate, after all 8 bits are out
+    *ticks = CYCLES_PER_BIT * 8;
+
+    let received = match &mut self.link {
+      Some(Link(link)) => link.receive(),
+      None => Some(0xFF),
+    };
+
+    if let Some(byte) = received {
+      self.data = byte;
+      self.finish_transfer(interrupts);
+    }
+  }
+
+  /// Reads from the serial port's registers.
+  pub fn read_register(&self, address: u16) -> u8 {
+    match address {
+      0xFF01 => self.data,
+      // The unused bits always read 1
+      0xFF02 => self.control | 0x7E,
+      _ => unreachable!(),
+    }
+  }
+
+  /// Writes to the serial port's registers.
+  pub fn write_register(&mut self, address: u16, value: u8) {
+    match address {
+      0xFF01 => self.data = value,
+      0xFF02 => {
+        self.control = value & (TRANSFER_ENABLE_MASK | CLOCK_SELECT_MASK);
+
+        if !is_flag_set!(self.control, TRANSFER_ENABLE_MASK) {
+          self.transfer_ticks = None;
+          return;
+        }
+
+        self.transfer_ticks = Some(0);
+
+        // Transfers using the internal clock start right away, while transfers using an
+        // external clock wait for the other side to start them
+        if is_flag_set!(self.control, CLOCK_SELECT_MASK) {
+          self.push_output(self.data);
+
+          if let Some(Link(link)) = &mut self.link {
+            link.send(self.data);
+          }
+        }
+      }
+      _ => unreachable!(),
+    }
+  }
+
+  /// Connects the other side of the link cable, replacing the previous one.
+  pub fn connect(&mut self, link: Box<dyn SerialLink>) {
+    self.link = Some(Link(link));
+  }
+
+  /// Disconnects the link cable, returning the other side, if it was connected.
+  pub fn disconnect(&mut self) -> Option<Box<dyn SerialLink>> {
+    self.link.take().map(|Link(link)| link)
+  }
+
+  /// Returns the last bytes that were sent over serial.
+  pub fn output(&self) -> &[u8] {
+    &self.output
+  }
+
+  /// Returns the number of bytes that were sent over serial, including the ones that were
+  /// dropped from the output.
+  pub fn bytes_sent(&self) -> usize {
+    self.bytes_sent
+  }
+
+  /// Keeps a byte that was sent over serial, dropping the oldest half of the output once it's
+  /// full.
+  fn push_output(&mut self, byte: u8) {
+    if self.output.len() == SERIAL_OUTPUT_CAPACITY {
+      self.output.drain(..SERIAL_OUTPUT_CAPACITY / 2);
+    }
+
+    self.output.push(byte);
+    self.bytes_sent += 1;
+  }
+
+  /// Ends the running transfer and requests the serial interrupt.
+  fn finish_transfer(&mut self, interrupts: &mut Interrupts) {
+    remove_flag!(&mut self.control, TRANSFER_ENABLE_MASK);
+    self.transfer_ticks = None;
+
+    interrupts.request_interrupt(Interrupt::Serial);
+  }
+}
+
+impl Default for Serial {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl TcpLink {
+  /// Connects to another emulator that's listening at the address.
+  pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+    Self::with_stream(TcpStream::connect(address)?)
+  }
+
+  /// Waits for another emulator to connect at the address.
+  pub fn listen(address: impl ToSocketAddrs) -> io::Result<Self> {
+    let (stream, _) = TcpListener::bind(address)?.accept()?;
+
+    Self::with_stream(stream)
+  }
+
+  /// Creates a new [`TcpLink`] over the connected stream, with a thread that reads from it and
+  /// a thread that writes to it.
+  fn with_stream(stream: TcpStream) -> io::Result<Self> {
+    stream.set_nodelay(true)?;
+
+    let mut reader = stream.try_clone()?;
+    let mut writer = stream;
+    let (outgoing, outgoing_receiver) = mpsc::channel::<LinkMessage>();
+    let (requests_sender, requests) = mpsc::channel();
+    let (replies_sender, replies) = mpsc::channel();
+
+    // Both threads stop once the connection or the link is closed
+    thread::spawn(move || {
+      let mut bytes = [0; 3];
+
+      while reader.read_exact(&mut bytes).is_ok() {
+        let Some(message) = LinkMessage::from_bytes(bytes) else {
+          break;
+        };
+        let sender = match message.kind {
+          LinkMessageKind::Request => &requests_sender,
+          LinkMessageKind::Reply => &replies_sender,
+        };
+
+        if sender.send(message).is_err() {
+          break;
+        }
+      }
+    });
+    thread::spawn(move || {
+      for message in outgoing_receiver {
+        if writer.write_all(&message.to_bytes()).is_err() {
+          break;
+        }
+      }
+    });
+
+    Ok(Self {
+      outgoing,
+      requests,
+      replies,
+      sequence: 0,
+      sent_at: None,
+    })
+  }
+}
+
+impl SerialLink for TcpLink {
+  fn send(&mut self, out: u8) {
+    self.sequence = self.sequence.wrapping_add(1);
+    self.sent_at = Some(Instant::now());
+
+    let _ = self.outgoing.send(LinkMessage {
+      kind: LinkMessageKind::Request,
+      sequence: self.sequence,
+      byte: out,
+    });
+  }
+
+  fn receive(&mut self) -> Option<u8> {
+    let sent_at = self.sent_at?;
+
+    loop {
+      match self.replies.try_recv() {
+        // Answers to transfers that timed out are dropped
+        Ok(reply) if reply.sequence != self.sequence => continue,
+        Ok(reply) => {
+          self.sent_at = None;
+
+          return Some(reply.byte);
+        }
+        Err(TryRecvError::Empty) if sent_at.elapsed() < TCP_LINK_TIMEOUT => return None,
+        Err(_) => {
+          self.sent_at = None;
+
+          return Some(0xFF);
+        }
+      }
+    }
+  }
+
+  fn poll(&mut self, out: u8) -> Option<u8> {
+    // Only the latest transfer is still waiting for an answer, since the other side gave up
+    // on the earlier ones
+    let request = self.requests.try_iter().last()?;
+
+    let _ = self.outgoing.send(LinkMessage {
+      kind: LinkMessageKind::Reply,
+      sequence: request.sequence,
+      byte: out,
+    });
+
+    Some(request.byte)
+  }
+}
+
+impl LinkMessage {
+  /// Parses a message from the bytes that were sent over TCP, if they're valid.
+  fn from_bytes([kind, sequence, byte]: [u8; 3]) -> Option<Self> {
+    let kind = match kind {
+      LINK_REQUEST => LinkMessageKind::Request,
+      LINK_REPLY => LinkMessageKind::Reply,
+      _ => return None,
+    };
+
+    Some(Self {
+      kind,
+      sequence,
+      byte,
+    })
+  }
+
+  /// Returns the bytes that get sent over TCP for the message.
+  fn to_bytes(self) -> [u8; 3] {
+    let kind = match self.kind {
+      LinkMessageKind::Request => LINK_REQUEST,
+      LinkMessageKind::Reply => LINK_REPLY,
+    };
+
+    [kind, self.sequence, self.byte]
+  }
+}
+
+/// The bit mask for the SC register for starting a transfer.
+const TRANSFER_ENABLE_MASK: u8 = 0x80;
+/// The bit mask for the SC register for using the internal clock.
+const CLOCK_SELECT_MASK: u8 = 0x01;
+/// The number of T-cycles it takes to shift a bit with the internal clock, at 8192 Hz.
+const CYCLES_PER_BIT: u16 = 512;
+/// The number of bytes that were sent over serial that are kept.
+const SERIAL_OUTPUT_CAPACITY: usize = 0x10000;
+/// How long a [`TcpLink`] waits for the other side to answer a transfer.
+const TCP_LINK_TIMEOUT: Duration = Duration::from_secs(1);
+/// The first byte of a [`LinkMessage`] that starts a transfer.
+const LINK_REQUEST: u8 = 0x01;
+/// The first byte of a [`LinkMessage`] that answers a transfer.
+const LINK_REPLY: u8 = 0x02;
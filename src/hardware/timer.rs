@@ -5,6 +5,7 @@ use crate::{
 };
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Timer {
   /// The timer counter register.
   tima: u8,
@@ -16,10 +17,13 @@ pub struct Timer {
   counter: u16,
   /// The timer interrupt.
   timer_interrupt: TimerInterrupt,
+  /// Whether DIV-APU ticked and the APU's frame sequencer hasn't been clocked by it yet.
+  div_apu_ticked: bool,
 }
 
 /// The timer interrupt.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum TimerInterrupt {
   /// There is currently no timer interrupt.
   None,
@@ -38,6 +42,7 @@ impl Timer {
       tac: 0,
       counter: 0xABCC,
       timer_interrupt: TimerInterrupt::None,
+      div_apu_ticked: false,
     }
   }
 
@@ -72,7 +77,7 @@ impl Timer {
       TCycle::T4 => {
         let prev_and_result = counter_and_result(self.counter, self.tac);
 
-        self.counter = self.counter.wrapping_add(1);
+        self.set_counter(self.counter.wrapping_add(1));
 
         let curr_and_result = counter_and_result(self.counter, self.tac);
 
@@ -102,7 +107,8 @@ impl Timer {
         let prev_and_result = counter_and_result(self.counter, self.tac);
         let curr_and_result = counter_and_result(0, self.tac);
 
-        self.counter = 0;
+        // Resetting DIV can also tick DIV-APU early
+        self.set_counter(0);
 
         if is_falling_edge!(prev_and_result, curr_and_result) {
           self.increment_tima();
@@ -142,6 +148,21 @@ impl Timer {
     }
   }
 
+  /// Returns whether DIV-APU ticked since the last time this was called, which clocks the
+  /// APU's frame sequencer.
+  pub fn take_div_apu_tick(&mut self) -> bool {
+    std::mem::take(&mut self.div_apu_ticked)
+  }
+
+  /// Sets the internal counter, ticking DIV-APU on a falling edge of bit 4 of DIV.
+  fn set_counter(&mut self, counter: u16) {
+    if is_falling_edge!(self.counter, counter, DIV_APU_MASK) {
+      self.div_apu_ticked = true;
+    }
+
+    self.counter = counter;
+  }
+
   /// Increments the TIMA register.
   fn increment_tima(&mut self) {
     self.tima = self.tima.wrapping_add(1);
@@ -159,6 +180,12 @@ impl Timer {
   }
 }
 
+impl Default for Timer {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 /// Gets the and result for the timer counter.
 const fn counter_and_result(counter: u16, tac: u8) -> bool {
   is_flag_set!(tac, TIMER_ENABLE_MASK) && is_flag_set!(counter, tac_bit_mask(tac))
@@ -177,7 +204,185 @@ const fn tac_bit_mask(tac: u8) -> u16 {
 
 /// The bit mask for the TAC register for checking if the timer is enabled.
 const TIMER_ENABLE_MASK: u8 = 0x04;
+/// The bit of the internal counter that clocks DIV-APU, which is bit 4 of DIV.
+const DIV_APU_MASK: u16 = 1 << 10;
 /// The number of T-cycles to wait before firing an interrupt.
 const TIMER_INTERRUPT_DELAY: u8 = 4;
 /// The number of T-cycles during which TIMA can be affected while reloading.
 const TIMER_TIMA_RELOAD_CYCLES: u8 = 4;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// The value of `TAC` that enables the timer and selects bit 1 of the internal counter, so
+  /// that TIMA increments every 4 M-cycles.
+  const FASTEST_TAC: u8 = 0b101;
+
+  /// Creates a [`Timer`] with the internal counter and `TAC` set directly, without the writes
+  /// to them clocking TIMA.
+  fn timer_with(counter: u16, tac: u8) -> Timer {
+    let mut timer = Timer::new();
+
+    timer.counter = counter;
+    timer.tac = tac;
+
+    timer
+  }
+
+  #[test]
+  fn disabling_timer_with_selected_bit_set_increments_tima() {
+    // Bit 1 of the counter is selected, and it's set
+    let mut timer = timer_with(0b10, 0b101);
+
+    timer.write_register(0xFF07, 0b001);
+
+    assert_eq!(timer.read_register(0xFF05), 1);
+  }
+
+  #[test]
+  fn changing_frequency_with_selected_bit_set_increments_tima() {
+    // Bit 1 is set, but bit 3, which gets selected instead, isn't
+    let mut timer = timer_with(0b10, 0b101);
+
+    timer.write_register(0xFF07, 0b110);
+
+    assert_eq!(timer.read_register(0xFF05), 1);
+
+    // Bits 3 and 5 are both set, so there's no falling edge
+    let mut timer = timer_with(0b10_1000, 0b110);
+
+    timer.write_register(0xFF07, 0b111);
+
+    assert_eq!(timer.read_register(0xFF05), 0);
+  }
+
+  #[test]
+  fn changing_tac_with_selected_bit_clear_leaves_tima() {
+    let mut timer = timer_with(0, 0b101);
+
+    timer.write_register(0xFF07, 0b001);
+    timer.write_register(0xFF07, 0b110);
+
+    assert_eq!(timer.read_register(0xFF05), 0);
+  }
+
+  #[test]
+  fn enabling_timer_with_selected_bit_set_leaves_tima() {
+    let mut timer = timer_with(0b10, 0b001);
+
+    timer.write_register(0xFF07, 0b101);
+
+    assert_eq!(timer.read_register(0xFF05), 0);
+  }
+
+  #[test]
+  fn writing_div_with_selected_bit_set_increments_tima() {
+    let mut timer = timer_with(0b10, 0b101);
+
+    timer.write_register(0xFF04, 0);
+
+    assert_eq!(timer.read_register(0xFF04), 0);
+    assert_eq!(timer.read_register(0xFF05), 1);
+  }
+
+  /// Creates a [`Timer`] whose TIMA overflows at the next M-cycle, with TMA set to `tma`.
+  fn timer_about_to_overflow(tma: u8) -> Timer {
+    // The next M-cycle clears bit 1 of the counter, which increments TIMA
+    let mut timer = timer_with(0b11, FASTEST_TAC);
+
+    timer.tima = 0xFF;
+    timer.tma = tma;
+
+    timer
+  }
+
+  /// Steps the timer by an M-cycle, starting at T4 like the rest of the hardware.
+  fn step_m_cycle(timer: &mut Timer, interrupts: &mut Interrupts, sys_clock: &mut SystemClock) {
+    for _ in 0..4 {
+      timer.step(interrupts, sys_clock);
+      sys_clock.increment_clock();
+    }
+  }
+
+  /// Returns whether the timer interrupt was requested.
+  fn timer_interrupt_requested(interrupts: &Interrupts) -> bool {
+    is_flag_set!(interrupts.requested_bitfield(), Interrupt::Timer as u8)
+  }
+
+  #[test]
+  fn tima_reads_0_for_an_m_cycle_before_reloading() {
+    let mut timer = timer_about_to_overflow(0x42);
+    let mut interrupts = Interrupts::new();
+    let mut sys_clock = SystemClock::new();
+
+    step_m_cycle(&mut timer, &mut interrupts, &mut sys_clock);
+
+    assert_eq!(timer.read_register(0xFF05), 0x00);
+    assert!(!timer_interrupt_requested(&interrupts));
+
+    step_m_cycle(&mut timer, &mut interrupts, &mut sys_clock);
+
+    assert_eq!(timer.read_register(0xFF05), 0x42);
+    assert!(timer_interrupt_requested(&interrupts));
+  }
+
+  #[test]
+  fn writing_tima_before_reload_cancels_it() {
+    let mut timer = timer_about_to_overflow(0x42);
+    let mut interrupts = Interrupts::new();
+    let mut sys_clock = SystemClock::new();
+
+    step_m_cycle(&mut timer, &mut interrupts, &mut sys_clock);
+    timer.write_register(0xFF05, 0x10);
+    step_m_cycle(&mut timer, &mut interrupts, &mut sys_clock);
+
+    assert_eq!(timer.read_register(0xFF05), 0x10);
+    assert!(!timer_interrupt_requested(&interrupts));
+  }
+
+  #[test]
+  fn writing_tima_while_reloading_is_ignored() {
+    let mut timer = timer_about_to_overflow(0x42);
+    let mut interrupts = Interrupts::new();
+    let mut sys_clock = SystemClock::new();
+
+    step_m_cycle(&mut timer, &mut interrupts, &mut sys_clock);
+    step_m_cycle(&mut timer, &mut interrupts, &mut sys_clock);
+    timer.write_register(0xFF05, 0x10);
+
+    assert_eq!(timer.read_register(0xFF05), 0x42);
+    assert!(timer_interrupt_requested(&interrupts));
+  }
+
+  #[test]
+  fn writing_tma_while_reloading_also_sets_tima() {
+    let mut timer = timer_about_to_overflow(0x42);
+    let mut interrupts = Interrupts::new();
+    let mut sys_clock = SystemClock::new();
+
+    step_m_cycle(&mut timer, &mut interrupts, &mut sys_clock);
+    step_m_cycle(&mut timer, &mut interrupts, &mut sys_clock);
+    timer.write_register(0xFF06, 0x24);
+
+    assert_eq!(timer.read_register(0xFF05), 0x24);
+    assert_eq!(timer.read_register(0xFF06), 0x24);
+  }
+
+  #[test]
+  fn writes_after_reload_behave_normally() {
+    let mut timer = timer_about_to_overflow(0x42);
+    let mut interrupts = Interrupts::new();
+    let mut sys_clock = SystemClock::new();
+
+    for _ in 0..3 {
+      step_m_cycle(&mut timer, &mut interrupts, &mut sys_clock);
+    }
+
+    timer.write_register(0xFF06, 0x24);
+    timer.write_register(0xFF05, 0x10);
+
+    assert_eq!(timer.read_register(0xFF05), 0x10);
+    assert_eq!(timer.read_register(0xFF06), 0x24);
+  }
+}
@@ -0,0 +1,102 @@
+use std::fmt;
+
+use crate::{
+  emulator::Emulator,
+  hardware::joypad::{Button, ButtonAction},
+};
+
+/// Button presses and releases at set frames, for playing a game without a window.
+///
+/// Each line of a script is a frame, a button and `press` or `release`, like `60 start press`,
+/// where the frame counts from 0. Blank lines and anything after a `#` are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct InputScript {
+  /// The inputs, ordered by the frame that they happen on.
+  inputs: Vec<ScriptedInput>,
+  /// The index of the next input to apply.
+  next_input: usize,
+}
+
+/// A button press or release in an [`InputScript`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptedInput {
+  /// The frame that the input happens at the start of.
+  pub frame: u32,
+  /// The button that gets pressed or released.
+  pub button: Button,
+  /// Whether the button gets pressed or released.
+  pub action: ButtonAction,
+}
+
+impl InputScript {
+  /// Parses an input script, failing on the first line that isn't a valid input.
+  pub fn parse(script: &str) -> Result<Self, InputScriptError> {
+    let mut inputs = Vec::new();
+
+    for (index, line) in script.lines().enumerate() {
+      let line = line.split('#').next().unwrap_or_default().trim();
+
+      if line.is_empty() {
+        continue;
+      }
+
+      let error = |message: &str| InputScriptError {
+        line: index + 1,
+        message: message.to_string(),
+      };
+      let [frame, button, action] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+        return Err(error("expected a frame, a button and `press` or `release`"));
+      };
+
+      inputs.push(ScriptedInput {
+        frame: frame
+          .parse()
+          .map_err(|_| error("expected a frame number"))?,
+        button: button.parse().map_err(|_| error("unknown button"))?,
+        action: match action {
+          "press" => ButtonAction::Pressed,
+          "release" => ButtonAction::Released,
+          _ => return Err(error("expected `press` or `release`")),
+        },
+      });
+    }
+
+    // Inputs on the same frame keep the order that they were written in
+    inputs.sort_by_key(|input| input.frame);
+
+    Ok(Self {
+      inputs,
+      next_input: 0,
+    })
+  }
+
+  /// Applies the inputs that happen at the start of the frame, and any earlier ones that
+  /// weren't applied yet.
+  pub fn apply(&mut self, frame: u32, emulator: &mut Emulator) {
+    while let Some(input) = self
+      .inputs
+      .get(self.next_input)
+      .filter(|input| input.frame <= frame)
+    {
+      emulator.hardware.update_button(input.button, input.action);
+      self.next_input += 1;
+    }
+  }
+}
+
+/// An error returned when an input script has an invalid line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputScriptError {
+  /// The line number, starting from 1.
+  pub line: usize,
+  /// What's wrong with the line.
+  pub message: String,
+}
+
+impl fmt::Display for InputScriptError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "line {}: {}", self.line, self.message)
+  }
+}
+
+impl std::error::Error for InputScriptError {}
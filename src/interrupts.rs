@@ -1,7 +1,7 @@
 use crate::flags::{add_flag, is_flag_set, remove_flag};
 
 /// A kind of interrupt.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Interrupt {
   VBlank = 1 << 0,
@@ -13,6 +13,7 @@ pub enum Interrupt {
 
 /// Stores the enabled interrupts and pending interrupts.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Interrupts {
   /// The `IF` register, which is the currently pending interrupts.
   requested: u8,
@@ -90,6 +91,12 @@ impl Interrupts {
   }
 }
 
+impl Default for Interrupts {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl Interrupt {
   /// Converts the [`Interrupt`] to its vector address.
   pub const fn to_vector(self) -> u16 {
@@ -100,4 +107,71 @@ impl Interrupt {
 
     BASE_INTERRUPT_ADDRESS + leading_zeros * INTERRUPT_OFFSET
   }
+
+  /// Returns the [`Interrupt`] whose vector is at the address, if any.
+  pub fn from_vector(address: u16) -> Option<Self> {
+    [
+      Interrupt::VBlank,
+      Interrupt::Lcd,
+      Interrupt::Timer,
+      Interrupt::Serial,
+      Interrupt::Joypad,
+    ]
+    .into_iter()
+    .find(|interrupt| interrupt.to_vector() == address)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::emulator::{Emulator, Executed};
+
+  /// The address of the `IE` register.
+  const IE_REGISTER: u16 = 0xFFFF;
+  /// The address of the `IF` register.
+  const IF_REGISTER: u16 = 0xFF0F;
+
+  #[test]
+  fn ie_keeps_all_8_bits() {
+    let mut emulator = Emulator::with_program(&[]);
+
+    emulator.hardware.write_byte(IE_REGISTER, 0xFF);
+
+    assert_eq!(emulator.hardware.read_byte(IE_REGISTER), 0xFF);
+  }
+
+  #[test]
+  fn upper_ie_bits_dont_enable_interrupts() {
+    let mut interrupts = Interrupts::new();
+
+    interrupts.set_enabled(0b1110_0000);
+    interrupts.set_requested(0xFF);
+
+    assert_eq!(interrupts.pending_bitfield(), 0);
+    assert_eq!(
+      Interrupts::next_interrupt_from_bitfield(interrupts.pending_bitfield()),
+      None
+    );
+  }
+
+  #[test]
+  fn only_lower_ie_bits_dispatch_interrupts() {
+    let mut emulator = Emulator::with_program(&[]);
+
+    emulator.cpu_mut().set_interrupt_master_enabled(true);
+    emulator.hardware.write_byte(IF_REGISTER, 0xFF);
+    emulator.hardware.write_byte(IE_REGISTER, 0b1110_0000);
+
+    // Only the unused upper bits are set, so the CPU carries on with the program
+    let step = emulator.step_instruction().unwrap();
+
+    assert!(matches!(step.executed, Executed::Instruction(_)));
+
+    emulator.hardware.write_byte(IE_REGISTER, 0b1110_0100);
+
+    let step = emulator.step_instruction().unwrap();
+
+    assert_eq!(step.executed, Executed::Interrupt(Some(Interrupt::Timer)));
+  }
 }
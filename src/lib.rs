@@ -0,0 +1,16 @@
+pub mod accuracy;
+pub mod disassembler;
+pub mod emulator;
+pub mod flags;
+pub mod hardware;
+pub mod input_script;
+pub mod interrupts;
+#[cfg(feature = "save-states")]
+pub mod rewind;
+#[cfg(feature = "save-states")]
+pub mod save_state;
+#[cfg(feature = "serde")]
+mod serde_arrays;
+pub mod test_rom;
+pub mod trace;
+pub mod watchdog;
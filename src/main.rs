@@ -1,50 +1,134 @@
-mod emulator;
-mod flags;
-mod hardware;
-mod interrupts;
-
-use emulator::Emulator;
-use flags::is_flag_set;
-use hardware::{
-  Cpu, Hardware,
-  apu::{Apu, AudioSample},
-  joypad::{Button, ButtonAction},
+mod frontend;
+
+#[cfg(feature = "save-states")]
+use gameboy_emu::rewind::RewindBuffer;
+use gameboy_emu::{
+  accuracy::AccuracyProfile,
+  emulator::CYCLES_PER_FRAME,
+  hardware::{
+    apu,
+    joypad::{ButtonAction, SocdMode},
+    serial::TcpLink,
+  },
+  input_script::InputScript,
+  test_rom::{self, TestRomResult},
 };
 
-use cpal::{
-  BufferSize, SampleRate, StreamConfig,
-  traits::{DeviceTrait, HostTrait, StreamTrait},
-};
-use softbuffer::{Context, Surface};
+use cpal::traits::StreamTrait;
 use winit::{
   dpi::PhysicalSize,
   event::{ElementState, Event, KeyEvent, WindowEvent},
   event_loop::{ControlFlow, EventLoop},
   keyboard::{KeyCode, PhysicalKey},
-  window::WindowBuilder,
+  window::{Fullscreen, WindowBuilder},
 };
 
 use std::{
-  collections::VecDeque,
-  fmt::Write,
-  fs,
-  num::NonZeroU32,
+  cell::RefCell,
+  fs, io,
+  path::{Path, PathBuf},
   rc::Rc,
-  sync::{Arc, Mutex},
+  sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+  },
   time::{Duration, Instant},
 };
 
-/// The Gameboy runs at 59.7275 frames per second.
-const FRAME_TIME: Duration = Duration::from_micros(16_740);
-
-const GAMEBOY_WIDTH: u32 = 160;
-const GAMEBOY_HEIGHT: u32 = 144;
-
-const INITIAL_GAMEBOY_WIDTH: u32 = GAMEBOY_WIDTH * 6;
-const INITIAL_GAMEBOY_HEIGHT: u32 = GAMEBOY_HEIGHT * 6;
+#[cfg(feature = "gpu")]
+use frontend::render::create_gpu_renderer;
+use frontend::{
+  FRAME_TIME, GAMEBOY_FRAME_RATE, GAMEBOY_HEIGHT, GAMEBOY_WIDTH,
+  audio::{AUDIO_DEVICE_BUFFER_SIZE, AudioSource, get_audio_stream, set_audio_paused},
+  capture::{GifRecorder, print_serial_output, record_audio, run_headless, save_gif},
+  config::{
+    FramePacing, Palette, load_palette, parse_accuracy_profile, parse_cartridge_kind,
+    parse_frame_pacing, parse_palette, parse_socd_mode, save_palette,
+  },
+  game::{
+    EmulatorSetup, TraceLog, load_boot_rom, load_emulator, save_game, step_emulator,
+    step_emulator_with_breakpoints, step_instruction, switch_game, window_title,
+  },
+  input::{
+    DEFAULT_KEY_BINDINGS_PATH, convert_button, default_key_bindings, hex_digit, load_key_bindings,
+  },
+  overlay::{
+    DEBUGGER_ADDRESS_DIGITS, Debugger, NOTICE_DURATION, Overlays, TEXT_BUFFER_MAX_LENGTH, Toasts,
+    Viewport, VramView, draw_overlays, muted_channels_text, volume_text,
+  },
+  render::{
+    DisplayFilter, PresentedFrame, Renderer, compute_scale_factor, create_software_renderer,
+    lerp_color, sharp_bilinear_axis,
+  },
+};
 
-/// The maximum length of the text buffer used to draw text.
-const TEXT_BUFFER_MAX_LENGTH: usize = 16;
+/// The most frames that get emulated in one redraw to catch up, when the host redraws less
+/// often than the Gameboy's frame rate.
+const MAX_CATCH_UP_FRAMES: u32 = 4;
+
+/// How many times bigger than the Gameboy's screen the window starts out.
+const DEFAULT_WINDOW_SCALE: u32 = 6;
+
+/// The usage that gets printed for `--help` and when no game is given.
+const USAGE: &str = "\
+Usage: gameboy_emu <rom> [options]
+
+Options:
+  --scale <n>                 Start the window at n times the size of the screen (default 6)
+  --fullscreen                Start in borderless fullscreen
+  --palette <name>            Start with the palette: green, grayscale, pocket or high_contrast
+  --no-audio                  Don't play any audio
+  --boot-rom <path>           Run the DMG boot ROM before the game
+  --cgb                       Run the game in CGB mode
+  --serial                    Print the bytes that the game sends over serial
+  --key-bindings <path>       Load the key bindings from a TOML file
+  --force-mbc <kind>          Ignore the cartridge type: rom_only, mbc1, mbc2 or mbc5
+  --accuracy <profile>        Pick the accuracy settings: fast, balanced or accurate
+  --pacing <pacing>           Pace frames by the wall_clock or by the audio
+  --socd <mode>               Resolve opposing directions: hardware, last or neutral
+  --audio-ring-buffer         Hand the audio over through a lock-free ring buffer
+  --audio-prefill <ms>        Queue up this much silence before playing audio
+  --record-audio <path>       Record the audio to a WAV file without opening a window
+  --frames <n>                The number of frames to record audio for (default 600)
+  --headless <n>              Run n frames as fast as possible without a window, and print the fps
+  --input-script <path>       Press and release buttons at set frames with --record-audio or
+                              --headless
+  --test-rom <n>              Run a Blargg or Mooneye test ROM for up to n frames, and exit with
+                              an error if it doesn't pass
+  --link-listen <address>     Wait for the other side of the link cable on the address
+  --link-connect <address>    Connect the link cable to the address
+  --watchdog <instructions>   Warn when the CPU gets stuck for this many instructions
+  --debug                     Print the last executed instructions if the emulator panics
+  --trace <path>              Log the CPU state before every instruction, like Gameboy Doctor
+  --gpu                       Scale the game on the GPU, with the `gpu` feature
+  -h, --help                  Print this message";
+
+/// The number of frames to run when running headless, about 10 seconds.
+const DEFAULT_HEADLESS_FRAMES: u32 = 600;
+/// The extension of the save state files that are written next to the ROMs.
+#[cfg(feature = "save-states")]
+const SAVE_STATE_EXTENSION: &str = "state";
+
+/// The number of snapshots that the rewind buffer keeps, for about 10 seconds of rewinding.
+#[cfg(feature = "save-states")]
+const REWIND_CAPACITY: usize = 150;
+/// The number of frames between the snapshots of the rewind buffer.
+#[cfg(feature = "save-states")]
+const REWIND_INTERVAL: u32 = 4;
+/// The most memory that the rewind buffer's snapshots take up, past which it keeps fewer.
+#[cfg(feature = "save-states")]
+const REWIND_MAX_BYTES: usize = 32 * 1024 * 1024;
+
+/// How many times faster than real time the game runs while fast-forwarding.
+const TURBO_SPEED: u32 = 4;
+/// The capacity of the lock-free audio ring buffer, which is enough to hold 2 callbacks' worth
+/// of samples, about 186ms.
+const AUDIO_RING_BUFFER_CAPACITY: usize = AUDIO_DEVICE_BUFFER_SIZE as usize * 2;
+/// The number of queued audio samples to stay above when pacing frames by the audio, which
+/// leaves a callback's worth of samples plus some headroom, about 139ms.
+const AUDIO_PACING_TARGET_SAMPLES: usize = AUDIO_DEVICE_BUFFER_SIZE as usize * 3 / 2;
+/// How often the queued audio gets checked when pacing frames by the audio.
+const AUDIO_PACING_POLL_INTERVAL: Duration = Duration::from_millis(1);
 
 fn main() {
   let mut args = std::env::args();
@@ -52,57 +136,415 @@ fn main() {
   // The first argument is usually the executable name
   args.next();
 
-  let Some(game_rom) = args.next() else {
-    eprintln!("Expected a game to be passed as an argument!");
+  let mut game_rom = None;
+  let mut record_audio_path = None;
+  let mut frames = DEFAULT_HEADLESS_FRAMES;
+  let mut headless_frames = None;
+  let mut input_script = InputScript::default();
+  let mut test_rom_frames: Option<usize> = None;
+  let mut socd_mode = SocdMode::default();
+  let mut use_audio_ring_buffer = false;
+  let mut audio_prefill_samples = AUDIO_DEVICE_BUFFER_SIZE as usize;
+  let mut frame_pacing = FramePacing::WallClock;
+  let mut accuracy_profile = AccuracyProfile::default();
+  let mut forced_cartridge_kind = None;
+  let mut watchdog_threshold = None;
+  let mut debug = false;
+  let mut trace_path = None;
+  let mut boot_rom = None;
+  let mut print_serial = false;
+  let mut cgb_mode = false;
+  let mut window_scale = DEFAULT_WINDOW_SCALE;
+  let mut fullscreen = false;
+  let mut initial_palette = None;
+  let mut play_audio = true;
+  let mut link = None;
+  let mut key_bindings_path = None;
+  #[cfg(feature = "gpu")]
+  let mut use_gpu = false;
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--record-audio" => {
+        let Some(path) = args.next() else {
+          eprintln!("Expected a file path after `--record-audio`!");
+          return;
+        };
+
+        record_audio_path = Some(path);
+      }
+      "--frames" => {
+        let Some(num_frames) = args.next().and_then(|x| x.parse().ok()) else {
+          eprintln!("Expected a number of frames after `--frames`!");
+          return;
+        };
+
+        frames = num_frames;
+      }
+      "--headless" => {
+        let Some(num_frames) = args.next().and_then(|x| x.parse().ok()) else {
+          eprintln!("Expected a number of frames after `--headless`!");
+          return;
+        };
+
+        headless_frames = Some(num_frames);
+      }
+      "--input-script" => {
+        let Some(path) = args.next() else {
+          eprintln!("Expected a file path after `--input-script`!");
+          return;
+        };
+
+        let result = fs::read_to_string(&path)
+          .map_err(|e| e.to_string())
+          .and_then(|script| InputScript::parse(&script).map_err(|e| e.to_string()));
+
+        match result {
+          Ok(script) => input_script = script,
+          Err(e) => {
+            eprintln!("Failed to load the input script {path}: {e}");
+            return;
+          }
+        }
+      }
+      "--test-rom" => {
+        let Some(num_frames) = args.next().and_then(|x| x.parse().ok()) else {
+          eprintln!("Expected a number of frames after `--test-rom`!");
+          return;
+        };
+
+        test_rom_frames = Some(num_frames);
+      }
+      "--socd" => {
+        let Some(mode) = args.next().as_deref().and_then(parse_socd_mode) else {
+          eprintln!("Expected one of `hardware`, `last` or `neutral` after `--socd`!");
+          return;
+        };
+
+        socd_mode = mode;
+      }
+      "--audio-ring-buffer" => use_audio_ring_buffer = true,
+      "--audio-prefill" => {
+        let Some(ms) = args.next().and_then(|x| x.parse::<usize>().ok()) else {
+          eprintln!("Expected a number of milliseconds after `--audio-prefill`!");
+          return;
+        };
+
+        audio_prefill_samples = ms * apu::SAMPLE_RATE as usize / 1000;
+      }
+      "--accuracy" => {
+        let Some(profile) = args.next().as_deref().and_then(parse_accuracy_profile) else {
+          eprintln!("Expected one of `fast`, `balanced` or `accurate` after `--accuracy`!");
+          return;
+        };
+
+        accuracy_profile = profile;
+      }
+      "--pacing" => {
+        let Some(pacing) = args.next().as_deref().and_then(parse_frame_pacing) else {
+          eprintln!("Expected one of `wall_clock` or `audio` after `--pacing`!");
+          return;
+        };
+
+        frame_pacing = pacing;
+      }
+      "--force-mbc" => {
+        let Some(kind) = args.next().as_deref().and_then(parse_cartridge_kind) else {
+          eprintln!("Expected one of `rom_only`, `mbc1`, `mbc2` or `mbc5` after `--force-mbc`!");
+          return;
+        };
+
+        forced_cartridge_kind = Some(kind);
+      }
+      "--watchdog" => {
+        let Some(threshold) = args.next().and_then(|x| x.parse().ok()) else {
+          eprintln!("Expected a number of instructions after `--watchdog`!");
+          return;
+        };
+
+        watchdog_threshold = Some(threshold);
+      }
+      "--help" | "-h" => {
+        println!("{USAGE}");
+        return;
+      }
+      "--scale" => {
+        let Some(scale) = args.next().and_then(|x| x.parse().ok()).filter(|&x| x > 0) else {
+          eprintln!("Expected a scale of at least 1 after `--scale`!");
+          return;
+        };
+
+        window_scale = scale;
+      }
+      "--fullscreen" => fullscreen = true,
+      "--palette" => {
+        let Some(palette) = args.next().as_deref().and_then(parse_palette) else {
+          eprintln!(
+            "Expected one of `green`, `grayscale`, `pocket` or `high_contrast` after `--palette`!"
+          );
+          return;
+        };
+
+        initial_palette = Some(palette);
+      }
+      "--no-audio" => play_audio = false,
+      "--debug" => debug = true,
+      "--trace" => {
+        let Some(path) = args.next() else {
+          eprintln!("Expected a file path after `--trace`!");
+          return;
+        };
+
+        trace_path = Some(path);
+      }
+      "--serial" => print_serial = true,
+      "--cgb" => cgb_mode = true,
+      #[cfg(feature = "gpu")]
+      "--gpu" => use_gpu = true,
+      #[cfg(not(feature = "gpu"))]
+      "--gpu" => {
+        eprintln!("`--gpu` needs the emulator to be built with the `gpu` feature!");
+        return;
+      }
+      "--link-listen" | "--link-connect" => {
+        let Some(address) = args.next() else {
+          eprintln!("Expected an address after `{arg}`!");
+          return;
+        };
+
+        let result = if arg == "--link-listen" {
+          eprintln!("Waiting for the other side of the link cable on {address}...");
+          TcpLink::listen(&address)
+        } else {
+          TcpLink::connect(&address)
+        };
+
+        match result {
+          Ok(tcp_link) => link = Some(tcp_link),
+          Err(e) => {
+            eprintln!("Failed to connect the link cable over {address}: {e}");
+            return;
+          }
+        }
+      }
+      "--key-bindings" => {
+        let Some(path) = args.next() else {
+          eprintln!("Expected a file path after `--key-bindings`!");
+          return;
+        };
+
+        key_bindings_path = Some(PathBuf::from(path));
+      }
+      "--boot-rom" => {
+        let Some(path) = args.next() else {
+          eprintln!("Expected a file path after `--boot-rom`!");
+          return;
+        };
+
+        match load_boot_rom(Path::new(&path)) {
+          Ok(bytes) => boot_rom = Some(bytes),
+          Err(e) => {
+            eprintln!("Failed to load the boot ROM {path}: {e}");
+            return;
+          }
+        }
+      }
+      _ => game_rom = Some(arg),
+    }
+  }
+
+  let Some(game_rom) = game_rom else {
+    eprintln!("Expected a game to be passed as an argument!\n\n{USAGE}");
+    return;
+  };
+
+  // Nothing drains the audio without an audio device, so frames would never be due
+  if !play_audio && matches!(frame_pacing, FramePacing::Audio) {
+    eprintln!("`--pacing audio` needs audio, so it can't be used with `--no-audio`!");
+    return;
+  }
+
+  let trace_log = match trace_path {
+    Some(path) => match fs::File::create(&path) {
+      Ok(file) => Some(Rc::new(RefCell::new(TraceLog {
+        path,
+        writer: io::BufWriter::new(file),
+        failed: false,
+      }))),
+      Err(e) => {
+        eprintln!("Failed to create the trace log {path}: {e}");
+        return;
+      }
+    },
+    None => None,
+  };
+  let setup = EmulatorSetup {
+    boot_rom,
+    cgb_mode,
+    accuracy_profile,
+    socd_mode,
+    watchdog_threshold,
+    keep_trace: debug,
+    trace_log,
+  };
+
+  let mut rom_path = PathBuf::from(game_rom);
+  let mut emulator = match load_emulator(&rom_path, forced_cartridge_kind, &setup) {
+    Ok(emulator) => emulator,
+    Err(e) => {
+      eprintln!("Failed to load {}: {e}", rom_path.display());
+      return;
+    }
+  };
+
+  if let Some(link) = link {
+    emulator.hardware.connect_serial(Box::new(link));
+  }
+
+  if let Some(path) = record_audio_path {
+    if let Err(e) = record_audio(&mut emulator, &path, frames, &mut input_script) {
+      eprintln!("Failed to record audio to {path}: {e}");
+    }
+
+    return;
+  }
+
+  if let Some(frames) = test_rom_frames {
+    let result = test_rom::run_test_rom(&mut emulator, frames * CYCLES_PER_FRAME);
+
+    let serial_output = String::from_utf8_lossy(emulator.hardware.serial_output());
+
+    if print_serial && !serial_output.is_empty() {
+      println!("{serial_output}");
+    }
+
+    println!("The test ROM {result}");
+
+    if result != TestRomResult::Passed {
+      std::process::exit(1);
+    }
+
     return;
+  }
+
+  if let Some(frames) = headless_frames {
+    run_headless(&mut emulator, frames, print_serial, &mut input_script);
+
+    return;
+  }
+
+  // The default key bindings file is optional, unlike one that's passed explicitly
+  let key_bindings = match key_bindings_path {
+    Some(path) => load_key_bindings(&path),
+    None if Path::new(DEFAULT_KEY_BINDINGS_PATH).exists() => {
+      load_key_bindings(Path::new(DEFAULT_KEY_BINDINGS_PATH))
+    }
+    None => Ok(default_key_bindings()),
+  };
+  let key_bindings = match key_bindings {
+    Ok(key_bindings) => key_bindings,
+    Err(e) => {
+      eprintln!("Failed to load the key bindings: {e}");
+      return;
+    }
   };
 
-  let rom_bytes = fs::read(&game_rom).unwrap();
+  let audio_source = if use_audio_ring_buffer {
+    AudioSource::RingBuffer(
+      emulator
+        .hardware
+        .apu
+        .use_ring_buffer(AUDIO_RING_BUFFER_CAPACITY),
+    )
+  } else {
+    // Don't let the latency grow without bound when running faster than real time
+    emulator
+      .hardware
+      .apu
+      .set_max_queued_samples(Some(AUDIO_RING_BUFFER_CAPACITY));
+
+    AudioSource::Shared(emulator.hardware.audio_buffer())
+  };
+  let audio_underruns = Arc::new(AtomicU64::new(0));
+  let audio_resumed = Arc::new(AtomicBool::new(false));
+  let audio_stream = play_audio.then(|| {
+    get_audio_stream(
+      audio_source,
+      Arc::clone(&audio_underruns),
+      Arc::clone(&audio_resumed),
+    )
+  });
 
-  let cpu = Cpu::with_register_defaults();
-  let hardware = Hardware::new(rom_bytes);
-  let mut emulator = Emulator::new(cpu, hardware);
-  let audio_stream = get_audio_stream(emulator.hardware.audio_buffer());
+  // Start with some silence queued up, so the first audio callbacks don't run dry
+  emulator.hardware.apu.prefill_audio(audio_prefill_samples);
 
+  let initial_width = GAMEBOY_WIDTH * window_scale;
+  let initial_height = GAMEBOY_HEIGHT * window_scale;
   let event_loop = EventLoop::new().unwrap();
   let window = Rc::new(
     WindowBuilder::new()
       .with_min_inner_size(PhysicalSize::new(GAMEBOY_WIDTH, GAMEBOY_HEIGHT))
-      .with_inner_size(PhysicalSize::new(
-        INITIAL_GAMEBOY_WIDTH,
-        INITIAL_GAMEBOY_HEIGHT,
-      ))
-      .with_title("Gameboy")
+      .with_inner_size(PhysicalSize::new(initial_width, initial_height))
+      .with_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)))
+      .with_title(window_title(&emulator, &rom_path))
       .build(&event_loop)
       .unwrap(),
   );
 
-  let context = Context::new(Rc::clone(&window)).unwrap();
-  let mut surface = Surface::new(&context, Rc::clone(&window)).unwrap();
+  #[cfg(feature = "gpu")]
+  let mut renderer = if use_gpu {
+    create_gpu_renderer(&window)
+  } else {
+    create_software_renderer(&window)
+  };
+  #[cfg(not(feature = "gpu"))]
+  let mut renderer = create_software_renderer(&window);
 
   let mut last_update = Instant::now();
   let mut first_update = true;
   let mut limit_frames = true;
   let mut show_debug_info = false;
+  let mut show_dma_progress = false;
   let mut is_shift_held = false;
-
-  let mut last_width = INITIAL_GAMEBOY_WIDTH;
-  let mut last_height = INITIAL_GAMEBOY_HEIGHT;
+  let mut display_filter = DisplayFilter::Nearest;
+  let mut frame_blending = false;
+  let mut presented_frame = PresentedFrame::new();
+  let mut vram_view = None;
+  let mut show_oam = false;
+  let mut palette = initial_palette
+    .or_else(|| load_palette(&rom_path))
+    .unwrap_or(Palette::Green);
+  let mut toasts = Toasts::new();
+  let mut gif_recorder: Option<GifRecorder> = None;
+  let mut is_turbo = false;
+  let mut is_paused = false;
+  let mut debugger = Debugger::default();
+  #[cfg(feature = "save-states")]
+  let mut rewind_buffer = RewindBuffer::new(REWIND_CAPACITY, REWIND_INTERVAL, REWIND_MAX_BYTES);
+  #[cfg(feature = "save-states")]
+  let mut is_rewinding = false;
+  let mut serial_printed = 0;
+
+  let mut last_width = initial_width;
+  let mut last_height = initial_height;
 
   let mut fps = 0.0;
   let mut num_frames = 0;
+  let mut speed = 0.0;
+  let mut num_emulated_frames = 0;
   let mut last_fps_update = last_update;
 
-  let mut window_frame = vec![0; (last_width * last_height) as usize];
   // Pre-allocate and reuse this buffer to avoid a bunch of micro allocations.
   let mut text_buffer = String::with_capacity(TEXT_BUFFER_MAX_LENGTH);
 
-  audio_stream.play().unwrap();
+  if let Some(audio_stream) = &audio_stream {
+    audio_stream.play().unwrap();
+  }
 
   event_loop
     .run(move |event, elwt| {
       if limit_frames {
-        let next_frame_time = last_update + FRAME_TIME;
+        let next_frame_time = get_next_frame_time(frame_pacing, last_update);
 
         elwt.set_control_flow(ControlFlow::WaitUntil(next_frame_time));
       } else {
@@ -114,7 +556,35 @@ fn main() {
           window_id,
           event: WindowEvent::CloseRequested,
           ..
-        } if window_id == window.id() => elwt.exit(),
+        } if window_id == window.id() => {
+          save_game(&emulator, &rom_path);
+
+          // Finish the recording before exiting, since exiting would cut the encoding short
+          if let Some(recorder) = gif_recorder.take() {
+            let _ = save_gif(recorder, &rom_path).join();
+          }
+
+          elwt.exit();
+        }
+
+        // Dropping a ROM onto the window loads it, like picking it with `Shift` and `O`
+        Event::WindowEvent {
+          window_id,
+          event: WindowEvent::DroppedFile(path),
+        } if window_id == window.id() => {
+          match switch_game(&mut emulator, &mut rom_path, path, &setup) {
+            Ok(()) => {
+              #[cfg(feature = "save-states")]
+              rewind_buffer.clear();
+              palette = load_palette(&rom_path).unwrap_or(palette);
+              window.set_title(&window_title(&emulator, &rom_path));
+            }
+            Err(e) => {
+              eprintln!("{e}");
+              toasts.push_toast(e, NOTICE_DURATION);
+            }
+          }
+        }
 
         Event::AboutToWait => {
           window.request_redraw();
@@ -136,28 +606,286 @@ fn main() {
           PhysicalKey::Code(KeyCode::ShiftLeft | KeyCode::ShiftRight) => {
             is_shift_held = matches!(state, ElementState::Pressed);
           }
+          // `Shift` and `D` opens or closes the debugger
+          PhysicalKey::Code(KeyCode::KeyD)
+            if is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            debugger.is_open = !debugger.is_open;
+            is_paused = debugger.is_open;
+
+            // The last frame stopped partway through an instruction
+            if debugger.is_open {
+              step_instruction(&mut emulator);
+            }
+
+            set_audio_paused(audio_stream.as_ref(), &audio_resumed, is_paused);
+          }
+          // The debugger takes all of the other key presses while it's open, but releases still
+          // go to the game, so that no buttons get stuck
+          PhysicalKey::Code(key)
+            if debugger.is_open && !is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            match key {
+              // `N` steps to the next instruction
+              KeyCode::KeyN => step_instruction(&mut emulator),
+              // `G` closes the debugger and runs until the next breakpoint
+              KeyCode::KeyG => {
+                debugger.is_open = false;
+                is_paused = false;
+
+                set_audio_paused(audio_stream.as_ref(), &audio_resumed, is_paused);
+              }
+              // `Enter` toggles a breakpoint at the typed address, or at the next instruction
+              KeyCode::Enter => {
+                let address = match u16::from_str_radix(&debugger.address_input, 16) {
+                  Ok(address) => address,
+                  Err(_) => emulator.next_instruction_address(),
+                };
+
+                if !debugger.breakpoints.remove(&address) {
+                  debugger.breakpoints.insert(address);
+                }
+
+                debugger.address_input.clear();
+              }
+              KeyCode::Backspace => {
+                debugger.address_input.pop();
+              }
+              key => {
+                if let Some(digit) = hex_digit(key) {
+                  if debugger.address_input.len() < DEBUGGER_ADDRESS_DIGITS {
+                    debugger.address_input.push(digit);
+                  }
+                }
+              }
+            }
+          }
+          // Keys that are bound to a button take priority over the hotkeys without `Shift`, like
+          // `P` and `Tab`, once the debugger is closed, and releasing them always releases the
+          // button
+          PhysicalKey::Code(key_code)
+            if key_bindings.contains_key(&key_code)
+              && (!is_shift_held || matches!(state, ElementState::Released)) =>
+          {
+            let button_action = match state {
+              ElementState::Pressed => ButtonAction::Pressed,
+              ElementState::Released => ButtonAction::Released,
+            };
+
+            emulator
+              .hardware
+              .update_button(key_bindings[&key_code], button_action)
+          }
           PhysicalKey::Code(KeyCode::Digit1)
             if is_shift_held && matches!(state, ElementState::Pressed) =>
           {
             show_debug_info = !show_debug_info;
           }
+          // `Shift` and `2` toggles the OAM DMA progress bar
+          PhysicalKey::Code(KeyCode::Digit2)
+            if is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            show_dma_progress = !show_dma_progress;
+          }
+          // `Shift` and `3` toggles rendering while the game has the LCD off
+          PhysicalKey::Code(KeyCode::Digit3)
+            if is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            let force_render = !emulator.hardware.ppu.force_render();
+
+            emulator.hardware.ppu.set_force_render(force_render);
+          }
+          // `Shift` and `F` cycles through the display filters
+          PhysicalKey::Code(KeyCode::KeyF)
+            if is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            display_filter = display_filter.next();
+          }
+          // `Shift` and `L` toggles blending each frame with the last one, like the slow LCD
+          PhysicalKey::Code(KeyCode::KeyL)
+            if is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            frame_blending = !frame_blending;
+            toasts.push_toast(
+              if frame_blending {
+                "FRAME BLENDING: ON"
+              } else {
+                "FRAME BLENDING: OFF"
+              },
+              NOTICE_DURATION,
+            );
+          }
+          // `Shift` and `V` cycles through the views of the VRAM viewer, then closes it
+          PhysicalKey::Code(KeyCode::KeyV)
+            if is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            vram_view = match vram_view {
+              None => Some(VramView::Tiles),
+              Some(view) => view.next(),
+            };
+          }
+          // `Shift` and `S` toggles the OAM inspector
+          PhysicalKey::Code(KeyCode::KeyS)
+            if is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            show_oam = !show_oam;
+          }
+          // `Shift` and `P` cycles through the palettes
+          PhysicalKey::Code(KeyCode::KeyP)
+            if is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            palette = palette.next();
+            toasts.push_toast(format!("PALETTE: {}", palette.name()), NOTICE_DURATION);
+
+            save_palette(&rom_path, palette);
+          }
+          // `Shift` and `4` to `7` mute or unmute the sound channels 1 to 4
+          PhysicalKey::Code(
+            key @ (KeyCode::Digit4 | KeyCode::Digit5 | KeyCode::Digit6 | KeyCode::Digit7),
+          ) if is_shift_held && matches!(state, ElementState::Pressed) => {
+            let channel = match key {
+              KeyCode::Digit4 => 1,
+              KeyCode::Digit5 => 2,
+              KeyCode::Digit6 => 3,
+              _ => 4,
+            };
+            let apu = &mut emulator.hardware.apu;
+
+            apu.set_channel_enabled(channel, !apu.is_channel_enabled(channel));
+            toasts.push_toast(muted_channels_text(apu), NOTICE_DURATION);
+          }
+          // `P` pauses or resumes the game
+          PhysicalKey::Code(KeyCode::KeyP)
+            if !is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            is_paused = !is_paused;
+
+            set_audio_paused(audio_stream.as_ref(), &audio_resumed, is_paused);
+          }
+          // `Shift` and `G` starts or stops recording a GIF
+          PhysicalKey::Code(KeyCode::KeyG)
+            if is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            match gif_recorder.take() {
+              Some(recorder) => {
+                save_gif(recorder, &rom_path);
+              }
+              None => gif_recorder = Some(GifRecorder::new(palette.colors())),
+            }
+          }
+          // `Shift` and `O` opens a file dialog to load a different game
+          PhysicalKey::Code(KeyCode::KeyO)
+            if is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            let picked_rom = rfd::FileDialog::new()
+              .add_filter("Game Boy ROM", &["gb", "gbc"])
+              .pick_file();
+
+            if let Some(path) = picked_rom {
+              match switch_game(&mut emulator, &mut rom_path, path, &setup) {
+                Ok(()) => {
+                  #[cfg(feature = "save-states")]
+                  rewind_buffer.clear();
+                  palette = load_palette(&rom_path).unwrap_or(palette);
+                  window.set_title(&window_title(&emulator, &rom_path));
+                }
+                Err(e) => {
+                  eprintln!("{e}");
+                  toasts.push_toast(e, NOTICE_DURATION);
+                }
+              }
+            }
+
+            // The key release is likely to have happened while the dialog was open
+            is_shift_held = false;
+          }
           // `Shift` and `-` decreases the master volume
           PhysicalKey::Code(KeyCode::Minus)
             if is_shift_held && matches!(state, ElementState::Pressed) =>
           {
             emulator.hardware.apu.decrement_volume();
+            toasts.push_toast(volume_text(&emulator.hardware.apu), NOTICE_DURATION);
           }
           // `Shift` and `=` increases the master volume
           PhysicalKey::Code(KeyCode::Equal)
             if is_shift_held && matches!(state, ElementState::Pressed) =>
           {
             emulator.hardware.apu.increment_volume();
+            toasts.push_toast(volume_text(&emulator.hardware.apu), NOTICE_DURATION);
+          }
+          // `Shift` and `R` restarts the game
+          PhysicalKey::Code(KeyCode::KeyR)
+            if is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            emulator.reset();
+          }
+          // `Shift` and `B` restarts the game from the boot ROM, if one was given
+          PhysicalKey::Code(KeyCode::KeyB)
+            if is_shift_held && matches!(state, ElementState::Pressed) =>
+          {
+            emulator.hard_reset();
+          }
+          // `F5` saves the state of the game
+          #[cfg(feature = "save-states")]
+          PhysicalKey::Code(KeyCode::F5) if matches!(state, ElementState::Pressed) => {
+            let state_path = rom_path.with_extension(SAVE_STATE_EXTENSION);
+
+            match fs::write(&state_path, emulator.save_state()) {
+              Ok(()) => toasts.push_toast("STATE SAVED", NOTICE_DURATION),
+              Err(e) => {
+                eprintln!("Failed to save the state to {}: {e}", state_path.display());
+                toasts.push_toast("FAILED TO SAVE STATE", NOTICE_DURATION);
+              }
+            }
+          }
+          // `F9` loads the state of the game that was saved with `F5`
+          #[cfg(feature = "save-states")]
+          PhysicalKey::Code(KeyCode::F9) if matches!(state, ElementState::Pressed) => {
+            let state_path = rom_path.with_extension(SAVE_STATE_EXTENSION);
+            let result = fs::read(&state_path)
+              .map_err(|e| e.to_string())
+              .and_then(|bytes| emulator.load_state(&bytes).map_err(|e| e.to_string()));
+
+            match result {
+              Ok(()) => {
+                // The snapshots from before loading the state don't lead up to it
+                rewind_buffer.clear();
+                toasts.push_toast("STATE LOADED", NOTICE_DURATION);
+              }
+              Err(e) => {
+                eprintln!(
+                  "Failed to load the state from {}: {e}",
+                  state_path.display()
+                );
+                toasts.push_toast("FAILED TO LOAD STATE", NOTICE_DURATION);
+              }
+            }
+          }
+          // Holding `Tab` fast-forwards the game
+          PhysicalKey::Code(KeyCode::Tab) => {
+            let is_held = matches!(state, ElementState::Pressed);
+
+            // Holding the key repeats the press, which shouldn't restart the audio skipping
+            if is_held != is_turbo {
+              is_turbo = is_held;
+
+              let speed = if is_turbo { TURBO_SPEED } else { 1 };
+
+              emulator.hardware.apu.set_output_speed(speed);
+            }
+          }
+          // Holding `R` rewinds the game
+          #[cfg(feature = "save-states")]
+          PhysicalKey::Code(KeyCode::KeyR) if !is_shift_held => {
+            is_rewinding = matches!(state, ElementState::Pressed);
           }
           PhysicalKey::Code(KeyCode::Space) if matches!(state, ElementState::Released) => {
             limit_frames = !limit_frames;
 
             if limit_frames {
-              elwt.set_control_flow(ControlFlow::WaitUntil(last_update + FRAME_TIME));
+              let next_frame_time = get_next_frame_time(frame_pacing, last_update);
+
+              elwt.set_control_flow(ControlFlow::WaitUntil(next_frame_time));
             } else {
               elwt.set_control_flow(ControlFlow::Poll);
             }
@@ -165,7 +893,7 @@ fn main() {
             window.request_redraw();
           }
           key => {
-            if let Some(gb_button) = convert_button(&key) {
+            if let Some(gb_button) = convert_button(&key, &key_bindings) {
               let button_action = match state {
                 ElementState::Pressed => ButtonAction::Pressed,
                 ElementState::Released => ButtonAction::Released,
@@ -183,119 +911,252 @@ fn main() {
         } if window_id == window.id() => {
           let now = Instant::now();
 
-          if first_update || !limit_frames || now >= last_update + FRAME_TIME {
+          let is_frame_due = match frame_pacing {
+            FramePacing::WallClock => now >= last_update + FRAME_TIME,
+            // Nothing drains the audio while paused, so keep redrawing by the clock
+            FramePacing::Audio if is_paused => now >= last_update + FRAME_TIME,
+            FramePacing::Audio => {
+              emulator.hardware.apu.queued_samples() < AUDIO_PACING_TARGET_SAMPLES
+            }
+          };
+
+          if first_update || !limit_frames || is_frame_due {
+            // A slow host redraws less often than the Gameboy's frame rate, so the missed frames
+            // get emulated without being shown, instead of slowing the game down
+            let catching_up =
+              frame_pacing == FramePacing::WallClock && limit_frames && !first_update;
+            let due_frames = if catching_up {
+              get_due_frames(now, last_update)
+            } else {
+              1
+            };
+
             let (width, height) = {
               let size = window.inner_size();
               (size.width, size.height)
             };
 
             if width != last_width || height != last_height {
-              surface
-                .resize(
-                  NonZeroU32::new(width).unwrap(),
-                  NonZeroU32::new(height).unwrap(),
-                )
-                .unwrap();
-
-              window_frame.resize((width * height) as usize, 0);
+              renderer.resize(width, height);
 
               last_width = width;
               last_height = height;
             }
 
-            emulator.step();
-
-            let scale = compute_scale_factor(width, height);
-            let game_width = (GAMEBOY_WIDTH as f64 * scale) as u32;
-            let game_height = (GAMEBOY_HEIGHT as f64 * scale) as u32;
-
-            // Make sure that the game is in the center of the screen
-            let offset_x = (width - game_width) / 2;
-            let offset_y = (height - game_height) / 2;
-
-            let game_buffer = emulator.hardware.frame_buffer();
-
-            #[cfg(debug_assertions)]
-            // Pre-fill the buffer with green in debug mode
-            window_frame.fill(0x0000FF00);
-            #[cfg(not(debug_assertions))]
-            // Pre-fill the buffer with black in release builds
-            window_frame.fill(0x00000000);
-
-            for y in offset_y..offset_y + game_height {
-              for x in offset_x..offset_x + game_width {
-                let index = width * y + x;
-                let src_x = (((x - offset_x) as f64 / scale) as u32).min(GAMEBOY_WIDTH - 1);
-                let src_y = (((y - offset_y) as f64 / scale) as u32).min(GAMEBOY_HEIGHT - 1);
-
-                let color = match game_buffer[src_y as usize][src_x as usize] {
-                  0 => 0x00FFFFFF,
-                  1 => 0x0088C070,
-                  2 => 0x00346856,
-                  3 => 0x00081820,
-                  _ => 0x00FF0000,
+            #[cfg(feature = "save-states")]
+            let rewound = !is_paused && is_rewinding && rewind_buffer.rewind(&mut emulator);
+            #[cfg(not(feature = "save-states"))]
+            let rewound = false;
+
+            if !is_paused && !rewound {
+              let emulated_frames = due_frames * if is_turbo { TURBO_SPEED } else { 1 };
+
+              for _ in 0..emulated_frames {
+                let (frame_completed, hit_breakpoint) = if debugger.breakpoints.is_empty() {
+                  (step_emulator(&mut emulator), false)
+                } else {
+                  let hit_breakpoint =
+                    step_emulator_with_breakpoints(&mut emulator, &debugger.breakpoints);
+
+                  (!hit_breakpoint, hit_breakpoint)
                 };
 
-                window_frame[index as usize] = color;
+                // Redraws don't line up with emulated frames, so the speed and the GIF recording
+                // count the latter
+                if frame_completed {
+                  num_emulated_frames += 1;
+
+                  presented_frame.push_frame(&emulator, palette.colors(), frame_blending);
+
+                  if let Some(recorder) = &mut gif_recorder {
+                    recorder.capture(emulator.hardware.frame_buffer());
+
+                    if recorder.is_full() {
+                      eprintln!("The GIF recording reached its maximum length, so it was stopped");
+
+                      save_gif(gif_recorder.take().unwrap(), &rom_path);
+                    }
+                  }
+                }
+
+                #[cfg(feature = "save-states")]
+                rewind_buffer.record_frame(&emulator);
+
+                if hit_breakpoint {
+                  debugger.is_open = true;
+                  is_paused = true;
+
+                  set_audio_paused(audio_stream.as_ref(), &audio_resumed, is_paused);
+
+                  break;
+                }
               }
             }
 
+            if let Some(report) = emulator.take_stuck_cpu_report() {
+              eprintln!("Warning: {report}");
+            }
+
+            if print_serial {
+              print_serial_output(&emulator, &mut serial_printed);
+            }
+
+            let shade_colors = palette.colors();
+
+            presented_frame.refresh(&emulator, shade_colors, frame_blending);
+
+            let pixel_color = |x: usize, y: usize| presented_frame.pixel(x, y);
+
             num_frames += 1;
 
             let delta = now.duration_since(last_fps_update).as_secs_f64();
 
             if delta >= 1.0 {
               fps = num_frames as f64 / delta;
+              speed = num_emulated_frames as f64 / delta / GAMEBOY_FRAME_RATE * 100.0;
               last_fps_update = now;
               num_frames = 0;
+              num_emulated_frames = 0;
             }
 
-            if show_debug_info {
-              const FPS_X_POS: u32 = 2;
-              const FPS_Y_POS: u32 = 2;
-              const RED_COLOR: u32 = 0x00FF0000;
-
-              text_buffer.clear();
-
-              write!(&mut text_buffer, "FPS: {:.1}", fps).unwrap();
-
-              draw_text(
-                &text_buffer,
-                &mut window_frame,
-                width,
-                FPS_X_POS,
-                FPS_Y_POS,
-                RED_COLOR,
-                scale as u32,
-              );
-
-              const VOLUME_TEXT_PADDING: u32 = 10;
-
-              text_buffer.clear();
+            toasts.remove_expired(now);
+
+            let overlays = Overlays {
+              fps: show_debug_info.then_some(fps),
+              speed: show_debug_info.then_some(speed),
+              show_dma_progress,
+              toasts: &toasts,
+              now,
+              recording: gif_recorder.is_some(),
+              paused: is_paused && !debugger.is_open,
+              debugger: debugger.is_open.then_some(&debugger),
+              vram_view,
+              shade_colors,
+              show_oam,
+              audio_underruns: audio_underruns.load(Ordering::Relaxed),
+            };
 
-              write!(&mut text_buffer, "{} %", get_volume(&emulator.hardware.apu)).unwrap();
+            match &mut renderer {
+              Renderer::Software {
+                surface,
+                window_frame,
+              } => {
+                let scale = compute_scale_factor(width, height);
+                let game_width = (GAMEBOY_WIDTH as f64 * scale) as u32;
+                let game_height = (GAMEBOY_HEIGHT as f64 * scale) as u32;
+
+                // Make sure that the game is in the center of the screen
+                let viewport = Viewport {
+                  x: (width - game_width) / 2,
+                  y: (height - game_height) / 2,
+                  width: game_width,
+                  height: game_height,
+                  scale,
+                };
 
-              let volume_text_width = get_text_pixel_width(&text_buffer, scale as u32);
-              let volume_x = width - volume_text_width - VOLUME_TEXT_PADDING;
-              let volume_y = 2;
+                #[cfg(debug_assertions)]
+                // Pre-fill the buffer with green in debug mode
+                window_frame.fill(0x0000FF00);
+                #[cfg(not(debug_assertions))]
+                // Pre-fill the buffer with black in release builds
+                window_frame.fill(0x00000000);
+
+                for y in viewport.y..viewport.y + viewport.height {
+                  for x in viewport.x..viewport.x + viewport.width {
+                    let index = width * y + x;
+
+                    let color = match display_filter {
+                      DisplayFilter::Nearest => {
+                        let src_x =
+                          (((x - viewport.x) as f64 / scale) as u32).min(GAMEBOY_WIDTH - 1);
+                        let src_y =
+                          (((y - viewport.y) as f64 / scale) as u32).min(GAMEBOY_HEIGHT - 1);
+
+                        pixel_color(src_x as usize, src_y as usize)
+                      }
+                      DisplayFilter::SharpBilinear => {
+                        let (x0, x1, weight_x) =
+                          sharp_bilinear_axis(x - viewport.x, scale, GAMEBOY_WIDTH);
+                        let (y0, y1, weight_y) =
+                          sharp_bilinear_axis(y - viewport.y, scale, GAMEBOY_HEIGHT);
+
+                        let top = lerp_color(pixel_color(x0, y0), pixel_color(x1, y0), weight_x);
+                        let bottom = lerp_color(pixel_color(x0, y1), pixel_color(x1, y1), weight_x);
+
+                        lerp_color(top, bottom, weight_y)
+                      }
+                    };
+
+                    window_frame[index as usize] = color;
+                  }
+                }
+
+                draw_overlays(
+                  window_frame,
+                  width,
+                  &viewport,
+                  &emulator,
+                  &overlays,
+                  &mut text_buffer,
+                );
+
+                let mut buffer = surface.buffer_mut().unwrap();
+
+                buffer.copy_from_slice(window_frame);
+                buffer.present().unwrap();
+              }
+              #[cfg(feature = "gpu")]
+              Renderer::Gpu { pixels, game_frame } => {
+                // The GPU scales the game, so everything gets drawn at the game's size
+                let viewport = Viewport {
+                  x: 0,
+                  y: 0,
+                  width: GAMEBOY_WIDTH,
+                  height: GAMEBOY_HEIGHT,
+                  scale: 1.0,
+                };
 
-              draw_text(
-                &text_buffer,
-                &mut window_frame,
-                width,
-                volume_x,
-                volume_y,
-                RED_COLOR,
-                scale as u32,
-              );
+                for (index, color) in game_frame.iter_mut().enumerate() {
+                  *color = pixel_color(
+                    index % GAMEBOY_WIDTH as usize,
+                    index / GAMEBOY_WIDTH as usize,
+                  );
+                }
+
+                draw_overlays(
+                  game_frame,
+                  GAMEBOY_WIDTH,
+                  &viewport,
+                  &emulator,
+                  &overlays,
+                  &mut text_buffer,
+                );
+
+                for (pixel, color) in pixels.frame_mut().chunks_exact_mut(4).zip(&*game_frame) {
+                  // Colors are `0x00RRGGBB`, while `pixels` wants RGBA bytes
+                  let [_, red, green, blue] = color.to_be_bytes();
+
+                  pixel.copy_from_slice(&[red, green, blue, 0xFF]);
+                }
+
+                if let Err(e) = pixels.render() {
+                  eprintln!("Failed to render the frame on the GPU: {e}");
+                }
+              }
             }
 
-            let mut buffer = surface.buffer_mut().unwrap();
-
-            buffer.copy_from_slice(&window_frame);
-            buffer.present().unwrap();
+            last_update = if catching_up {
+              // Keep to the schedule, unless the host fell too far behind to catch up
+              let next_update = last_update + FRAME_TIME * due_frames;
 
-            last_update = now;
+              if now.saturating_duration_since(next_update) >= FRAME_TIME {
+                now
+              } else {
+                next_update
+              }
+            } else {
+              now
+            };
             first_update = false;
           }
         }
@@ -306,289 +1167,20 @@ fn main() {
     .unwrap();
 }
 
-fn get_audio_stream(audio_buffer: Arc<Mutex<VecDeque<AudioSample>>>) -> cpal::Stream {
-  let device = cpal::default_host().default_output_device().unwrap();
-
-  let config = StreamConfig {
-    channels: 2,
-    sample_rate: SampleRate(44_100),
-    buffer_size: BufferSize::Fixed(4096),
-  };
-
-  device
-    .build_output_stream(
-      &config,
-      move |data: &mut [f32], _| {
-        let mut buffer = audio_buffer.lock().unwrap();
-
-        for frame in data.chunks_mut(2) {
-          let AudioSample { left, right } = buffer.pop_front().unwrap_or_default();
+/// Returns how many frames are due since the last update, going by the system clock, up to
+/// [`MAX_CATCH_UP_FRAMES`].
+fn get_due_frames(now: Instant, last_update: Instant) -> u32 {
+  let elapsed = now.saturating_duration_since(last_update);
+  let due_frames = (elapsed.as_secs_f64() / FRAME_TIME.as_secs_f64()) as u32;
 
-          frame[0] = left;
-          frame[1] = right;
-        }
-      },
-      move |err| {
-        eprintln!("error {:?}", err);
-      },
-      None,
-    )
-    .unwrap()
-}
-
-/// Draws the text into the buffer at the following x and y position.
-fn draw_text(
-  text: &str,
-  buffer: &mut [u32],
-  buffer_width: u32,
-  x_pos: u32,
-  y_pos: u32,
-  color: u32,
-  scale: u32,
-) {
-  for (character_x_pos, bitmap) in text.as_bytes().iter().enumerate().flat_map(|(index, b)| {
-    get_character_bitmap(*b).map(|row| {
-      (
-        x_pos + (index as u32 * DEFAULT_CHARACTER_WIDTH * scale),
-        row,
-      )
-    })
-  }) {
-    for (row, bits) in bitmap.iter().enumerate() {
-      for col in 0..DEFAULT_CHARACTER_WIDTH {
-        let mask = 1 << (DEFAULT_CHARACTER_WIDTH - 1 - col);
-
-        if is_flag_set!(bits, mask) {
-          for dx in 0..scale {
-            for dy in 0..scale {
-              let draw_x = character_x_pos + col * scale + dx;
-              let draw_y = y_pos + row as u32 * scale + dy;
-              let buffer_index = draw_y * buffer_width + draw_x;
-
-              if buffer_index < buffer.len() as u32 {
-                buffer[buffer_index as usize] = color;
-              }
-            }
-          }
-        }
-      }
-    }
-  }
+  due_frames.clamp(1, MAX_CATCH_UP_FRAMES)
 }
 
-const DEFAULT_CHARACTER_WIDTH: u32 = 7;
-const DEFAULT_CHARACTER_HEIGHT: u32 = 8;
-
-/// Converts the ASCII byte to a 7x8 bitmap.
-#[rustfmt::skip]
-const fn get_character_bitmap(byte: u8) -> Option<[u8; DEFAULT_CHARACTER_HEIGHT as usize]> {
-  match byte {
-    b'0' => Some([
-      0b0111100,
-      0b1000010,
-      0b1000010,
-      0b1000010,
-      0b1000010,
-      0b1000010,
-      0b1000010,
-      0b0111100,
-    ]),
-    b'1' => Some([
-      0b0011000,
-      0b0101000,
-      0b0001000,
-      0b0001000,
-      0b0001000,
-      0b0001000,
-      0b0001000,
-      0b0111100,
-    ]),
-    b'2' => Some([
-      0b0111100,
-      0b1000010,
-      0b0000010,
-      0b0000100,
-      0b0001000,
-      0b0010000,
-      0b0100000,
-      0b1111110,
-    ]),
-    b'3' => Some([
-      0b0111100,
-      0b1000010,
-      0b0000010,
-      0b0011100,
-      0b0000010,
-      0b0000010,
-      0b1000010,
-      0b0111100,
-    ]),
-    b'4' => Some([
-      0b0000100,
-      0b0001100,
-      0b0010100,
-      0b0100100,
-      0b1000100,
-      0b1111110,
-      0b0000100,
-      0b0000100,
-    ]),
-    b'5' => Some([
-      0b1111110,
-      0b1000000,
-      0b1000000,
-      0b1111100,
-      0b0000010,
-      0b0000010,
-      0b1000010,
-      0b0111100,
-    ]),
-    b'6' => Some([
-      0b0111100,
-      0b1000010,
-      0b1000000,
-      0b1111100,
-      0b1000010,
-      0b1000010,
-      0b1000010,
-      0b0111100,
-    ]),
-    b'7' => Some([
-      0b1111110,
-      0b0000010,
-      0b0000100,
-      0b0001000,
-      0b0010000,
-      0b0010000,
-      0b0010000,
-      0b0010000,
-    ]),
-    b'8' => Some([
-      0b0111100,
-      0b1000010,
-      0b1000010,
-      0b0111100,
-      0b1000010,
-      0b1000010,
-      0b1000010,
-      0b0111100,
-    ]),
-    b'9' => Some([
-      0b0111100,
-      0b1000010,
-      0b1000010,
-      0b0111110,
-      0b0000010,
-      0b0000010,
-      0b1000010,
-      0b0111100,
-    ]),
-    b'F' => Some([
-      0b1111110,
-      0b1000000,
-      0b1000000,
-      0b1111100,
-      0b1000000,
-      0b1000000,
-      0b1000000,
-      0b1000000,
-    ]),
-    b'P' => Some([
-      0b1111100,
-      0b1000010,
-      0b1000010,
-      0b1111100,
-      0b1000000,
-      0b1000000,
-      0b1000000,
-      0b1000000,
-    ]),
-    b'S' => Some([
-      0b0111100,
-      0b1000010,
-      0b1000000,
-      0b0111100,
-      0b0000010,
-      0b0000010,
-      0b1000010,
-      0b0111100,
-    ]),
-    b':' => Some([
-      0b0000000,
-      0b0011000,
-      0b0011000,
-      0b0000000,
-      0b0000000,
-      0b0011000,
-      0b0011000,
-      0b0000000,
-    ]),
-    b'.' => Some([
-      0b0000000,
-      0b0000000,
-      0b0000000,
-      0b0000000,
-      0b0000000,
-      0b0000000,
-      0b0011000,
-      0b0011000,
-    ]),
-    b' ' => Some([
-      0b00000000,
-      0b00000000,
-      0b00000000,
-      0b00000000,
-      0b00000000,
-      0b00000000,
-      0b00000000,
-      0b00000000,
-    ]),
-    b'%' => Some([
-      0b1100001,
-      0b1100010,
-      0b0000100,
-      0b0001000,
-      0b0010000,
-      0b0100000,
-      0b1000011,
-      0b0000011,
-    ]),
-    _ => None,
+/// Returns when the event loop should wake up to check whether the next frame is due.
+fn get_next_frame_time(frame_pacing: FramePacing, last_update: Instant) -> Instant {
+  match frame_pacing {
+    FramePacing::WallClock => last_update + FRAME_TIME,
+    // The audio device drains the queue in chunks, so keep checking on it
+    FramePacing::Audio => Instant::now() + AUDIO_PACING_POLL_INTERVAL,
   }
 }
-
-/// Converts a winit key into a Gameboy button.
-fn convert_button(physical_key: &PhysicalKey) -> Option<Button> {
-  Some(match physical_key {
-    PhysicalKey::Code(KeyCode::KeyW | KeyCode::ArrowUp) => Button::Up,
-    PhysicalKey::Code(KeyCode::KeyS | KeyCode::ArrowDown) => Button::Down,
-    PhysicalKey::Code(KeyCode::KeyA | KeyCode::ArrowLeft) => Button::Left,
-    PhysicalKey::Code(KeyCode::KeyD | KeyCode::ArrowRight) => Button::Right,
-
-    PhysicalKey::Code(KeyCode::KeyZ) => Button::A,
-    PhysicalKey::Code(KeyCode::KeyX) => Button::B,
-
-    PhysicalKey::Code(KeyCode::Enter) => Button::Start,
-    PhysicalKey::Code(KeyCode::Backspace) => Button::Select,
-
-    _ => return None,
-  })
-}
-
-/// Computes the scale factor for the game.
-fn compute_scale_factor(window_width: u32, window_height: u32) -> f64 {
-  let scale_x = window_width as f64 / GAMEBOY_WIDTH as f64;
-  let scale_y = window_height as f64 / GAMEBOY_HEIGHT as f64;
-
-  scale_x.min(scale_y).max(1.0)
-}
-
-/// Returns the pixel width of the scaled text.
-fn get_text_pixel_width(text: &str, scale: u32) -> u32 {
-  (DEFAULT_CHARACTER_WIDTH * scale) * text.len() as u32
-}
-
-/// Returns the current volume of the APU as an integer.
-fn get_volume(apu: &Apu) -> u8 {
-  (apu.volume() * 100.0).round() as u8
-}
@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+
+use crate::emulator::Emulator;
+
+/// A ring buffer of recent save states, for stepping the emulator back in time.
+///
+/// Snapshots are save states, so they only hold the state that can change, like the RAM, VRAM,
+/// OAM and registers, without the ROM. Only the newest snapshot is kept whole. Every older
+/// snapshot is stored as the bytes that differ from the snapshot after it, since most of the
+/// state doesn't change between snapshots.
+#[derive(Debug, Clone)]
+pub struct RewindBuffer {
+  /// The newest snapshot, if any.
+  latest: Option<Vec<u8>>,
+  /// The differences that turn each snapshot into the one before it, from oldest to newest.
+  deltas: VecDeque<Delta>,
+  /// The maximum number of snapshots that are kept.
+  capacity: usize,
+  /// The maximum number of bytes that the snapshots take up, past which the oldest ones get
+  /// dropped early.
+  max_bytes: usize,
+  /// The number of bytes that the snapshots take up.
+  bytes: usize,
+  /// The number of frames between snapshots.
+  interval: u32,
+  /// The number of frames left until the next snapshot.
+  frames_until_snapshot: u32,
+}
+
+/// The differences between a snapshot and the one after it.
+#[derive(Debug, Clone)]
+struct Delta {
+  /// The length of the snapshot.
+  len: usize,
+  /// The runs of bytes that differ, as their offset and the bytes XORed with the bytes at
+  /// the same offset in the snapshot after it.
+  runs: Vec<(usize, Vec<u8>)>,
+}
+
+impl RewindBuffer {
+  /// Creates a new [`RewindBuffer`] that keeps the last `capacity` snapshots, taking one
+  /// every `interval` frames, while they take up at most `max_bytes`.
+  pub fn new(capacity: usize, interval: u32, max_bytes: usize) -> Self {
+    Self {
+      latest: None,
+      deltas: VecDeque::new(),
+      capacity,
+      max_bytes,
+      bytes: 0,
+      interval: interval.max(1),
+      frames_until_snapshot: 0,
+    }
+  }
+
+  /// Records the state of the emulator after it completed a frame, if a snapshot is due,
+  /// dropping the oldest snapshots if the buffer is full or over its memory limit.
+  pub fn record_frame(&mut self, emulator: &Emulator) {
+    if self.capacity == 0 {
+      return;
+    }
+
+    if self.frames_until_snapshot > 0 {
+      self.frames_until_snapshot -= 1;
+      return;
+    }
+
+    self.frames_until_snapshot = self.interval - 1;
+
+    let snapshot = emulator.save_state();
+
+    if let Some(previous) = self.latest.take() {
+      let delta = Delta::between(&previous, &snapshot);
+
+      self.bytes = self.bytes - previous.len() + delta.size();
+      self.deltas.push_back(delta);
+    }
+
+    self.bytes += snapshot.len();
+    self.latest = Some(snapshot);
+
+    // The newest snapshot is whole, so it counts towards the capacity too
+    while self.deltas.len() >= self.capacity
+      || (self.bytes > self.max_bytes && !self.deltas.is_empty())
+    {
+      self.drop_oldest();
+    }
+  }
+
+  /// Restores the emulator to the newest snapshot and drops it, so the next call goes back
+  /// further. Returns whether there was a snapshot to restore.
+  ///
+  /// The oldest snapshot is kept, so it keeps getting restored once everything else is gone.
+  pub fn rewind(&mut self, emulator: &mut Emulator) -> bool {
+    let Some(latest) = &mut self.latest else {
+      return false;
+    };
+
+    emulator
+      .load_state(latest)
+      .expect("snapshots should be made by the same emulator");
+
+    if let Some(delta) = self.deltas.pop_back() {
+      self.bytes -= delta.size() + latest.len();
+      delta.apply(latest);
+      self.bytes += latest.len();
+    }
+
+    // Start recording again from the restored state
+    self.frames_until_snapshot = 0;
+
+    true
+  }
+
+  /// Drops every snapshot, like when another game gets loaded.
+  pub fn clear(&mut self) {
+    self.latest = None;
+    self.deltas.clear();
+    self.bytes = 0;
+    self.frames_until_snapshot = 0;
+  }
+
+  /// Returns the number of snapshots in the buffer.
+  pub fn len(&self) -> usize {
+    self.latest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+  }
+
+  /// Returns whether the buffer has no snapshots.
+  pub fn is_empty(&self) -> bool {
+    self.latest.is_none()
+  }
+
+  /// Returns the number of bytes that the snapshots take up, which stays under the memory
+  /// limit unless the newest snapshot alone is over it.
+  pub fn memory_usage(&self) -> usize {
+    self.bytes
+  }
+
+  /// Drops the oldest snapshot.
+  fn drop_oldest(&mut self) {
+    if let Some(delta) = self.deltas.pop_front() {
+      self.bytes -= delta.size();
+    }
+  }
+}
+
+impl Delta {
+  /// Finds the differences that turn `next` back into `snapshot`.
+  fn between(snapshot: &[u8], next: &[u8]) -> Self {
+    // Only the bytes of the snapshot matter, since the rest gets truncated
+    let len = snapshot.len();
+    let xor = |index: usize| {
+      snapshot.get(index).copied().unwrap_or(0) ^ next.get(index).copied().unwrap_or(0)
+    };
+    let mut runs: Vec<(usize, Vec<u8>)> = Vec::new();
+    let mut index = 0;
+    let common_len = len.min(next.len());
+
+    while index < len {
+      // Most of the state doesn't change, so skip over whole chunks of equal bytes at once
+      let chunk_end = index + EQUAL_CHUNK_SIZE;
+
+      if chunk_end <= common_len && snapshot[index..chunk_end] == next[index..chunk_end] {
+        index = chunk_end;
+        continue;
+      }
+
+      if xor(index) == 0 {
+        index += 1;
+        continue;
+      }
+
+      let start = index;
+      let mut end = index;
+
+      // Extend the run over short gaps of equal bytes, which are cheaper to store than
+      // starting another run
+      while index < len && index - end <= MAX_RUN_GAP {
+        if xor(index) != 0 {
+          end = index + 1;
+        }
+
+        index += 1;
+      }
+
+      runs.push((start, (start..end).map(xor).collect()));
+      index = end;
+    }
+
+    Self {
+      len: snapshot.len(),
+      runs,
+    }
+  }
+
+  /// Returns the number of bytes that the delta takes up.
+  fn size(&self) -> usize {
+    let run_size = std::mem::size_of::<(usize, Vec<u8>)>();
+
+    self
+      .runs
+      .iter()
+      .map(|(_, bytes)| run_size + bytes.len())
+      .sum()
+  }
+
+  /// Turns the snapshot after this one back into the snapshot before it.
+  fn apply(&self, next: &mut Vec<u8>) {
+    next.resize(next.len().max(self.len), 0);
+
+    for (start, bytes) in &self.runs {
+      for (byte, xor) in next[*start..].iter_mut().zip(bytes) {
+        *byte ^= xor;
+      }
+    }
+
+    next.truncate(self.len);
+  }
+}
+
+/// The number of equal bytes that a run of differing bytes gets extended over.
+const MAX_RUN_GAP: usize = 16;
+/// The number of bytes that get compared at once when looking for the bytes that differ.
+const EQUAL_CHUNK_SIZE: usize = 64;
@@ -0,0 +1,40 @@
+use std::fmt;
+
+/// An error returned when a save state can't be loaded.
+#[derive(Debug)]
+pub enum SaveStateError {
+  /// The save state was made by a version of the emulator with a different state layout.
+  UnsupportedVersion(u32),
+  /// The save state couldn't be decoded.
+  InvalidData(bincode::Error),
+  /// The save state was made with a game whose header doesn't match the loaded game, which has
+  /// the title in the save state.
+  DifferentGame(String),
+}
+
+impl fmt::Display for SaveStateError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SaveStateError::UnsupportedVersion(version) => write!(
+        f,
+        "unsupported save state version {version}, expected {SAVE_STATE_VERSION}"
+      ),
+      SaveStateError::InvalidData(e) => write!(f, "invalid save state: {e}"),
+      SaveStateError::DifferentGame(title) => {
+        write!(f, "the save state was made with a different game ({title})")
+      }
+    }
+  }
+}
+
+impl std::error::Error for SaveStateError {}
+
+impl From<bincode::Error> for SaveStateError {
+  fn from(e: bincode::Error) -> Self {
+    SaveStateError::InvalidData(e)
+  }
+}
+
+/// The version of the save state layout, which needs to be bumped whenever the serialized
+/// state changes.
+pub const SAVE_STATE_VERSION: u32 = 16;
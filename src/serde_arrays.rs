@@ -0,0 +1,153 @@
+//! Serializes the big arrays in the emulator's state as byte strings.
+//!
+//! Deserializing arrays element by element takes hundreds of kilobytes of stack in debug
+//! builds, which is enough to overflow the stack when loading a save state.
+
+/// Serializes a byte array.
+pub mod bytes {
+  use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+  pub fn serialize<S: Serializer, const N: usize>(
+    bytes: &[u8; N],
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bytes(bytes)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+    deserializer: D,
+  ) -> Result<[u8; N], D::Error> {
+    let bytes = Vec::<u8>::deserialize(deserializer)?;
+
+    bytes
+      .try_into()
+      .map_err(|bytes: Vec<u8>| D::Error::invalid_length(bytes.len(), &"a byte array"))
+  }
+}
+
+/// Serializes an optional byte array.
+pub mod optional_bytes {
+  use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+  pub fn serialize<S: Serializer, const N: usize>(
+    bytes: &Option<[u8; N]>,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    bytes
+      .as_ref()
+      .map(|bytes| Bytes(bytes))
+      .serialize(serializer)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+    deserializer: D,
+  ) -> Result<Option<[u8; N]>, D::Error> {
+    Option::<Vec<u8>>::deserialize(deserializer)?
+      .map(|bytes| {
+        bytes
+          .try_into()
+          .map_err(|bytes: Vec<u8>| D::Error::invalid_length(bytes.len(), &"a byte array"))
+      })
+      .transpose()
+  }
+
+  /// Serializes a byte slice as a byte string, rather than as a sequence.
+  struct Bytes<'a>(&'a [u8]);
+
+  impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+      serializer.serialize_bytes(self.0)
+    }
+  }
+}
+
+/// Serializes a boxed byte array.
+pub mod boxed_bytes {
+  use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+  pub fn serialize<S: Serializer, const N: usize>(
+    bytes: &[u8; N],
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    super::bytes::serialize(bytes, serializer)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+    deserializer: D,
+  ) -> Result<Box<[u8; N]>, D::Error> {
+    let bytes = Vec::<u8>::deserialize(deserializer)?.into_boxed_slice();
+
+    // Convert the boxed slice in place, so the array never ends up on the stack
+    bytes
+      .try_into()
+      .map_err(|bytes: Box<[u8]>| D::Error::invalid_length(bytes.len(), &"a byte array"))
+  }
+}
+
+/// Serializes a boxed [`Frame`], row after row.
+pub mod frame {
+  use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+  use crate::hardware::ppu::Frame;
+
+  pub fn serialize<S: Serializer>(frame: &Frame, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bytes(frame.as_flattened())
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Box<Frame>, D::Error> {
+    let bytes = Vec::<u8>::deserialize(deserializer)?;
+    let mut frame = Box::new([[0; 160]; 144]);
+
+    if bytes.len() != frame.as_flattened().len() {
+      return Err(D::Error::invalid_length(
+        bytes.len(),
+        &"a frame of 160x144 shades",
+      ));
+    }
+
+    frame.as_flattened_mut().copy_from_slice(&bytes);
+
+    Ok(frame)
+  }
+}
+
+/// Serializes a boxed [`ColorFrame`], row after row, as little-endian colors.
+pub mod color_frame {
+  use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+  use crate::hardware::ppu::ColorFrame;
+
+  pub fn serialize<S: Serializer>(frame: &ColorFrame, serializer: S) -> Result<S::Ok, S::Error> {
+    let bytes: Vec<u8> = frame
+      .as_flattened()
+      .iter()
+      .flat_map(|color| color.to_le_bytes())
+      .collect();
+
+    serializer.serialize_bytes(&bytes)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Box<ColorFrame>, D::Error> {
+    let bytes = Vec::<u8>::deserialize(deserializer)?;
+    let mut frame = Box::new([[0; 160]; 144]);
+
+    if bytes.len() != frame.as_flattened().len() * 2 {
+      return Err(D::Error::invalid_length(
+        bytes.len(),
+        &"a frame of 160x144 colors",
+      ));
+    }
+
+    for (color, bytes) in frame
+      .as_flattened_mut()
+      .iter_mut()
+      .zip(bytes.chunks_exact(2))
+    {
+      *color = u16::from_le_bytes([bytes[0], bytes[1]]);
+    }
+
+    Ok(frame)
+  }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+
+use crate::emulator::Emulator;
+
+/// The result of running a test ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestRomResult {
+  /// The test ROM reported that it passed.
+  Passed,
+  /// The test ROM reported that it failed.
+  Failed,
+  /// The test ROM didn't report a result before running out of cycles.
+  TimedOut,
+}
+
+impl fmt::Display for TestRomResult {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      TestRomResult::Passed => "passed",
+      TestRomResult::Failed => "failed",
+      TestRomResult::TimedOut => "timed out",
+    })
+  }
+}
+
+/// Runs a test ROM for up to `max_cycles` T-cycles, until it reports a result.
+///
+/// Blargg's test ROMs report by printing `Passed` or `Failed` over serial. Mooneye's test ROMs
+/// execute `LD B,B` as a breakpoint, with the Fibonacci numbers in `B`, `C`, `D`, `E`, `H` and
+/// `L` when they pass, or `0x42` in all of them when they fail.
+pub fn run_test_rom(emulator: &mut Emulator, max_cycles: usize) -> TestRomResult {
+  let mut remaining_cycles = max_cycles;
+  let mut checked_serial_len = 0;
+  let mut result = None;
+
+  while remaining_cycles > 0 {
+    emulator.step_until(|emulator| {
+      remaining_cycles -= 1;
+      result = check_serial_output(emulator, &mut checked_serial_len)
+        .or_else(|| check_mooneye_breakpoint(emulator));
+
+      result.is_some() || remaining_cycles == 0
+    });
+
+    if let Some(result) = result {
+      return result;
+    }
+  }
+
+  TestRomResult::TimedOut
+}
+
+/// Checks whether a Blargg test ROM printed its result over serial.
+fn check_serial_output(emulator: &Emulator, checked_len: &mut usize) -> Option<TestRomResult> {
+  let bytes_sent = emulator.hardware.serial_bytes_sent();
+
+  // Searching the whole output on every T-cycle would be slow, so only search new output
+  if bytes_sent == *checked_len {
+    return None;
+  }
+
+  *checked_len = bytes_sent;
+
+  let output = String::from_utf8_lossy(emulator.hardware.serial_output());
+
+  if output.contains("Passed") {
+    Some(TestRomResult::Passed)
+  } else if output.contains("Failed") {
+    Some(TestRomResult::Failed)
+  } else {
+    None
+  }
+}
+
+/// Checks whether a Mooneye test ROM hit its `LD B,B` breakpoint.
+fn check_mooneye_breakpoint(emulator: &Emulator) -> Option<TestRomResult> {
+  let cpu = emulator.cpu();
+
+  if !cpu.fetched_instruction() || cpu.registers.ir != LD_B_B {
+    return None;
+  }
+
+  let registers = &cpu.registers;
+  let values = [
+    registers.b,
+    registers.c,
+    registers.d,
+    registers.e,
+    registers.h,
+    registers.l,
+  ];
+
+  if values == MOONEYE_PASS_VALUES {
+    Some(TestRomResult::Passed)
+  } else if values == [MOONEYE_FAIL_VALUE; 6] {
+    Some(TestRomResult::Failed)
+  } else {
+    None
+  }
+}
+
+/// The opcode of `LD B,B`, which Mooneye's test ROMs use as a breakpoint.
+const LD_B_B: u8 = 0x40;
+/// The values in `B`, `C`, `D`, `E`, `H` and `L` when a Mooneye test ROM passes.
+const MOONEYE_PASS_VALUES: [u8; 6] = [3, 5, 8, 13, 21, 34];
+/// The value in every register when a Mooneye test ROM fails.
+const MOONEYE_FAIL_VALUE: u8 = 0x42;
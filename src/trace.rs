@@ -0,0 +1,107 @@
+use std::{
+  collections::VecDeque,
+  fmt,
+  io::{self, Write},
+};
+
+use crate::hardware::{Cpu, Hardware};
+
+/// A fixed-size ring buffer of the last instructions that the CPU executed, for looking into
+/// what led up to a crash.
+#[derive(Debug, Clone)]
+pub struct InstructionTrace {
+  /// The traced instructions, from oldest to newest.
+  entries: VecDeque<TraceEntry>,
+  /// The maximum number of instructions that are kept.
+  capacity: usize,
+}
+
+/// An instruction that the CPU executed.
+///
+/// Instructions are traced once their opcode is fetched, so the newest entry is the
+/// instruction that was executing when the trace was read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+  /// The address of the instruction.
+  pub pc: u16,
+  /// The opcode of the instruction.
+  pub opcode: u8,
+}
+
+impl InstructionTrace {
+  /// Creates a new [`InstructionTrace`] that keeps the last `capacity` instructions.
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      entries: VecDeque::with_capacity(capacity),
+      capacity,
+    }
+  }
+
+  /// Records an instruction, dropping the oldest one if the trace is full.
+  pub fn push(&mut self, entry: TraceEntry) {
+    if self.capacity == 0 {
+      return;
+    }
+
+    if self.entries.len() == self.capacity {
+      self.entries.pop_front();
+    }
+
+    self.entries.push_back(entry);
+  }
+
+  /// Returns the traced instructions, from oldest to newest.
+  pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+    self.entries.iter()
+  }
+}
+
+impl fmt::Display for InstructionTrace {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for entry in &self.entries {
+      writeln!(f, "{entry}")?;
+    }
+
+    Ok(())
+  }
+}
+
+impl fmt::Display for TraceEntry {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:04X}: {:02X}", self.pc, self.opcode)
+  }
+}
+
+/// Writes the state of the CPU before the next instruction in the format of Gameboy Doctor's
+/// logs, followed by the disassembled instruction after a `;`.
+///
+/// This is meant to be called from an [`Emulator::set_instruction_hook`] callback, where the
+/// opcode of the next instruction was already fetched.
+///
+/// [`Emulator::set_instruction_hook`]: crate::emulator::Emulator::set_instruction_hook
+pub fn write_doctor_line(out: &mut impl Write, cpu: &Cpu, hardware: &Hardware) -> io::Result<()> {
+  let registers = &cpu.registers;
+  // The opcode was already fetched, so the program counter is past it
+  let pc = registers.pc.wrapping_sub(1);
+  let pc_memory = [0, 1, 2, 3].map(|offset| hardware.read_byte(pc.wrapping_add(offset)));
+  let (_, mnemonic, _) = hardware.disassemble(pc);
+
+  writeln!(
+    out,
+    "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{pc:04X} \
+     PCMEM:{:02X},{:02X},{:02X},{:02X} ; {mnemonic}",
+    registers.a,
+    cpu.flags(),
+    registers.b,
+    registers.c,
+    registers.d,
+    registers.e,
+    registers.h,
+    registers.l,
+    registers.sp,
+    pc_memory[0],
+    pc_memory[1],
+    pc_memory[2],
+    pc_memory[3],
+  )
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+
+use crate::hardware::{Cpu, Hardware, registers::Registers};
+
+/// Detects a stuck CPU, which keeps executing instructions without changing any registers.
+///
+/// Idle loops that wait on an interrupt, like `JR -2`, also leave the registers unchanged
+/// until the interrupt is dispatched, so the threshold should be well above how long a game
+/// waits for one.
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+  /// The number of instructions in a row that need to leave the CPU unchanged.
+  threshold: usize,
+  /// The registers and flags after the last instruction.
+  last_state: Option<(Registers, u8)>,
+  /// The number of instructions in a row that left the CPU unchanged.
+  unchanged_instructions: usize,
+  /// A report of the stuck CPU that wasn't taken yet.
+  report: Option<StuckCpuReport>,
+}
+
+/// A report of where the CPU got stuck.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StuckCpuReport {
+  /// The program counter that the CPU is stuck at.
+  pub pc: u16,
+  /// The address of the first byte in `bytes`.
+  pub bytes_start: u16,
+  /// The bytes in memory around the program counter.
+  pub bytes: [u8; SURROUNDING_BYTES],
+  /// The number of instructions that left the CPU unchanged.
+  pub instructions: usize,
+}
+
+impl Watchdog {
+  /// Creates a new [`Watchdog`] that reports the CPU after `threshold` instructions in a row
+  /// leave it unchanged.
+  pub fn new(threshold: usize) -> Self {
+    Self {
+      threshold,
+      last_state: None,
+      unchanged_instructions: 0,
+      report: None,
+    }
+  }
+
+  /// Returns the number of unchanged instructions in a row before the CPU gets reported.
+  pub fn threshold(&self) -> usize {
+    self.threshold
+  }
+
+  /// Checks the state of the CPU after it finished an instruction.
+  pub fn check(&mut self, cpu: &Cpu, hardware: &Hardware) {
+    let state = (cpu.registers.clone(), cpu.flags());
+
+    if self.last_state.as_ref() != Some(&state) {
+      self.last_state = Some(state);
+      self.unchanged_instructions = 0;
+
+      return;
+    }
+
+    self.unchanged_instructions += 1;
+
+    // Only report the CPU once per time it gets stuck
+    if self.unchanged_instructions == self.threshold {
+      // The opcode was already fetched, so the program counter is past it
+      let pc = cpu.registers.pc.wrapping_sub(1);
+      let bytes_start = pc.wrapping_sub(SURROUNDING_BYTES as u16 / 2);
+      let bytes = std::array::from_fn(|i| hardware.read_byte(bytes_start.wrapping_add(i as u16)));
+
+      self.report = Some(StuckCpuReport {
+        pc,
+        bytes_start,
+        bytes,
+        instructions: self.unchanged_instructions,
+      });
+    }
+  }
+
+  /// Returns the report of a stuck CPU, if one happened since the last call.
+  pub fn take_report(&mut self) -> Option<StuckCpuReport> {
+    self.report.take()
+  }
+}
+
+impl fmt::Display for StuckCpuReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "CPU stuck at PC {:04X} for {} instructions, memory from {:04X}:",
+      self.pc, self.instructions, self.bytes_start
+    )?;
+
+    for byte in self.bytes {
+      write!(f, " {byte:02X}")?;
+    }
+
+    Ok(())
+  }
+}
+
+/// The number of bytes around the program counter to include in a [`StuckCpuReport`].
+const SURROUNDING_BYTES: usize = 8;
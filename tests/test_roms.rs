@@ -0,0 +1,71 @@
+//! Runs every test ROM in `tests/roms`, or in the directory set by `GAMEBOY_TEST_ROMS`, and
+//! checks that they pass. The ROMs can't be distributed with the emulator, so the test does
+//! nothing when the directory doesn't exist.
+
+use std::{
+  env, fs,
+  path::{Path, PathBuf},
+};
+
+use gameboy_emu::{
+  emulator::{CYCLES_PER_FRAME, Emulator},
+  hardware::{Cpu, Hardware},
+  test_rom::{self, TestRomResult},
+};
+
+/// The number of frames that each test ROM gets to report a result, which is about a minute.
+const TEST_ROM_FRAMES: usize = 3600;
+
+#[test]
+fn test_roms_pass() {
+  let directory = env::var_os("GAMEBOY_TEST_ROMS")
+    .map(PathBuf::from)
+    .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/roms"));
+  let Ok(entries) = fs::read_dir(&directory) else {
+    eprintln!(
+      "Skipping the test ROMs, since {} doesn't exist",
+      directory.display()
+    );
+    return;
+  };
+
+  let mut roms: Vec<PathBuf> = entries
+    .filter_map(|entry| Some(entry.ok()?.path()))
+    .filter(|path| {
+      path
+        .extension()
+        .is_some_and(|ext| ext == "gb" || ext == "gbc")
+    })
+    .collect();
+
+  roms.sort();
+
+  let failures: Vec<String> = roms
+    .iter()
+    .filter_map(|path| match run_rom(path) {
+      Ok(TestRomResult::Passed) => None,
+      Ok(result) => Some(format!("{}: {result}", path.display())),
+      Err(e) => Some(format!("{}: {e}", path.display())),
+    })
+    .collect();
+
+  assert!(
+    failures.is_empty(),
+    "{} of {} test ROMs didn't pass:\n{}",
+    failures.len(),
+    roms.len(),
+    failures.join("\n")
+  );
+}
+
+/// Runs the test ROM at the path until it reports a result or runs out of time.
+fn run_rom(path: &Path) -> Result<TestRomResult, String> {
+  let bytes = fs::read(path).map_err(|e| e.to_string())?;
+  let hardware = Hardware::new(bytes).map_err(|e| e.to_string())?;
+  let mut emulator = Emulator::new(Cpu::with_register_defaults(), hardware);
+
+  Ok(test_rom::run_test_rom(
+    &mut emulator,
+    TEST_ROM_FRAMES * CYCLES_PER_FRAME,
+  ))
+}